@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost::Message;
+use protobuf::test_messages::proto3::TestAllTypesProto3;
+
+fuzz_target!(|data: &[u8]| {
+    let mut message = TestAllTypesProto3::default();
+    if message.merge(data).is_err() {
+        return;
+    }
+
+    let buf = message.encode_to_vec();
+    assert_eq!(buf.len(), message.encoded_len());
+
+    // Compare the encoded bytes rather than the messages, since floating point fields may be NaN.
+    let roundtrip =
+        TestAllTypesProto3::decode(&*buf).expect("failed to decode the re-encoded message");
+    assert_eq!(roundtrip.encode_to_vec(), buf);
+});
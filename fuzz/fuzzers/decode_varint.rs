@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost::encoding::{decode_varint, encode_varint, encoded_len_varint};
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = data;
+    let value = match decode_varint(&mut buf) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+
+    // Varints may be encoded with redundant trailing zero groups, so the canonical encoding is
+    // at most as long as the input.
+    let consumed = data.len() - buf.len();
+    assert!(encoded_len_varint(value) <= consumed);
+
+    let mut encoded = Vec::new();
+    encode_varint(value, &mut encoded);
+    assert_eq!(encoded.len(), encoded_len_varint(value));
+    assert_eq!(decode_varint(&mut &*encoded).unwrap(), value);
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use prost::encoding::{decode_key, skip_field, DecodeContext};
+
+fuzz_target!(|data: &[u8]| {
+    let mut buf = data;
+    while !buf.is_empty() {
+        let len = buf.len();
+        let (tag, wire_type) = match decode_key(&mut buf) {
+            Ok(key) => key,
+            Err(_) => return,
+        };
+        if skip_field(wire_type, tag, &mut buf, DecodeContext::default()).is_err() {
+            return;
+        }
+        assert!(buf.len() < len);
+    }
+});
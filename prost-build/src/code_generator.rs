@@ -29,6 +29,7 @@ enum Syntax {
 pub struct CodeGenerator<'a> {
     config: &'a mut Config,
     package: String,
+    file_package: String,
     source_info: SourceCodeInfo,
     syntax: Syntax,
     message_graph: &'a MessageGraph,
@@ -63,9 +64,11 @@ impl<'a> CodeGenerator<'a> {
             Some(s) => panic!("unknown syntax: {}", s),
         };
 
+        let package = file.package.unwrap();
         let mut code_gen = CodeGenerator {
             config,
-            package: file.package.unwrap(),
+            file_package: package.clone(),
+            package,
             source_info,
             syntax,
             message_graph,
@@ -170,11 +173,55 @@ impl<'a> CodeGenerator<'a> {
                 }
             });
 
+        // The fields compared by `semantic_eq`, and whether each is compared as a set.
+        let mut eq_fields: Vec<(String, bool)> = fields
+            .iter()
+            .map(|&(ref field, _)| {
+                let is_map = field
+                    .type_name
+                    .as_ref()
+                    .map_or(false, |type_name| map_types.contains_key(type_name));
+                let is_set = field.label == Some(Label::Repeated as i32)
+                    && !is_map
+                    && self
+                        .config
+                        .set_semantics
+                        .get_field(&fq_message_name, field.name())
+                        .is_some();
+                (to_snake(field.name()), is_set)
+            })
+            .collect();
+
+        // The fields of the borrowed view, which leaves out map and group fields.
+        let view_fields: Vec<FieldDescriptorProto> =
+            if self.config.borrowed_views.get(&fq_message_name).is_some() {
+                fields
+                    .iter()
+                    .map(|&(ref field, _)| field)
+                    .filter(|field| {
+                        field.r#type() != Type::Group
+                            && !field
+                                .type_name
+                                .as_ref()
+                                .map_or(false, |type_name| map_types.contains_key(type_name))
+                    })
+                    .cloned()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+
         self.append_doc(&fq_message_name, None);
         self.append_type_attributes(&fq_message_name);
         self.push_indent();
         self.buf
             .push_str("#[derive(Clone, PartialEq, ::prost::Message)]\n");
+        if let Some(well_known_type) = well_known_json_type(&fq_message_name) {
+            let attribute = format!("#[prost(well_known = \"{}\")]\n", well_known_type);
+            self.push_indent();
+            self.buf.push_str(&attribute);
+        }
+        self.append_derive_attributes(&fq_message_name);
         self.push_indent();
         self.buf.push_str("pub struct ");
         self.buf.push_str(&to_upper_camel(&message_name));
@@ -213,10 +260,38 @@ impl<'a> CodeGenerator<'a> {
         }
         self.path.pop();
 
+        if self.config.unknown_fields.get(&fq_message_name).is_some() {
+            self.push_indent();
+            self.buf.push_str("#[prost(unknown_fields)]\n");
+            self.push_indent();
+            self.buf
+                .push_str("pub unknown_fields: ::prost::UnknownFields,\n");
+        }
+
         self.depth -= 1;
         self.push_indent();
         self.buf.push_str("}\n");
 
+        if self.config.type_names {
+            self.append_type_name(&message_name, &fq_message_name);
+        }
+
+        if eq_fields.iter().any(|&(_, is_set)| is_set) {
+            for (idx, oneof) in message.oneof_decl.iter().enumerate() {
+                if oneof_fields.get_vec(&(idx as i32)).is_some() {
+                    eq_fields.push((to_snake(oneof.name()), false));
+                }
+            }
+            if self.config.unknown_fields.get(&fq_message_name).is_some() {
+                eq_fields.push(("unknown_fields".to_string(), false));
+            }
+            self.append_semantic_eq(&message_name, &eq_fields);
+        }
+
+        if self.config.borrowed_views.get(&fq_message_name).is_some() {
+            self.append_view(&message_name, &fq_message_name, &view_fields);
+        }
+
         if !message.enum_type.is_empty() || !nested_types.is_empty() || !oneof_fields.is_empty() {
             self.push_mod(&message_name);
             self.path.push(3);
@@ -249,6 +324,284 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    fn append_type_name(&mut self, message_name: &str, fq_message_name: &str) {
+        // The name includes any enclosing messages, which follow the package in the fully
+        // qualified name.
+        let name = &fq_message_name[self.file_package.len() + 2..];
+
+        self.push_indent();
+        self.buf.push_str("impl ::prost::Name for ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+
+        self.push_indent();
+        self.buf
+            .push_str(&format!("const NAME: &'static str = \"{}\";\n", name));
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "const PACKAGE: &'static str = \"{}\";\n",
+            self.file_package
+        ));
+
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_semantic_eq(&mut self, message_name: &str, fields: &[(String, bool)]) {
+        self.push_indent();
+        self.buf.push_str("impl ");
+        self.buf.push_str(&to_upper_camel(message_name));
+        self.buf.push_str(" {\n");
+        self.depth += 1;
+
+        self.push_indent();
+        self.buf.push_str(
+            "/// Compares the messages like `PartialEq`, except that repeated fields with set\n",
+        );
+        self.push_indent();
+        self.buf
+            .push_str("/// semantics are compared regardless of the order of their elements.\n");
+        self.push_indent();
+        self.buf
+            .push_str("pub fn semantic_eq(&self, other: &Self) -> bool {\n");
+        self.depth += 1;
+
+        for (idx, &(ref field, is_set)) in fields.iter().enumerate() {
+            self.push_indent();
+            if idx > 0 {
+                self.buf.push_str("&& ");
+            }
+            if is_set {
+                self.buf.push_str(&format!(
+                    "::prost::encoding::set_eq(&self.{0}, &other.{0})\n",
+                    field
+                ));
+            } else {
+                self.buf
+                    .push_str(&format!("self.{0} == other.{0}\n", field));
+            }
+        }
+
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    fn append_view(
+        &mut self,
+        message_name: &str,
+        fq_message_name: &str,
+        fields: &[FieldDescriptorProto],
+    ) {
+        let view_name = format!("{}Ref", to_upper_camel(message_name));
+        // The lifetime must be used even if no field borrows from the buffer.
+        let needs_marker = fields
+            .iter()
+            .all(|field| !matches!(field.r#type(), Type::String | Type::Bytes | Type::Message));
+
+        self.push_indent();
+        self.buf.push_str(&format!(
+            "/// A view of a [`{}`] message which borrows its string, bytes and message fields.\n",
+            to_upper_camel(message_name)
+        ));
+        self.push_indent();
+        self.buf
+            .push_str("#[derive(Clone, Debug, Default, PartialEq)]\n");
+        self.push_indent();
+        self.buf
+            .push_str(&format!("pub struct {}<'a> {{\n", view_name));
+        self.depth += 1;
+        for field in fields {
+            let ty = self.view_field_type(field, fq_message_name);
+            self.push_indent();
+            self.buf
+                .push_str(&format!("pub {}: {},\n", to_snake(field.name()), ty));
+        }
+        if needs_marker {
+            self.push_indent();
+            self.buf
+                .push_str("_buf: ::core::marker::PhantomData<&'a [u8]>,\n");
+        }
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        self.push_indent();
+        self.buf
+            .push_str(&format!("impl<'a> {}<'a> {{\n", view_name));
+        self.depth += 1;
+
+        self.push_indent();
+        self.buf
+            .push_str("/// Decodes a view of the message from `buf`.\n");
+        self.push_indent();
+        self.buf.push_str(
+            "pub fn parse(buf: &'a [u8]) -> ::core::result::Result<Self, ::prost::DecodeError> {\n",
+        );
+        self.depth += 1;
+        self.push_indent();
+        self.buf.push_str(
+            "Self::parse_with_context(buf, ::prost::encoding::DecodeContext::default())\n",
+        );
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        self.push_indent();
+        self.buf.push_str(
+            "/// Decodes a view of the message from `buf`, with the limits and options of `ctx`.\n",
+        );
+        self.push_indent();
+        self.buf.push_str(
+            "pub fn parse_with_context(mut buf: &'a [u8], ctx: ::prost::encoding::DecodeContext) \
+             -> ::core::result::Result<Self, ::prost::DecodeError> {\n",
+        );
+        self.depth += 1;
+        self.push_indent();
+        if fields.is_empty() {
+            self.buf.push_str("let view = Self::default();\n");
+        } else {
+            self.buf.push_str("let mut view = Self::default();\n");
+        }
+        self.push_indent();
+        self.buf.push_str("while !buf.is_empty() {\n");
+        self.depth += 1;
+        self.push_indent();
+        self.buf
+            .push_str("let (tag, wire_type) = ctx.decode_key(&mut buf)?;\n");
+        self.push_indent();
+        self.buf.push_str("match tag {\n");
+        self.depth += 1;
+        for field in fields {
+            let merge = self.view_field_merge(field, fq_message_name);
+            self.push_indent();
+            self.buf
+                .push_str(&format!("{} => {},\n", field.number(), merge));
+        }
+        self.push_indent();
+        self.buf.push_str(
+            "_ => ::prost::encoding::skip_field(wire_type, tag, &mut buf, ctx.clone())?,\n",
+        );
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+        self.push_indent();
+        self.buf.push_str("::core::result::Result::Ok(view)\n");
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+
+        self.depth -= 1;
+        self.push_indent();
+        self.buf.push_str("}\n");
+    }
+
+    /// Returns the type of a field in a borrowed view.
+    fn view_field_type(&self, field: &FieldDescriptorProto, fq_message_name: &str) -> String {
+        let ty = match field.r#type() {
+            Type::String => "&'a str".to_string(),
+            Type::Bytes => "&'a [u8]".to_string(),
+            Type::Message if self.has_view(field.type_name()) => {
+                let ty = format!("{}Ref<'a>", self.resolve_ident(field.type_name()));
+                if self.view_boxed(field, fq_message_name) {
+                    format!("::prost::alloc::boxed::Box<{}>", ty)
+                } else {
+                    ty
+                }
+            }
+            Type::Message => "&'a [u8]".to_string(),
+            _ => self.resolve_type(field, fq_message_name),
+        };
+        if field.label() == Label::Repeated {
+            format!("::prost::alloc::vec::Vec<{}>", ty)
+        } else if self.optional(field) {
+            format!("::core::option::Option<{}>", ty)
+        } else {
+            ty
+        }
+    }
+
+    /// Returns the expression which merges an occurrence of a field into a borrowed view.
+    fn view_field_merge(&self, field: &FieldDescriptorProto, fq_message_name: &str) -> String {
+        let name = to_snake(field.name());
+        let value = match field.r#type() {
+            Type::String => "::prost::encoding::view::str(wire_type, &mut buf, &ctx)?".to_string(),
+            Type::Bytes => "::prost::encoding::view::bytes(wire_type, &mut buf, &ctx)?".to_string(),
+            Type::Message if self.has_view(field.type_name()) => {
+                let value = format!(
+                    "::prost::encoding::view::message(wire_type, &mut buf, &ctx, \
+                     {}Ref::parse_with_context)?",
+                    self.resolve_ident(field.type_name())
+                );
+                if self.view_boxed(field, fq_message_name) {
+                    format!("::prost::alloc::boxed::Box::new({})", value)
+                } else {
+                    value
+                }
+            }
+            Type::Message => {
+                "::prost::encoding::view::bytes(wire_type, &mut buf, &ctx)?".to_string()
+            }
+            _ => {
+                // Enumerations are decoded as their `i32` values.
+                let module = match field.r#type() {
+                    Type::Enum => Cow::Borrowed("int32"),
+                    _ => self.field_type_tag(field),
+                };
+                return if field.label() == Label::Repeated {
+                    format!(
+                        "::prost::encoding::{}::merge_repeated(wire_type, &mut view.{}, \
+                         &mut buf, ctx.clone())?",
+                        module, name
+                    )
+                } else if self.optional(field) {
+                    format!(
+                        "::prost::encoding::{}::merge(wire_type, \
+                         view.{}.get_or_insert_with(::core::default::Default::default), \
+                         &mut buf, ctx.clone())?",
+                        module, name
+                    )
+                } else {
+                    format!(
+                        "::prost::encoding::{}::merge(wire_type, &mut view.{}, &mut buf, \
+                         ctx.clone())?",
+                        module, name
+                    )
+                };
+            }
+        };
+        if field.label() == Label::Repeated {
+            format!("view.{}.push({})", name, value)
+        } else if self.optional(field) {
+            format!("view.{} = ::core::option::Option::Some({})", name, value)
+        } else {
+            format!("view.{} = {}", name, value)
+        }
+    }
+
+    /// Returns `true` if a borrowed view is generated for the message type.
+    fn has_view(&self, type_name: &str) -> bool {
+        self.extern_paths.resolve_ident(type_name).is_none()
+            && self.config.borrowed_views.get(type_name).is_some()
+    }
+
+    /// Returns `true` if the view of a singular message field must be boxed, because the nested
+    /// view contains the view of the enclosing message.
+    fn view_boxed(&self, field: &FieldDescriptorProto, fq_message_name: &str) -> bool {
+        field.label() != Label::Repeated
+            && self
+                .message_graph
+                .is_nested(field.type_name(), fq_message_name)
+    }
+
     fn append_type_attributes(&mut self, fq_message_name: &str) {
         assert_eq!(b'.', fq_message_name.as_bytes()[0]);
         // TODO: this clone is dirty, but expedious.
@@ -259,6 +612,31 @@ impl<'a> CodeGenerator<'a> {
         }
     }
 
+    /// Appends the `prost` attributes requesting the JSON, text format and reflection impls of a
+    /// message, or of one of its oneofs. These must follow the derive, unlike type attributes.
+    fn append_derive_attributes(&mut self, fq_message_name: &str) {
+        let derives = [
+            (&self.config.json, "json", "json"),
+            (&self.config.text_format, "text_format", "text-format"),
+            (&self.config.reflect, "reflect", "reflect"),
+        ];
+        let attributes: Vec<String> = derives
+            .iter()
+            .filter(|(paths, _, _)| paths.get(fq_message_name).is_some())
+            .map(|&(_, derive, feature)| {
+                if self.config.feature_gated_derives {
+                    format!("#[cfg_attr(feature = {:?}, prost({}))]\n", feature, derive)
+                } else {
+                    format!("#[prost({})]\n", derive)
+                }
+            })
+            .collect();
+        for attribute in attributes {
+            self.push_indent();
+            self.buf.push_str(&attribute);
+        }
+    }
+
     fn append_field_attributes(&mut self, fq_message_name: &str, field_name: &str) {
         assert_eq!(b'.', fq_message_name.as_bytes()[0]);
         // TODO: this clone is dirty, but expedious.
@@ -512,6 +890,7 @@ impl<'a> CodeGenerator<'a> {
         self.push_indent();
         self.buf
             .push_str("#[derive(Clone, PartialEq, ::prost::Oneof)]\n");
+        self.append_derive_attributes(fq_message_name);
         self.push_indent();
         self.buf.push_str("pub enum ");
         self.buf.push_str(&to_upper_camel(oneof.name()));
@@ -651,6 +1030,9 @@ impl<'a> CodeGenerator<'a> {
     ) {
         self.append_doc(fq_enum_name, Some(value.name()));
         self.append_field_attributes(fq_enum_name, &value.name());
+        let attribute = format!("#[prost(name = \"{}\")]\n", value.name());
+        self.push_indent();
+        self.buf.push_str(&attribute);
         self.push_indent();
         let name = to_upper_camel(value.name());
         let name_unprefixed = match prefix_to_strip {
@@ -868,6 +1250,17 @@ impl<'a> CodeGenerator<'a> {
     }
 }
 
+/// Returns the name of the well-known type with a special JSON representation, if the message is
+/// one.
+fn well_known_json_type(fq_message_name: &str) -> Option<&'static str> {
+    match fq_message_name {
+        ".google.protobuf.Duration" => Some("Duration"),
+        ".google.protobuf.Timestamp" => Some("Timestamp"),
+        ".google.protobuf.FieldMask" => Some("FieldMask"),
+        _ => None,
+    }
+}
+
 /// Returns `true` if the repeated field type can be packed.
 fn can_pack(field: &FieldDescriptorProto) -> bool {
     matches!(
@@ -1026,6 +1419,7 @@ impl MapType {
         match self {
             MapType::HashMap => "map",
             MapType::BTreeMap => "btree_map",
+            MapType::IndexMap => "index_map",
         }
     }
 
@@ -1034,6 +1428,7 @@ impl MapType {
         match self {
             MapType::HashMap => "::std::collections::HashMap",
             MapType::BTreeMap => "::prost::alloc::collections::BTreeMap",
+            MapType::IndexMap => "::prost::indexmap::IndexMap",
         }
     }
 }
@@ -193,6 +193,9 @@ enum MapType {
     HashMap,
     /// The [`std::collections::BTreeMap`] type.
     BTreeMap,
+    /// The `indexmap::IndexMap` type, re-exported by `prost` when its `indexmap` feature is
+    /// enabled.
+    IndexMap,
 }
 
 impl Default for MapType {
@@ -255,6 +258,14 @@ pub struct Config {
     extern_paths: Vec<(String, String)>,
     protoc_args: Vec<OsString>,
     disable_comments: PathMap<()>,
+    unknown_fields: PathMap<()>,
+    set_semantics: PathMap<()>,
+    borrowed_views: PathMap<()>,
+    json: PathMap<()>,
+    text_format: PathMap<()>,
+    reflect: PathMap<()>,
+    feature_gated_derives: bool,
+    type_names: bool,
 }
 
 impl Config {
@@ -324,6 +335,39 @@ impl Config {
         self
     }
 
+    /// Configure the code generator to generate Rust [`IndexMap`][1] fields for Protobuf
+    /// [`map`][2] type fields.
+    ///
+    /// `IndexMap` iterates in insertion order, and decoded maps are filled in the order the
+    /// entries appear on the wire, so messages re-encode their maps in the order they were
+    /// decoded. The generated code requires the `indexmap` feature of `prost`.
+    ///
+    /// For details about matching fields see [`btree_map`](#method.btree_map). Unlike
+    /// `btree_map`, this doesn't clear the previously configured paths, so that both can be
+    /// used, as long as `btree_map` is called first.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Use `IndexMap` for all map fields in a package.
+    /// config.index_map(&[".my_config"]);
+    /// ```
+    ///
+    /// [1]: https://docs.rs/indexmap/1/indexmap/map/struct.IndexMap.html
+    /// [2]: https://developers.google.com/protocol-buffers/docs/proto3#maps
+    pub fn index_map<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for matcher in paths {
+            self.map_type
+                .insert(matcher.as_ref().to_string(), MapType::IndexMap);
+        }
+        self
+    }
+
     /// Configure the code generator to generate Rust [`bytes::Bytes`][1] fields for Protobuf
     /// [`bytes`][2] type fields.
     ///
@@ -513,6 +557,239 @@ impl Config {
         self
     }
 
+    /// Configure the code generator to preserve unknown fields in matched messages.
+    ///
+    /// Matched messages get an additional `unknown_fields` field of type
+    /// [`prost::UnknownFields`][1]. Fields which are not recognized while decoding the message are
+    /// stored there instead of being skipped, and are re-emitted after the known fields when the
+    /// message is encoded. This allows a message to be forwarded without dropping fields added by
+    /// newer versions of the schema, at the cost of a larger message representation.
+    ///
+    /// This includes variants added to a oneof by a newer schema. The wire format does not record
+    /// which oneof a field belongs to, so an unknown variant is stored like any other unknown
+    /// field, and the oneof field of the decoded message is left unset.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages which should preserve unknown
+    /// fields. For details about matching messages see [`btree_map`](#method.btree_map), just
+    /// with the field name omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Preserve unknown fields in a specific message type.
+    /// config.preserve_unknown_fields(&[".my_messages.MyMessageType"]);
+    ///
+    /// // Preserve unknown fields in all messages in a package.
+    /// config.preserve_unknown_fields(&[".my_messages"]);
+    ///
+    /// // Preserve unknown fields in all messages.
+    /// config.preserve_unknown_fields(&["."]);
+    /// ```
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/struct.UnknownFields.html
+    pub fn preserve_unknown_fields<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.unknown_fields.clear();
+        for matcher in paths {
+            self.unknown_fields.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to compare matched repeated fields as unordered sets in a
+    /// generated `semantic_eq` method.
+    ///
+    /// Messages with at least one matched repeated field get a `semantic_eq(&self, other: &Self)
+    /// -> bool` method, which compares the matched fields regardless of the order of their
+    /// elements, counting duplicates, and all other fields like `PartialEq`. The derived
+    /// `PartialEq` implementation is unchanged. Nested messages are compared with `PartialEq`, so
+    /// fields of nested messages are not compared as sets.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific fields, messages, or packages whose repeated fields should
+    /// be compared as sets. For details about matching fields see [`btree_map`](#method.btree_map).
+    /// Map fields are not affected.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Compare a specific field as a set.
+    /// config.set_semantics(&[".my_messages.MyMessageType.tags"]);
+    ///
+    /// // Compare all repeated fields in a message type as sets.
+    /// config.set_semantics(&[".my_messages.MyMessageType"]);
+    /// ```
+    pub fn set_semantics<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.set_semantics.clear();
+        for matcher in paths {
+            self.set_semantics.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configure the code generator to generate borrowed views of matched messages.
+    ///
+    /// For a matched message `Foo`, a `FooRef<'a>` struct is generated next to `Foo`, with a
+    /// `parse(buf: &'a [u8])` function which decodes the message without copying its string and
+    /// bytes fields: string fields are `&'a str` and bytes fields are `&'a [u8]`, borrowed from
+    /// `buf`. A message field holds the view of the nested message if its type is matched as
+    /// well, and otherwise the encoded nested message as `&'a [u8]`. Scalar fields have the same
+    /// types as in `Foo`.
+    ///
+    /// Views are meant for reading large messages in place, and differ from the owned messages in
+    /// a few ways:
+    ///
+    /// * Map, oneof and group fields are not included in the view, and are skipped when parsing.
+    /// * Fields which are not present are set to the default value of their Rust type, rather
+    ///   than the default values declared in the schema.
+    /// * A singular message field which occurs more than once holds the last occurrence, rather
+    ///   than the merge of all occurrences.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages which should get a borrowed view. For
+    /// details about matching messages see [`btree_map`](#method.btree_map), just with the field
+    /// name omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Generate a view of a specific message type.
+    /// config.borrowed_views(&[".my_messages.MyMessageType"]);
+    ///
+    /// // Generate views of all messages in a package.
+    /// config.borrowed_views(&[".my_messages"]);
+    /// ```
+    pub fn borrowed_views<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.borrowed_views.clear();
+        for matcher in paths {
+            self.borrowed_views.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configures the code generator to implement the `prost::json` conversions for matched
+    /// messages.
+    ///
+    /// Matched messages and their oneofs are marked `#[prost(json)]`, which requires the `json`
+    /// feature of `prost`. The types of message fields must implement the conversions as well, so
+    /// nested messages usually need to be matched too.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages. For details about matching messages
+    /// see [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Implement the JSON conversions for all messages.
+    /// config.json(&["."]);
+    /// ```
+    pub fn json<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.json.clear();
+        for matcher in paths {
+            self.json.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configures the code generator to implement the `prost::text_format` conversions for
+    /// matched messages.
+    ///
+    /// Matched messages and their oneofs are marked `#[prost(text_format)]`, which requires the
+    /// `text-format` feature of `prost`. As with [`json`](#method.json), nested messages usually
+    /// need to be matched too.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages. For details about matching messages
+    /// see [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Implement the text format conversions for the messages of a package.
+    /// config.text_format(&[".my_messages"]);
+    /// ```
+    pub fn text_format<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.text_format.clear();
+        for matcher in paths {
+            self.text_format.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configures the code generator to implement `prost::reflect::Reflect` for matched messages.
+    ///
+    /// Matched messages and their oneofs are marked `#[prost(reflect)]`, which requires the
+    /// `reflect` feature of `prost`. As with [`json`](#method.json), nested messages usually need
+    /// to be matched too.
+    ///
+    /// # Arguments
+    ///
+    /// **`paths`** - paths to specific messages or packages. For details about matching messages
+    /// see [`btree_map`](#method.btree_map), just with the field name omitted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # let mut config = prost_build::Config::new();
+    /// // Implement reflection for a specific message type.
+    /// config.reflect(&[".my_messages.MyMessageType"]);
+    /// ```
+    pub fn reflect<I, S>(&mut self, paths: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.reflect.clear();
+        for matcher in paths {
+            self.reflect.insert(matcher.as_ref().to_string(), ());
+        }
+        self
+    }
+
+    /// Configures the code generator to gate the attributes requested with
+    /// [`json`](#method.json), [`text_format`](#method.text_format) and
+    /// [`reflect`](#method.reflect) on features of the crate including the generated code.
+    ///
+    /// The attributes are emitted as `#[cfg_attr(feature = "json", prost(json))]`,
+    /// `#[cfg_attr(feature = "text-format", prost(text_format))]` and
+    /// `#[cfg_attr(feature = "reflect", prost(reflect))]`, so a library can forward these
+    /// features to `prost` and only implement the conversions its users ask for.
+    pub fn feature_gated_derives(&mut self, enabled: bool) -> &mut Self {
+        self.feature_gated_derives = enabled;
+        self
+    }
+
     /// Declare an externally provided Protobuf package or type.
     ///
     /// `extern_path` allows `prost` types in external crates to be referenced in generated code.
@@ -669,6 +946,18 @@ impl Config {
         self
     }
 
+    /// Configures the code generator to implement [`prost::Name`][1] for generated messages.
+    ///
+    /// The trait exposes the fully-qualified Protobuf name and the type URL of each message, which
+    /// are needed to pack messages into a `google.protobuf.Any`, for instance with
+    /// `prost_types::Any::pack`.
+    ///
+    /// [1]: https://docs.rs/prost/latest/prost/trait.Name.html
+    pub fn enable_type_names(&mut self) -> &mut Self {
+        self.type_names = true;
+        self
+    }
+
     /// Configures the output directory where generated Rust files will be written.
     ///
     /// If unset, defaults to the `OUT_DIR` environment variable. `OUT_DIR` is set by Cargo when
@@ -870,6 +1159,14 @@ impl default::Default for Config {
             extern_paths: Vec::new(),
             protoc_args: Vec::new(),
             disable_comments: PathMap::default(),
+            unknown_fields: PathMap::default(),
+            set_semantics: PathMap::default(),
+            borrowed_views: PathMap::default(),
+            json: PathMap::default(),
+            text_format: PathMap::default(),
+            reflect: PathMap::default(),
+            feature_gated_derives: false,
+            type_names: false,
         }
     }
 }
@@ -892,6 +1189,14 @@ impl fmt::Debug for Config {
             .field("extern_paths", &self.extern_paths)
             .field("protoc_args", &self.protoc_args)
             .field("disable_comments", &self.disable_comments)
+            .field("unknown_fields", &self.unknown_fields)
+            .field("set_semantics", &self.set_semantics)
+            .field("borrowed_views", &self.borrowed_views)
+            .field("json", &self.json)
+            .field("text_format", &self.text_format)
+            .field("reflect", &self.reflect)
+            .field("feature_gated_derives", &self.feature_gated_derives)
+            .field("type_names", &self.type_names)
             .finish()
     }
 }
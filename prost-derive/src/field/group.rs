@@ -71,19 +71,49 @@ impl Field {
     }
 
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(ident, quote!(::prost::encoding::group::encode))
+    }
+
+    pub fn encode_deterministic(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(
+            ident,
+            quote!(::prost::encoding::group::encode_deterministic),
+        )
+    }
+
+    pub fn encode_with_options(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with_args(
+            ident,
+            quote!(::prost::encoding::group::encode_with_options),
+            quote!(, options),
+        )
+    }
+
+    fn encode_with(&self, ident: TokenStream, encode_fn: TokenStream) -> TokenStream {
+        self.encode_with_args(ident, encode_fn, quote!())
+    }
+
+    /// Returns a statement which encodes the field with `encode_fn`, passing `args` after the
+    /// buffer.
+    fn encode_with_args(
+        &self,
+        ident: TokenStream,
+        encode_fn: TokenStream,
+        args: TokenStream,
+    ) -> TokenStream {
         let tag = self.tag;
         match self.label {
             Label::Optional => quote! {
                 if let Some(ref msg) = #ident {
-                    ::prost::encoding::group::encode(#tag, msg, buf);
+                    #encode_fn(#tag, msg, buf #args);
                 }
             },
             Label::Required => quote! {
-                ::prost::encoding::group::encode(#tag, &#ident, buf);
+                #encode_fn(#tag, &#ident, buf #args);
             },
             Label::Repeated => quote! {
                 for msg in &#ident {
-                    ::prost::encoding::group::encode(#tag, msg, buf);
+                    #encode_fn(#tag, msg, buf #args);
                 }
             },
         }
@@ -124,6 +154,81 @@ impl Field {
         }
     }
 
+    pub fn encoded_len_with_options(&self, ident: TokenStream) -> TokenStream {
+        let tag = self.tag;
+        let encoded_len_fn = quote!(::prost::encoding::group::encoded_len_with_options);
+        match self.label {
+            Label::Optional => quote! {
+                #ident.as_ref().map_or(0, |msg| #encoded_len_fn(#tag, msg, options))
+            },
+            Label::Required => quote! {
+                #encoded_len_fn(#tag, &#ident, options)
+            },
+            Label::Repeated => quote! {
+                #ident.iter().map(|msg| #encoded_len_fn(#tag, msg, options)).sum::<usize>()
+            },
+        }
+    }
+
+    /// Returns an expression which evaluates to the JSON representation of the field.
+    pub fn json(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                #ident.as_ref().map_or(::prost::json::Value::Null, ::prost::json::ToJson::to_json)
+            },
+            Label::Required => quote!(::prost::json::ToJson::to_json(&#ident)),
+            Label::Repeated => quote! {
+                ::prost::json::Value::Array(
+                    #ident.iter().map(::prost::json::ToJson::to_json).collect()
+                )
+            },
+        }
+    }
+
+    /// Returns an expression which converts the JSON value `value` to the type of the field.
+    pub fn from_json(&self) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                ::prost::json::decode::message(value, options).map(::core::option::Option::Some)
+            },
+            Label::Required => quote!(::prost::json::decode::message(value, options)),
+            Label::Repeated => quote! {
+                ::prost::json::decode::array(value, |value| {
+                    ::prost::json::decode::message(value, options)
+                })
+            },
+        }
+    }
+
+    /// Returns statements which write the field to the text format `printer`, if it is set.
+    pub fn text(&self, name: &str, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote! {
+                if let ::core::option::Option::Some(ref value) = #ident {
+                    printer.message(#name, value);
+                }
+            },
+            Label::Required => quote!(printer.message(#name, &#ident);),
+            Label::Repeated => quote! {
+                for value in &#ident {
+                    printer.message(#name, value);
+                }
+            },
+        }
+    }
+
+    /// Returns statements which parse the value of the field from the text format `parser`.
+    pub fn merge_text(&self, ident: TokenStream) -> TokenStream {
+        let parse_fn = quote!(::prost::text_format::decode::message);
+        match self.label {
+            Label::Optional => quote! {
+                #ident = ::core::option::Option::Some(parser.value(#parse_fn)?);
+            },
+            Label::Required => quote!(#ident = parser.value(#parse_fn)?;),
+            Label::Repeated => quote!(parser.repeated(&mut #ident, #parse_fn)?;),
+        }
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match self.label {
             Label::Optional => quote!(#ident = ::core::option::Option::None),
@@ -131,4 +236,14 @@ impl Field {
             Label::Repeated => quote!(#ident.clear()),
         }
     }
+
+    /// Returns an expression which is true if the field is not encoded. Required fields are
+    /// always encoded.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.label {
+            Label::Optional => quote!(#ident.is_none()),
+            Label::Required => quote!(false),
+            Label::Repeated => quote!(#ident.is_empty()),
+        }
+    }
 }
@@ -9,6 +9,7 @@ use crate::field::{scalar, set_option, tag_attr};
 pub enum MapTy {
     HashMap,
     BTreeMap,
+    IndexMap,
 }
 
 impl MapTy {
@@ -16,6 +17,7 @@ impl MapTy {
         match s {
             "map" | "hash_map" => Some(MapTy::HashMap),
             "btree_map" => Some(MapTy::BTreeMap),
+            "index_map" => Some(MapTy::IndexMap),
             _ => None,
         }
     }
@@ -24,13 +26,16 @@ impl MapTy {
         match *self {
             MapTy::HashMap => Ident::new("hash_map", Span::call_site()),
             MapTy::BTreeMap => Ident::new("btree_map", Span::call_site()),
+            MapTy::IndexMap => Ident::new("index_map", Span::call_site()),
         }
     }
 
-    fn lib(&self) -> TokenStream {
+    /// Returns the path of the map type.
+    fn rust_type(&self) -> TokenStream {
         match self {
-            MapTy::HashMap => quote! { std },
-            MapTy::BTreeMap => quote! { prost::alloc },
+            MapTy::HashMap => quote!(::std::collections::HashMap),
+            MapTy::BTreeMap => quote!(::prost::alloc::collections::BTreeMap),
+            MapTy::IndexMap => quote!(::prost::indexmap::IndexMap),
         }
     }
 }
@@ -129,16 +134,41 @@ impl Field {
 
     /// Returns a statement which encodes the map field.
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(ident, false)
+    }
+
+    /// Returns a statement which encodes the map field, emitting entries in key order.
+    pub fn encode_deterministic(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(ident, true)
+    }
+
+    fn encode_with(&self, ident: TokenStream, deterministic: bool) -> TokenStream {
         let tag = self.tag;
         let key_mod = self.key_ty.module();
         let ke = quote!(::prost::encoding::#key_mod::encode);
         let kl = quote!(::prost::encoding::#key_mod::encoded_len);
         let module = self.map_ty.module();
+        // `BTreeMap` entries are always encoded in key order; `HashMap` and `IndexMap` entries
+        // need sorting.
+        let (encode_fn, encode_with_default_fn, message_encode_fn) =
+            match (deterministic, &self.map_ty) {
+                (false, _) => (quote!(encode), quote!(encode_with_default), quote!(encode)),
+                (true, MapTy::HashMap) | (true, MapTy::IndexMap) => (
+                    quote!(encode_sorted),
+                    quote!(encode_with_default_sorted),
+                    quote!(encode_deterministic),
+                ),
+                (true, MapTy::BTreeMap) => (
+                    quote!(encode),
+                    quote!(encode_with_default),
+                    quote!(encode_deterministic),
+                ),
+            };
         match &self.value_ty {
             ValueTy::Scalar(scalar::Ty::Enumeration(ty)) => {
                 let default = quote!(#ty::default() as i32);
                 quote! {
-                    ::prost::encoding::#module::encode_with_default(
+                    ::prost::encoding::#module::#encode_with_default_fn(
                         #ke,
                         #kl,
                         ::prost::encoding::int32::encode,
@@ -155,7 +185,7 @@ impl Field {
                 let ve = quote!(::prost::encoding::#val_mod::encode);
                 let vl = quote!(::prost::encoding::#val_mod::encoded_len);
                 quote! {
-                    ::prost::encoding::#module::encode(
+                    ::prost::encoding::#module::#encode_fn(
                         #ke,
                         #kl,
                         #ve,
@@ -167,10 +197,10 @@ impl Field {
                 }
             }
             ValueTy::Message => quote! {
-                ::prost::encoding::#module::encode(
+                ::prost::encoding::#module::#encode_fn(
                     #ke,
                     #kl,
-                    ::prost::encoding::message::encode,
+                    ::prost::encoding::message::#message_encode_fn,
                     ::prost::encoding::message::encoded_len,
                     #tag,
                     &#ident,
@@ -256,6 +286,90 @@ impl Field {
         quote!(#ident.clear())
     }
 
+    /// Returns an expression which is true if the map has no entries.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        quote!(#ident.is_empty())
+    }
+
+    /// Returns an expression which evaluates to the JSON representation of the map, an object
+    /// keyed by the string form of the map keys.
+    pub fn json(&self, ident: TokenStream) -> TokenStream {
+        let key_json = self.key_ty.json();
+        let value_json = match &self.value_ty {
+            ValueTy::Scalar(value_ty) => value_ty.json(),
+            ValueTy::Message => quote!(::prost::json::ToJson::to_json),
+        };
+        quote! {
+            ::prost::json::Value::Object(
+                #ident
+                    .iter()
+                    .map(|(key, value)| {
+                        (::prost::json::encode::map_key((#key_json)(key)), (#value_json)(value))
+                    })
+                    .collect()
+            )
+        }
+    }
+
+    /// Returns an expression which converts the JSON object `value` to the map.
+    pub fn from_json(&self) -> TokenStream {
+        let key_from_json = match self.key_ty {
+            scalar::Ty::Bool => quote!(::prost::json::decode::bool_key),
+            ref key_ty => key_ty.from_json(),
+        };
+        let value_from_json = match &self.value_ty {
+            ValueTy::Scalar(value_ty) => value_ty.from_json(),
+            ValueTy::Message => quote! {
+                |value: &::prost::json::Value| ::prost::json::decode::message(value, options)
+            },
+        };
+        quote!(::prost::json::decode::map(value, #key_from_json, #value_from_json))
+    }
+
+    /// Returns statements which write the map to the text format `printer`, as a repeated
+    /// message field with `key` and `value` fields, sorted by key.
+    pub fn text(&self, name: &str, ident: TokenStream) -> TokenStream {
+        let key_text = self.key_ty.text();
+        let value_text = match &self.value_ty {
+            ValueTy::Scalar(value_ty) => {
+                let value_text = value_ty.text();
+                quote!(printer.scalar("value", &(#value_text)(value));)
+            }
+            ValueTy::Message => quote!(printer.message("value", value);),
+        };
+        quote! {
+            {
+                let mut entries = #ident.iter().collect::<::prost::alloc::vec::Vec<_>>();
+                entries.sort_by(|a, b| a.0.cmp(b.0));
+                for (key, value) in entries {
+                    printer.begin_message(#name);
+                    printer.scalar("key", &(#key_text)(key));
+                    #value_text
+                    printer.end_message();
+                }
+            }
+        }
+    }
+
+    /// Returns statements which parse an entry, or a list of entries, of the map from the text
+    /// format `parser`.
+    pub fn merge_text(&self, ident: TokenStream) -> TokenStream {
+        let parse_key = self.key_ty.parse_text();
+        let parse_value = match &self.value_ty {
+            ValueTy::Scalar(value_ty) => value_ty.parse_text(),
+            ValueTy::Message => quote!(::prost::text_format::decode::message),
+        };
+        quote! {
+            let mut entries = ::prost::alloc::vec::Vec::new();
+            parser.repeated(&mut entries, |parser| {
+                ::prost::text_format::decode::map_entry(parser, #parse_key, #parse_value)
+            })?;
+            for (key, value) in entries {
+                #ident.insert(key, value);
+            }
+        }
+    }
+
     /// Returns methods to embed in the message.
     pub fn methods(&self, ident: &Ident) -> Option<TokenStream> {
         if let ValueTy::Scalar(scalar::Ty::Enumeration(ty)) = &self.value_ty {
@@ -296,15 +410,10 @@ impl Field {
     /// The Debug tries to convert any enumerations met into the variants if possible, instead of
     /// outputting the raw numbers.
     pub fn debug(&self, wrapper_name: TokenStream) -> TokenStream {
-        let type_name = match self.map_ty {
-            MapTy::HashMap => Ident::new("HashMap", Span::call_site()),
-            MapTy::BTreeMap => Ident::new("BTreeMap", Span::call_site()),
-        };
-
         // A fake field for generating the debug wrapper
         let key_wrapper = fake_scalar(self.key_ty.clone()).debug(quote!(KeyWrapper));
         let value_wrapper = self.value_ty.debug();
-        let libname = self.map_ty.lib();
+        let map_ty = self.map_ty.rust_type();
         let fmt = quote! {
             fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 #key_wrapper
@@ -319,7 +428,7 @@ impl Field {
         match &self.value_ty {
             ValueTy::Scalar(_ty) => {
                 quote! {
-                    struct #wrapper_name<'a, K, V>(&'a ::#libname::collections::#type_name<K, V>);
+                    struct #wrapper_name<'a, K, V>(&'a #map_ty<K, V>);
                     impl<'a, K, V> ::core::fmt::Debug for #wrapper_name<'a, K, V>
                     where
                         K: ::core::fmt::Debug,
@@ -330,7 +439,7 @@ impl Field {
                 }
             }
             ValueTy::Message => quote! {
-                struct #wrapper_name<'a, K, V: 'a>(&'a ::#libname::collections::#type_name<K, V>);
+                struct #wrapper_name<'a, K, V: 'a>(&'a #map_ty<K, V>);
                 impl<'a, K, V> ::core::fmt::Debug for #wrapper_name<'a, K, V>
                 where
                     K: ::core::fmt::Debug,
@@ -370,6 +479,14 @@ pub enum ValueTy {
 }
 
 impl ValueTy {
+    /// Returns the `prost::reflect` module which converts values of the type.
+    pub fn reflect_module(&self) -> TokenStream {
+        match *self {
+            ValueTy::Scalar(ref ty) => ty.reflect_module(),
+            ValueTy::Message => quote!(::prost::reflect::message),
+        }
+    }
+
     fn from_str(s: &str) -> Result<ValueTy, Error> {
         if let Ok(ty) = scalar::Ty::from_str(s) {
             Ok(ValueTy::Scalar(ty))
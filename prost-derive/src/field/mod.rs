@@ -98,6 +98,28 @@ impl Field {
         }
     }
 
+    /// Returns a statement which encodes the field, emitting map entries in key order.
+    pub fn encode_deterministic(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.encode(ident),
+            Field::Message(ref message) => message.encode_deterministic(ident),
+            Field::Map(ref map) => map.encode_deterministic(ident),
+            Field::Oneof(ref oneof) => oneof.encode_deterministic(ident),
+            Field::Group(ref group) => group.encode_deterministic(ident),
+        }
+    }
+
+    /// Returns a statement which encodes the field following the `options` in scope.
+    pub fn encode_with_options(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.encode_with_options(ident),
+            Field::Message(ref message) => message.encode_with_options(ident),
+            Field::Map(ref map) => map.encode(ident),
+            Field::Oneof(ref oneof) => oneof.encode_with_options(ident),
+            Field::Group(ref group) => group.encode_with_options(ident),
+        }
+    }
+
     /// Returns an expression which evaluates to the result of merging a decoded
     /// value into the field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
@@ -121,6 +143,30 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to the encoded length of the field following the
+    /// `options` in scope.
+    pub fn encoded_len_with_options(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.encoded_len_with_options(ident),
+            Field::Map(ref map) => map.encoded_len(ident),
+            Field::Message(ref msg) => msg.encoded_len_with_options(ident),
+            Field::Oneof(ref oneof) => oneof.encoded_len_with_options(ident),
+            Field::Group(ref group) => group.encoded_len_with_options(ident),
+        }
+    }
+
+    /// Returns an expression which is true if the field is not encoded, so contributes nothing to
+    /// the encoded length.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.is_default(ident),
+            Field::Message(ref message) => message.is_default(ident),
+            Field::Map(ref map) => map.is_default(ident),
+            Field::Oneof(ref oneof) => oneof.is_default(ident),
+            Field::Group(ref group) => group.is_default(ident),
+        }
+    }
+
     /// Returns a statement which clears the field.
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         match *self {
@@ -132,6 +178,77 @@ impl Field {
         }
     }
 
+    /// Returns a statement which inserts the JSON representation of the field into `map`, keyed
+    /// by `name`.
+    ///
+    /// Oneof fields insert the occupied variant, if any, keyed by the variant's own name.
+    pub fn json(&self, name: &str, ident: TokenStream) -> TokenStream {
+        let value = match *self {
+            Field::Scalar(ref scalar) => scalar.json(ident),
+            Field::Message(ref message) => message.json(ident),
+            Field::Map(ref map) => map.json(ident),
+            Field::Oneof(ref oneof) => return oneof.json(ident),
+            Field::Group(ref group) => group.json(ident),
+        };
+        quote!(let _ = map.insert(::prost::alloc::string::String::from(#name), #value);)
+    }
+
+    /// Returns an expression which converts the JSON value `value` to the type of the field,
+    /// evaluating to a `Result<_, ::prost::json::JsonError>`.
+    ///
+    /// Oneof fields are instead merged by the oneof's `merge_json_field` method.
+    pub fn from_json(&self) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.from_json(),
+            Field::Message(ref message) => message.from_json(),
+            Field::Map(ref map) => map.from_json(),
+            Field::Oneof(..) => unreachable!("oneof fields are not converted from JSON values"),
+            Field::Group(ref group) => group.from_json(),
+        }
+    }
+
+    /// Returns statements which write the field to the text format `printer`, if it is set.
+    ///
+    /// Oneof fields write the occupied variant, if any, named after the variant.
+    pub fn text(&self, name: &str, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.text(name, ident),
+            Field::Message(ref message) => message.text(name, ident),
+            Field::Map(ref map) => map.text(name, ident),
+            Field::Oneof(ref oneof) => oneof.text(ident),
+            Field::Group(ref group) => group.text(name, ident),
+        }
+    }
+
+    /// Returns statements which parse the value of the field from the text format `parser` and
+    /// merge it into the field.
+    ///
+    /// Oneof fields are instead merged by the oneof's `merge_text_field` method.
+    pub fn merge_text(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => scalar.merge_text(ident),
+            Field::Message(ref message) => message.merge_text(ident),
+            Field::Map(ref map) => map.merge_text(ident),
+            Field::Oneof(..) => unreachable!("oneof fields are not parsed as a single field"),
+            Field::Group(ref group) => group.merge_text(ident),
+        }
+    }
+
+    /// Returns an expression which parses the value of a oneof variant from the text format
+    /// `parser`, evaluating to a `Result<_, ::prost::text_format::ParseError>`.
+    pub fn parse_text_value(&self) -> TokenStream {
+        match *self {
+            Field::Scalar(ref scalar) => {
+                let parse_fn = scalar.ty.parse_text();
+                quote!(parser.value(#parse_fn))
+            }
+            Field::Message(..) | Field::Group(..) => {
+                quote!(parser.value(::prost::text_format::decode::message))
+            }
+            Field::Map(..) | Field::Oneof(..) => unreachable!("invalid oneof variant field"),
+        }
+    }
+
     pub fn default(&self) -> TokenStream {
         match *self {
             Field::Scalar(ref scalar) => scalar.default(),
@@ -171,6 +288,286 @@ impl Field {
             _ => None,
         }
     }
+
+    /// Returns the `prost::reflect` module which converts single values of a scalar, message or
+    /// group field, and whether the field is optional, required or repeated.
+    pub fn reflect_module(&self) -> (TokenStream, Label) {
+        match *self {
+            Field::Scalar(ref scalar) => {
+                let label = match scalar.kind {
+                    scalar::Kind::Plain(..) | scalar::Kind::Required(..) => Label::Required,
+                    scalar::Kind::Optional(..) => Label::Optional,
+                    scalar::Kind::Repeated | scalar::Kind::Packed => Label::Repeated,
+                };
+                (scalar.ty.reflect_module(), label)
+            }
+            Field::Message(ref message) => (quote!(::prost::reflect::message), message.label),
+            Field::Group(ref group) => (quote!(::prost::reflect::message), group.label),
+            Field::Map(..) | Field::Oneof(..) => {
+                unreachable!("map and oneof fields have no module")
+            }
+        }
+    }
+
+    /// Returns an expression which evaluates to the `Option<prost::reflect::Value>` of the field
+    /// with tag `tag`, for `Reflect::get_field`.
+    pub fn reflect_get(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Map(ref map) => {
+                let key_module = map.key_ty.reflect_module();
+                let value_module = map.value_ty.reflect_module();
+                quote! {
+                    ::core::option::Option::Some(::prost::reflect::Value::Map(
+                        #ident
+                            .iter()
+                            .map(|(key, value)| {
+                                (#key_module::get(key), #value_module::get(value))
+                            })
+                            .collect(),
+                    ))
+                }
+            }
+            Field::Oneof(..) => quote! {
+                #ident.as_ref().and_then(|oneof| oneof.reflect_get(tag))
+            },
+            _ => match self.reflect_module() {
+                (module, Label::Required) => {
+                    quote!(::core::option::Option::Some(#module::get(&#ident)))
+                }
+                (module, Label::Optional) => quote!(#ident.as_ref().map(#module::get)),
+                (module, Label::Repeated) => quote! {
+                    ::core::option::Option::Some(::prost::reflect::Value::List(
+                        #ident.iter().map(#module::get).collect(),
+                    ))
+                },
+            },
+        }
+    }
+
+    /// Returns an expression which sets the field with tag `tag` to the `prost::reflect::Value`
+    /// named `value`, and evaluates to a `Result<(), ReflectError>`, for `Reflect::set_field`.
+    pub fn reflect_set(&self, ident: TokenStream) -> TokenStream {
+        match *self {
+            Field::Map(ref map) => {
+                let key_module = map.key_ty.reflect_module();
+                let value_module = map.value_ty.reflect_module();
+                quote! {
+                    ::prost::reflect::set_map(
+                        &mut #ident,
+                        value,
+                        #key_module::set,
+                        #value_module::set,
+                    )
+                }
+            }
+            Field::Oneof(ref oneof) => {
+                let ty = &oneof.ty;
+                quote!(#ty::reflect_set(&mut #ident, tag, value))
+            }
+            _ => match self.reflect_module() {
+                (module, Label::Required) => quote!(#module::set(&mut #ident, value)),
+                (module, Label::Optional) => quote! {
+                    ::prost::reflect::set_owned(value, #module::set)
+                        .map(|value| #ident = ::core::option::Option::Some(value))
+                },
+                (module, Label::Repeated) => {
+                    quote!(::prost::reflect::set_list(&mut #ident, value, #module::set))
+                }
+            },
+        }
+    }
+
+    /// Returns the statements of `Message::missing_required_fields` which check the required fields
+    /// of the messages held by the field `ident`, named `name`: the declaration of a variable
+    /// collecting the encoded values of the field, a match arm adding an occurrence of the field to
+    /// it, and a statement checking the collected values once the whole message has been read.
+    ///
+    /// Occurrences are collected the way they are merged by decoding, so that each message is
+    /// checked against the encoding it was decoded from: the occurrences of a singular field are
+    /// concatenated, each occurrence of a repeated field is an element, a map entry replaces an
+    /// earlier entry with the same key, and a oneof keeps the occurrences of its last variant.
+    ///
+    /// Returns `None` if the field does not hold messages.
+    pub fn missing_required_fields(
+        &self,
+        ident: &Ident,
+        name: &str,
+    ) -> Option<(TokenStream, TokenStream, TokenStream)> {
+        let values = Ident::new(
+            &format!("{}_values", ident.to_string().trim_start_matches("r#")),
+            Span::call_site(),
+        );
+        let path = quote!(&::prost::alloc::format!("{}{}.", path, #name));
+        let check = |witness: TokenStream, path: TokenStream| {
+            quote! {
+                ::prost::encoding::message::missing_required_fields(
+                    |message: &Self| #witness,
+                    value,
+                    #path,
+                    missing,
+                )
+            }
+        };
+        let (tag, label) = match *self {
+            Field::Message(ref message) => (message.tag, message.label),
+            Field::Group(ref group) => (group.tag, group.label),
+            Field::Map(ref map) if map.value_ty == map::ValueTy::Message => {
+                let tag = map.tag;
+                let check = check(quote!(message.#ident.values().next()), path);
+                return Some((
+                    quote! {
+                        let mut #values = ::prost::alloc::vec::Vec::new();
+                    },
+                    quote! {
+                        #tag => ::prost::encoding::message::push_map_entry(&mut #values, field.value),
+                    },
+                    quote! {
+                        for &(_, ref value) in &#values {
+                            #check;
+                        }
+                    },
+                ));
+            }
+            Field::Oneof(ref oneof) => {
+                let ty = &oneof.ty;
+                let tags = &oneof.tags;
+                return Some((
+                    quote! {
+                        let mut #values: ::core::option::Option<(
+                            u32,
+                            ::prost::alloc::vec::Vec<u8>,
+                        )> = ::core::option::Option::None;
+                    },
+                    quote! {
+                        #(#tags)|* => match #values {
+                            ::core::option::Option::Some((tag, ref mut value)) if tag == field.tag => {
+                                value.extend_from_slice(field.value)
+                            }
+                            _ => {
+                                #values = ::core::option::Option::Some((field.tag, field.value.to_vec()))
+                            }
+                        },
+                    },
+                    quote! {
+                        if let ::core::option::Option::Some((tag, ref value)) = #values {
+                            #ty::missing_required_fields(tag, value, path, missing);
+                        }
+                    },
+                ));
+            }
+            _ => return None,
+        };
+        Some(match label {
+            Label::Required | Label::Optional => {
+                let witness = if label == Label::Required {
+                    quote!(::core::option::Option::Some(&message.#ident))
+                } else {
+                    quote!(message.#ident.as_ref())
+                };
+                let check = check(witness, path);
+                (
+                    quote! {
+                        let mut #values: ::core::option::Option<::prost::alloc::vec::Vec<u8>> =
+                            ::core::option::Option::None;
+                    },
+                    quote! {
+                        #tag => #values
+                            .get_or_insert_with(::prost::alloc::vec::Vec::new)
+                            .extend_from_slice(field.value),
+                    },
+                    quote! {
+                        if let ::core::option::Option::Some(ref value) = #values {
+                            #check;
+                        }
+                    },
+                )
+            }
+            Label::Repeated => {
+                // Elements are named by their index.
+                let path = quote!(&::prost::alloc::format!("{}{}[{}].", path, #name, index));
+                let check = check(quote!(message.#ident.first()), path);
+                (
+                    quote! {
+                        let mut #values = ::prost::alloc::vec::Vec::new();
+                    },
+                    quote! {
+                        #tag => #values.push(field.value),
+                    },
+                    quote! {
+                        for (index, &value) in #values.iter().enumerate() {
+                            #check;
+                        }
+                    },
+                )
+            }
+        })
+    }
+
+    /// Returns a statement of `Message::missing_required_fields`, which records the field as
+    /// missing if it is required and its tag is not among the decoded `tags`.
+    pub fn missing_required_check(&self, name: &str) -> Option<TokenStream> {
+        let tag = match *self {
+            Field::Scalar(scalar::Field {
+                tag,
+                kind: scalar::Kind::Required(..),
+                ..
+            }) => tag,
+            Field::Message(ref message) if message.label == Label::Required => message.tag,
+            Field::Group(ref group) if group.label == Label::Required => group.tag,
+            _ => return None,
+        };
+        Some(quote! {
+            if !tags.contains(&#tag) {
+                missing.push(::prost::alloc::format!("{}{}", path, #name));
+            }
+        })
+    }
+
+    /// Returns constant `prost::descriptor::FieldDescriptor` expressions describing the field.
+    ///
+    /// A oneof field is described by the descriptors of its variants, which are taken from the
+    /// `FIELDS` constant derived for the oneof enum.
+    pub fn descriptors(&self, name: &str, json_name: &str) -> Vec<TokenStream> {
+        let (kind, wire_type, repeated) = match *self {
+            Field::Scalar(ref scalar) => scalar.descriptor(),
+            Field::Message(ref message) => (
+                quote!(Message),
+                quote!(LengthDelimited),
+                message.label == Label::Repeated,
+            ),
+            Field::Map(..) => (quote!(Map), quote!(LengthDelimited), true),
+            Field::Group(ref group) => (
+                quote!(Group),
+                quote!(StartGroup),
+                group.label == Label::Repeated,
+            ),
+            Field::Oneof(ref oneof) => {
+                let ty = &oneof.ty;
+                return (0..oneof.tags.len())
+                    .map(|i| {
+                        quote! {
+                            ::prost::descriptor::FieldDescriptor {
+                                oneof: ::core::option::Option::Some(#name),
+                                ..#ty::FIELDS[#i]
+                            }
+                        }
+                    })
+                    .collect();
+            }
+        };
+        let tag = self.tags()[0];
+        vec![quote! {
+            ::prost::descriptor::FieldDescriptor {
+                name: #name,
+                json_name: #json_name,
+                tag: #tag,
+                wire_type: ::prost::encoding::WireType::#wire_type,
+                kind: ::prost::descriptor::FieldKind::#kind,
+                repeated: #repeated,
+                oneof: ::core::option::Option::None,
+            }
+        }]
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -223,6 +620,76 @@ impl fmt::Display for Label {
     }
 }
 
+/// Returns the proto3 JSON name of a field: the field name converted to `lowerCamelCase`.
+pub fn json_name(ident: &Ident) -> String {
+    let name = proto_name(ident);
+    let mut json_name = String::with_capacity(name.len());
+    let mut capitalize_next = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            json_name.extend(c.to_uppercase());
+            capitalize_next = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+/// Returns the original Protobuf name of a field, without the raw identifier prefix or the
+/// trailing underscore added to names which are Rust keywords.
+pub fn proto_name(ident: &Ident) -> String {
+    let ident = ident.to_string();
+    ident
+        .trim_start_matches("r#")
+        .trim_end_matches('_')
+        .to_string()
+}
+
+/// Returns the proto3 JSON name of a oneof variant, which is named after its field in
+/// `UpperCamelCase`.
+pub fn variant_json_name(ident: &Ident) -> String {
+    let ident = ident.to_string();
+    let mut chars = ident.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => ident,
+    }
+}
+
+/// Returns the original Protobuf name of a oneof variant, converting its `UpperCamelCase` name
+/// back to `lower_snake_case`.
+pub fn variant_proto_name(ident: &Ident) -> String {
+    let ident = ident.to_string();
+    let mut name = String::with_capacity(ident.len());
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                name.push('_');
+            }
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Checks whether a message field is annotated as the storage for unknown fields, e.g.
+/// `#[prost(unknown_fields)]`.
+pub fn is_unknown_fields(attrs: &[Attribute]) -> Result<bool, Error> {
+    let attrs = prost_attrs(attrs.to_vec())?;
+    if !attrs.iter().any(|attr| word_attr("unknown_fields", attr)) {
+        return Ok(false);
+    }
+    if attrs.len() != 1 {
+        bail!("unknown_fields attribute can not be combined with other attributes");
+    }
+    Ok(true)
+}
+
 /// Get the items belonging to the 'prost' list attribute, e.g. `#[prost(foo, bar="baz")]`.
 pub(super) fn prost_attrs(attrs: Vec<Attribute>) -> Result<Vec<Meta>, Error> {
     Ok(attrs
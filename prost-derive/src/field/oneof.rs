@@ -77,6 +77,24 @@ impl Field {
         }
     }
 
+    /// Returns a statement which encodes the oneof field, emitting map entries in key order.
+    pub fn encode_deterministic(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let Some(ref oneof) = #ident {
+                oneof.encode_deterministic(buf)
+            }
+        }
+    }
+
+    /// Returns a statement which encodes the oneof field following the `options` in scope.
+    pub fn encode_with_options(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let Some(ref oneof) = #ident {
+                oneof.encode_with_options(buf, options)
+            }
+        }
+    }
+
     /// Returns an expression which evaluates to the result of decoding the oneof field.
     pub fn merge(&self, ident: TokenStream) -> TokenStream {
         let ty = &self.ty;
@@ -93,7 +111,54 @@ impl Field {
         }
     }
 
+    /// Returns an expression which evaluates to the encoded length of the oneof field following
+    /// the `options` in scope.
+    pub fn encoded_len_with_options(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            #ident.as_ref().map_or(0, |oneof| oneof.encoded_len_with_options(options))
+        }
+    }
+
+    /// Returns a statement which inserts the JSON representation of the occupied variant, if any,
+    /// into `map`.
+    pub fn json(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let ::core::option::Option::Some(ref oneof) = #ident {
+                oneof.to_json_fields(&mut map);
+            }
+        }
+    }
+
+    /// Returns an expression which merges the JSON value `value` of the field `name` into the
+    /// oneof, evaluating to `false` if `name` does not name a variant.
+    pub fn merge_json(&self, ident: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        quote!(#ty::merge_json_field(&mut #ident, name, value, options)?)
+    }
+
+    /// Returns a statement which writes the occupied variant, if any, to the text format
+    /// `printer`.
+    pub fn text(&self, ident: TokenStream) -> TokenStream {
+        quote! {
+            if let ::core::option::Option::Some(ref oneof) = #ident {
+                oneof.write_text_fields(printer);
+            }
+        }
+    }
+
+    /// Returns an expression which parses the value of the field `name` from the text format
+    /// `parser` into the oneof, evaluating to `false` if `name` does not name a variant.
+    pub fn merge_text(&self, ident: TokenStream) -> TokenStream {
+        let ty = &self.ty;
+        quote!(#ty::merge_text_field(&mut #ident, name, parser)?)
+    }
+
     pub fn clear(&self, ident: TokenStream) -> TokenStream {
         quote!(#ident = ::core::option::Option::None)
     }
+
+    /// Returns an expression which is true if no variant is set.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        quote!(#ident.is_none())
+    }
 }
@@ -64,6 +64,14 @@ impl Field {
             |lit| DefaultValue::from_lit(&ty, lit),
         )?;
 
+        if let (Ty::String(StringTy::ArrayString(..)), DefaultValue::String(ref value)) =
+            (&ty, &default)
+        {
+            if !value.is_empty() {
+                bail!("array_string fields may not have a default value");
+            }
+        }
+
         let kind = match (label, packed, has_default) {
             (None, Some(true), _)
             | (Some(Label::Optional), Some(true), _)
@@ -105,7 +113,71 @@ impl Field {
         }
     }
 
+    /// Returns an expression which is true if the plain field `ident` differs from its default.
+    fn differs_from_default(&self, ident: &TokenStream, default: &DefaultValue) -> TokenStream {
+        let default = default.typed();
+        match self.ty {
+            // `Box<[u8]>` can only be compared with a `[u8]`, not with a `&[u8]`.
+            Ty::Bytes(BytesTy::Boxed) => quote!(&*#ident != #default),
+            _ => quote!(#ident != #default),
+        }
+    }
+
+    /// Returns an expression which is true if the plain field `ident` is encoded: if it differs
+    /// from its default, or, with `with_options`, if the `options` in scope emit defaults.
+    fn is_set(
+        &self,
+        ident: &TokenStream,
+        default: &DefaultValue,
+        with_options: bool,
+    ) -> TokenStream {
+        let differs = self.differs_from_default(ident, default);
+        if with_options {
+            quote!(options.emits_defaults() || #differs)
+        } else {
+            differs
+        }
+    }
+
+    /// Returns the `FieldKind` variant, the `WireType` variant and whether the field is repeated,
+    /// for the field's descriptor.
+    pub fn descriptor(&self) -> (TokenStream, TokenStream, bool) {
+        let (kind, wire_type) = match self.ty {
+            Ty::Double => (quote!(Double), quote!(SixtyFourBit)),
+            Ty::Float => (quote!(Float), quote!(ThirtyTwoBit)),
+            Ty::Int32 => (quote!(Int32), quote!(Varint)),
+            Ty::Int64 => (quote!(Int64), quote!(Varint)),
+            Ty::Uint32 => (quote!(Uint32), quote!(Varint)),
+            Ty::Uint64 => (quote!(Uint64), quote!(Varint)),
+            Ty::Sint32 => (quote!(Sint32), quote!(Varint)),
+            Ty::Sint64 => (quote!(Sint64), quote!(Varint)),
+            Ty::Fixed32 => (quote!(Fixed32), quote!(ThirtyTwoBit)),
+            Ty::Fixed64 => (quote!(Fixed64), quote!(SixtyFourBit)),
+            Ty::Sfixed32 => (quote!(Sfixed32), quote!(ThirtyTwoBit)),
+            Ty::Sfixed64 => (quote!(Sfixed64), quote!(SixtyFourBit)),
+            Ty::Bool => (quote!(Bool), quote!(Varint)),
+            Ty::String(..) => (quote!(String), quote!(LengthDelimited)),
+            Ty::Bytes(..) => (quote!(Bytes), quote!(LengthDelimited)),
+            Ty::Enumeration(..) => (quote!(Enumeration), quote!(Varint)),
+        };
+        match self.kind {
+            Kind::Packed => (kind, quote!(LengthDelimited), true),
+            Kind::Repeated => (kind, wire_type, true),
+            _ => (kind, wire_type, false),
+        }
+    }
+
     pub fn encode(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(ident, false)
+    }
+
+    /// Returns a statement which encodes the field following the `options` in scope, writing a
+    /// plain field at its default value if requested.
+    pub fn encode_with_options(&self, ident: TokenStream) -> TokenStream {
+        self.encode_with(ident, true)
+    }
+
+    fn encode_with(&self, ident: TokenStream, with_options: bool) -> TokenStream {
         let module = self.ty.module();
         let encode_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encode),
@@ -117,9 +189,9 @@ impl Field {
 
         match self.kind {
             Kind::Plain(ref default) => {
-                let default = default.typed();
+                let is_set = self.is_set(&ident, default, with_options);
                 quote! {
-                    if #ident != #default {
+                    if #is_set {
                         #encode_fn(#tag, &#ident, buf);
                     }
                 }
@@ -149,6 +221,11 @@ impl Field {
             Kind::Plain(..) | Kind::Required(..) | Kind::Repeated | Kind::Packed => quote! {
                 #merge_fn(wire_type, #ident, buf, ctx)
             },
+            // Numeric fields may be merged from a packed run in lenient mode, which must leave the
+            // field unset if it is empty.
+            Kind::Optional(..) if self.ty.is_numeric() => quote! {
+                ::prost::encoding::#module::merge_optional(wire_type, #ident, buf, ctx)
+            },
             Kind::Optional(..) => quote! {
                 #merge_fn(wire_type,
                           #ident.get_or_insert_with(Default::default),
@@ -160,6 +237,16 @@ impl Field {
 
     /// Returns an expression which evaluates to the encoded length of the field.
     pub fn encoded_len(&self, ident: TokenStream) -> TokenStream {
+        self.encoded_len_with(ident, false)
+    }
+
+    /// Returns an expression which evaluates to the encoded length of the field following the
+    /// `options` in scope.
+    pub fn encoded_len_with_options(&self, ident: TokenStream) -> TokenStream {
+        self.encoded_len_with(ident, true)
+    }
+
+    fn encoded_len_with(&self, ident: TokenStream, with_options: bool) -> TokenStream {
         let module = self.ty.module();
         let encoded_len_fn = match self.kind {
             Kind::Plain(..) | Kind::Optional(..) | Kind::Required(..) => quote!(encoded_len),
@@ -171,9 +258,9 @@ impl Field {
 
         match self.kind {
             Kind::Plain(ref default) => {
-                let default = default.typed();
+                let is_set = self.is_set(&ident, default, with_options);
                 quote! {
-                    if #ident != #default {
+                    if #is_set {
                         #encoded_len_fn(#tag, &#ident)
                     } else {
                         0
@@ -194,6 +281,14 @@ impl Field {
             Kind::Plain(ref default) | Kind::Required(ref default) => {
                 let default = default.typed();
                 match self.ty {
+                    // Neither `SmolStr` nor `Cow` have an inherent `clear` method.
+                    Ty::String(StringTy::SmolStr) | Ty::String(StringTy::Cow) => {
+                        quote!(::prost::encoding::string::StringAdapter::clear(&mut #ident))
+                    }
+                    // Boxed slices have a fixed length, so are replaced instead.
+                    Ty::Bytes(BytesTy::Boxed) => {
+                        quote!(#ident = ::core::default::Default::default())
+                    }
                     Ty::String(..) | Ty::Bytes(..) => quote!(#ident.clear()),
                     _ => quote!(#ident = #default),
                 }
@@ -203,12 +298,92 @@ impl Field {
         }
     }
 
+    /// Returns an expression which is true if the field is not encoded. Required fields are
+    /// always encoded.
+    pub fn is_default(&self, ident: TokenStream) -> TokenStream {
+        match self.kind {
+            Kind::Plain(ref default) => {
+                let is_set = self.differs_from_default(&ident, default);
+                quote!(!(#is_set))
+            }
+            Kind::Optional(_) => quote!(#ident.is_none()),
+            Kind::Required(_) => quote!(false),
+            Kind::Repeated | Kind::Packed => quote!(#ident.is_empty()),
+        }
+    }
+
+    /// Returns an expression which evaluates to the JSON representation of the field.
+    pub fn json(&self, ident: TokenStream) -> TokenStream {
+        let json_fn = self.ty.json();
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => quote!((#json_fn)(&#ident)),
+            Kind::Optional(..) => quote! {
+                #ident.as_ref().map_or(::prost::json::Value::Null, #json_fn)
+            },
+            Kind::Repeated | Kind::Packed => quote! {
+                ::prost::json::Value::Array(#ident.iter().map(#json_fn).collect())
+            },
+        }
+    }
+
+    /// Returns an expression which converts the JSON value `value` to the type of the field.
+    pub fn from_json(&self) -> TokenStream {
+        let from_json_fn = self.ty.from_json();
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => quote!((#from_json_fn)(value)),
+            Kind::Optional(..) => quote! {
+                (#from_json_fn)(value).map(::core::option::Option::Some)
+            },
+            Kind::Repeated | Kind::Packed => quote! {
+                ::prost::json::decode::array(value, #from_json_fn)
+            },
+        }
+    }
+
+    /// Returns statements which write the field to the text format `printer`, if it is set.
+    pub fn text(&self, name: &str, ident: TokenStream) -> TokenStream {
+        let text_fn = self.ty.text();
+        match self.kind {
+            Kind::Plain(ref default) => {
+                let is_set = self.differs_from_default(&ident, default);
+                quote! {
+                    if #is_set {
+                        printer.scalar(#name, &(#text_fn)(&#ident));
+                    }
+                }
+            }
+            Kind::Optional(..) => quote! {
+                if let ::core::option::Option::Some(ref value) = #ident {
+                    printer.scalar(#name, &(#text_fn)(value));
+                }
+            },
+            Kind::Required(..) => quote!(printer.scalar(#name, &(#text_fn)(&#ident));),
+            Kind::Repeated | Kind::Packed => quote! {
+                for value in &#ident {
+                    printer.scalar(#name, &(#text_fn)(value));
+                }
+            },
+        }
+    }
+
+    /// Returns statements which parse the value of the field from the text format `parser`.
+    pub fn merge_text(&self, ident: TokenStream) -> TokenStream {
+        let parse_fn = self.ty.parse_text();
+        match self.kind {
+            Kind::Plain(..) | Kind::Required(..) => quote!(#ident = parser.value(#parse_fn)?;),
+            Kind::Optional(..) => quote! {
+                #ident = ::core::option::Option::Some(parser.value(#parse_fn)?);
+            },
+            Kind::Repeated | Kind::Packed => quote!(parser.repeated(&mut #ident, #parse_fn)?;),
+        }
+    }
+
     /// Returns an expression which evaluates to the default value of the field.
     pub fn default(&self) -> TokenStream {
         match self.kind {
             Kind::Plain(ref value) | Kind::Required(ref value) => value.owned(),
             Kind::Optional(_) => quote!(::core::option::Option::None),
-            Kind::Repeated | Kind::Packed => quote!(::prost::alloc::vec::Vec::new()),
+            Kind::Repeated | Kind::Packed => quote!(::core::default::Default::default()),
         }
     }
 
@@ -250,7 +425,7 @@ impl Field {
             },
             Kind::Repeated | Kind::Packed => {
                 quote! {
-                    struct #wrapper_name<'a>(&'a ::prost::alloc::vec::Vec<#inner_ty>);
+                    struct #wrapper_name<'a>(&'a [#inner_ty]);
                     impl<'a> ::core::fmt::Debug for #wrapper_name<'a> {
                         fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                             let mut vec_builder = f.debug_list();
@@ -283,12 +458,25 @@ impl Field {
                          or the default if the field is set to an invalid enum value.",
                         ident_str,
                     );
+                    let try_get = Ident::new(&format!("try_{}", ident_str), Span::call_site());
+                    let try_get_doc = format!(
+                        "Returns the enum value of `{}`, \
+                         or the raw value if the field is set to an unknown enum value.",
+                        ident_str,
+                    );
                     quote! {
                         #[doc=#get_doc]
                         pub fn #ident(&self) -> #ty {
                             #ty::from_i32(self.#ident).unwrap_or(#default)
                         }
 
+                        #[doc=#try_get_doc]
+                        pub fn #try_get(
+                            &self,
+                        ) -> ::core::result::Result<#ty, ::prost::UnknownEnumValue> {
+                            #ty::try_from_i32(self.#ident)
+                        }
+
                         #[doc=#set_doc]
                         pub fn #set(&mut self, value: #ty) {
                             self.#ident = value as i32;
@@ -389,6 +577,10 @@ pub enum Ty {
 pub enum BytesTy {
     Vec,
     Bytes,
+    /// A `Box<[u8]>`, which unlike `Vec<u8>` has no spare capacity.
+    Boxed,
+    /// A fixed-capacity `heapless::Vec<u8, N>` with the given capacity.
+    Heapless(usize),
 }
 
 impl BytesTy {
@@ -396,7 +588,18 @@ impl BytesTy {
         match s {
             "vec" => Ok(BytesTy::Vec),
             "bytes" => Ok(BytesTy::Bytes),
-            _ => bail!("Invalid bytes type: {}", s),
+            "box" => Ok(BytesTy::Boxed),
+            _ => {
+                if let Some(capacity) = s
+                    .strip_prefix("heapless<")
+                    .and_then(|s| s.strip_suffix('>'))
+                {
+                    if let Ok(capacity) = capacity.trim().parse() {
+                        return Ok(BytesTy::Heapless(capacity));
+                    }
+                }
+                bail!("Invalid bytes type: {}", s)
+            }
         }
     }
 
@@ -404,6 +607,8 @@ impl BytesTy {
         match self {
             BytesTy::Vec => quote! { ::prost::alloc::vec::Vec<u8> },
             BytesTy::Bytes => quote! { ::prost::bytes::Bytes },
+            BytesTy::Boxed => quote! { ::prost::alloc::boxed::Box<[u8]> },
+            BytesTy::Heapless(capacity) => quote! { ::prost::heapless::Vec<u8, #capacity> },
         }
     }
 }
@@ -412,6 +617,10 @@ impl BytesTy {
 pub enum StringTy {
     String,
     ByteString,
+    SmolStr,
+    Cow,
+    /// A fixed-capacity `arrayvec::ArrayString` with the given capacity.
+    ArrayString(usize),
 }
 
 impl StringTy {
@@ -419,7 +628,19 @@ impl StringTy {
         match s {
             "string" => Ok(StringTy::String),
             "bytestring" => Ok(StringTy::ByteString),
-            _ => bail!("Invalid bytes type: {}", s),
+            "smol_str" => Ok(StringTy::SmolStr),
+            "cow" => Ok(StringTy::Cow),
+            _ => {
+                if let Some(capacity) = s
+                    .strip_prefix("array_string<")
+                    .and_then(|s| s.strip_suffix('>'))
+                {
+                    if let Ok(capacity) = capacity.trim().parse() {
+                        return Ok(StringTy::ArrayString(capacity));
+                    }
+                }
+                bail!("Invalid string type: {}", s)
+            }
         }
     }
 
@@ -427,6 +648,11 @@ impl StringTy {
         match self {
             StringTy::String => quote! { ::prost::alloc::string::String },
             StringTy::ByteString => quote! { ::prost::bytestring::ByteString },
+            StringTy::SmolStr => quote! { ::prost::smol_str::SmolStr },
+            StringTy::Cow => quote! { ::prost::alloc::borrow::Cow<'static, str> },
+            StringTy::ArrayString(capacity) => {
+                quote! { ::prost::arrayvec::ArrayString<#capacity> }
+            }
         }
     }
 }
@@ -544,6 +770,19 @@ impl Ty {
         }
     }
 
+    /// Returns the `prost::reflect` module which converts values of the type.
+    pub fn reflect_module(&self) -> TokenStream {
+        match *self {
+            Ty::String(..) => quote!(::prost::reflect::string),
+            Ty::Bytes(..) => quote!(::prost::reflect::bytes),
+            Ty::Enumeration(..) => quote!(::prost::reflect::enumeration),
+            _ => {
+                let module = self.module();
+                quote!(::prost::reflect::#module)
+            }
+        }
+    }
+
     // TODO: rename to 'owned_type'.
     pub fn rust_type(&self) -> TokenStream {
         match self {
@@ -582,6 +821,72 @@ impl Ty {
         }
     }
 
+    /// Returns an expression which converts a reference to a value of the type to JSON.
+    pub fn json(&self) -> TokenStream {
+        match *self {
+            Ty::Enumeration(ref ty) => quote! {
+                |value: &i32| ::prost::json::encode::enumeration(
+                    *value,
+                    #ty::from_i32(*value).map(|value| value.as_str_name()),
+                )
+            },
+            _ => {
+                let module = self.module();
+                quote!(::prost::json::encode::#module)
+            }
+        }
+    }
+
+    /// Returns an expression which converts a reference to a JSON value to a value of the type.
+    pub fn from_json(&self) -> TokenStream {
+        match *self {
+            Ty::Enumeration(ref ty) => quote! {
+                |value: &::prost::json::Value| ::prost::json::decode::enumeration(
+                    value,
+                    |name| #ty::from_str_name(name).map(|value| value as i32),
+                )
+            },
+            _ => {
+                let module = self.module();
+                quote!(::prost::json::decode::#module)
+            }
+        }
+    }
+
+    /// Returns an expression which formats a reference to a value of the type in the text format.
+    pub fn text(&self) -> TokenStream {
+        match *self {
+            Ty::Enumeration(ref ty) => quote! {
+                |value: &i32| ::prost::text_format::encode::enumeration(
+                    *value,
+                    #ty::from_i32(*value).map(|value| value.as_str_name()),
+                )
+            },
+            _ => {
+                let module = self.module();
+                quote!(::prost::text_format::encode::#module)
+            }
+        }
+    }
+
+    /// Returns an expression which parses a value of the type from the text format `parser`. The
+    /// expression must be passed directly as an argument, so that the types of closure arguments
+    /// can be inferred.
+    pub fn parse_text(&self) -> TokenStream {
+        match *self {
+            Ty::Enumeration(ref ty) => quote! {
+                |parser| ::prost::text_format::decode::enumeration(
+                    parser,
+                    |name| #ty::from_str_name(name).map(|value| value as i32),
+                )
+            },
+            _ => {
+                let module = self.module();
+                quote!(::prost::text_format::decode::#module)
+            }
+        }
+    }
+
     /// Returns false if the scalar type is length delimited (i.e., `string` or `bytes`).
     pub fn is_numeric(&self) -> bool {
         !matches!(self, Ty::String(..) | Ty::Bytes(..))
@@ -676,13 +981,11 @@ impl DefaultValue {
             Lit::Int(ref lit) if *ty == Ty::Double => DefaultValue::F64(lit.base10_parse()?),
 
             Lit::Bool(ref lit) if *ty == Ty::Bool => DefaultValue::Bool(lit.value),
-            Lit::Str(ref lit)
-                if *ty == Ty::String(StringTy::String) || *ty == Ty::String(StringTy::ByteString) =>
-            {
-                DefaultValue::String(lit.value())
-            }
+            Lit::Str(ref lit) if matches!(*ty, Ty::String(..)) => DefaultValue::String(lit.value()),
             Lit::ByteStr(ref lit)
-                if *ty == Ty::Bytes(BytesTy::Bytes) || *ty == Ty::Bytes(BytesTy::Vec) =>
+                if *ty == Ty::Bytes(BytesTy::Bytes)
+                    || *ty == Ty::Bytes(BytesTy::Vec)
+                    || *ty == Ty::Bytes(BytesTy::Boxed) =>
             {
                 DefaultValue::Bytes(lit.value())
             }
@@ -11,17 +11,138 @@ use proc_macro::TokenStream;
 use proc_macro2::Span;
 use quote::quote;
 use syn::{
-    punctuated::Punctuated, Data, DataEnum, DataStruct, DeriveInput, Expr, Fields, FieldsNamed,
-    FieldsUnnamed, Ident, Variant,
+    parse_quote, punctuated::Punctuated, Attribute, Data, DataEnum, DataStruct, DeriveInput, Expr,
+    Fields, FieldsNamed, FieldsUnnamed, Generics, Ident, Lit, Meta, MetaNameValue, Variant,
 };
 
 mod field;
 use crate::field::Field;
 
+/// A well-known type with a special JSON representation.
+enum WellKnownType {
+    Duration,
+    Timestamp,
+    FieldMask,
+}
+
+impl WellKnownType {
+    /// Parses the type from the value of a `#[prost(well_known = "...")]` attribute.
+    fn from_name(name: &str) -> Result<WellKnownType, Error> {
+        Ok(match name {
+            "Duration" => WellKnownType::Duration,
+            "Timestamp" => WellKnownType::Timestamp,
+            "FieldMask" => WellKnownType::FieldMask,
+            _ => bail!("unknown well-known type: {}", name),
+        })
+    }
+
+    /// Returns an expression which evaluates to the JSON representation of `self`.
+    fn json(&self) -> proc_macro2::TokenStream {
+        match *self {
+            WellKnownType::Duration => {
+                quote!(::prost::json::encode::duration(self.seconds, self.nanos))
+            }
+            WellKnownType::Timestamp => {
+                quote!(::prost::json::encode::timestamp(self.seconds, self.nanos))
+            }
+            WellKnownType::FieldMask => quote!(::prost::json::encode::field_mask(&self.paths)),
+        }
+    }
+
+    /// Returns statements which merge the JSON representation `value` into `self`.
+    fn from_json(&self) -> proc_macro2::TokenStream {
+        match *self {
+            WellKnownType::Duration => quote! {
+                let (seconds, nanos) = ::prost::json::decode::duration(value)?;
+                self.seconds = seconds;
+                self.nanos = nanos;
+            },
+            WellKnownType::Timestamp => quote! {
+                let (seconds, nanos) = ::prost::json::decode::timestamp(value)?;
+                self.seconds = seconds;
+                self.nanos = nanos;
+            },
+            WellKnownType::FieldMask => quote! {
+                self.paths = ::prost::json::decode::field_mask(value)?;
+            },
+        }
+    }
+}
+
+/// The attributes of a message or oneof type, e.g. `#[prost(json, text_format)]`.
+///
+/// The conversions are derived only for the types which ask for them, rather than whenever the
+/// corresponding feature of `prost` is enabled, so that enabling a feature doesn't break the
+/// derives of types whose fields don't support it.
+#[derive(Default)]
+struct TypeAttrs {
+    well_known_type: Option<WellKnownType>,
+    /// Derive `prost::json::ToJson` and `prost::json::FromJson`.
+    json: bool,
+    /// Derive `prost::text_format::ToText` and `prost::text_format::FromText`.
+    text_format: bool,
+    /// Derive `prost::reflect::Reflect`, and the `prost::descriptor::Descriptor` it builds on.
+    reflect: bool,
+}
+
+impl TypeAttrs {
+    fn new(attrs: &[Attribute]) -> Result<TypeAttrs, Error> {
+        let mut type_attrs = TypeAttrs::default();
+        for attr in field::prost_attrs(attrs.to_vec())? {
+            match attr {
+                Meta::NameValue(MetaNameValue {
+                    ref path,
+                    lit: Lit::Str(ref lit),
+                    ..
+                }) if path.is_ident("well_known") => {
+                    let ty = WellKnownType::from_name(&lit.value())?;
+                    if type_attrs.well_known_type.replace(ty).is_some() {
+                        bail!("duplicate well_known attributes");
+                    }
+                }
+                Meta::Path(ref path) if path.is_ident("json") => type_attrs.json = true,
+                Meta::Path(ref path) if path.is_ident("text_format") => {
+                    type_attrs.text_format = true
+                }
+                Meta::Path(ref path) if path.is_ident("reflect") => type_attrs.reflect = true,
+                _ => bail!("invalid type attribute: {:?}", attr),
+            }
+        }
+        Ok(type_attrs)
+    }
+}
+
+/// Returns a copy of `generics` with `bound`, e.g. `::prost::json::ToJson`, added to every type
+/// parameter.
+fn bounded_generics(generics: &Generics, bound: proc_macro2::TokenStream) -> Generics {
+    let mut bounded_generics = generics.clone();
+    let params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = bounded_generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+    bounded_generics
+}
+
+/// Returns a match pattern for the JSON object keys of a field: its JSON name, and its original
+/// Protobuf name if that differs.
+fn json_names(json_name: String, proto_name: String) -> proc_macro2::TokenStream {
+    if json_name == proto_name {
+        quote!(#json_name)
+    } else {
+        quote!(#json_name | #proto_name)
+    }
+}
+
 fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     let input: DeriveInput = syn::parse(input)?;
 
     let ident = input.ident;
+    let type_attrs = TypeAttrs::new(&input.attrs)?;
+    let well_known_type = type_attrs.well_known_type;
 
     let variant_data = match input.data {
         Data::Struct(variant_data) => variant_data,
@@ -51,6 +172,7 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
     };
 
     let mut next_tag: u32 = 1;
+    let mut unknown_fields = None;
     let mut fields = fields
         .into_iter()
         .enumerate()
@@ -58,6 +180,25 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             let field_ident = field
                 .ident
                 .unwrap_or_else(|| Ident::new(&idx.to_string(), Span::call_site()));
+            match field::is_unknown_fields(&field.attrs) {
+                Ok(true) if unknown_fields.is_some() => {
+                    return Some(Err(anyhow::anyhow!(
+                        "message {} has multiple unknown_fields fields",
+                        ident
+                    )));
+                }
+                Ok(true) => {
+                    unknown_fields = Some(field_ident);
+                    return None;
+                }
+                Ok(false) => (),
+                Err(err) => {
+                    return Some(Err(err.context(format!(
+                        "invalid message field {}.{}",
+                        ident, field_ident
+                    ))));
+                }
+            }
             match Field::new(field.attrs, Some(next_tag)) {
                 Ok(Some(field)) => {
                     next_tag = field.tags().iter().max().map(|t| t + 1).unwrap_or(next_tag);
@@ -100,6 +241,18 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         .iter()
         .map(|&(ref field_ident, ref field)| field.encode(quote!(self.#field_ident)));
 
+    let encode_deterministic = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| field.encode_deterministic(quote!(self.#field_ident)));
+
+    let encode_with_options = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| field.encode_with_options(quote!(self.#field_ident)));
+
+    let encoded_len_with_options = fields.iter().map(|&(ref field_ident, ref field)| {
+        field.encoded_len_with_options(quote!(self.#field_ident))
+    });
+
     let merge = fields.iter().map(|&(ref field_ident, ref field)| {
         let merge = field.merge(quote!(value));
         let tags = field
@@ -111,13 +264,78 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             #(#tags)* => {
                 let mut value = &mut self.#field_ident;
                 #merge.map_err(|mut error| {
-                    error.push(STRUCT_NAME, stringify!(#field_ident));
+                    error.push(STRUCT_NAME, stringify!(#field_ident), tag);
                     error
                 })
             },
         }
     });
 
+    // Required fields are checked on the encoding, since their presence isn't tracked.
+    let nested_required = fields
+        .iter()
+        .filter_map(|&(ref field_ident, ref field)| {
+            field.missing_required_fields(field_ident, &field::proto_name(field_ident))
+        })
+        .collect::<Vec<_>>();
+    let missing_required_values = nested_required.iter().map(|&(ref values, _, _)| values);
+    let missing_required_arms = nested_required.iter().map(|&(_, ref arm, _)| arm);
+    let missing_required_nested = nested_required.iter().map(|&(_, _, ref check)| check);
+    let missing_required_checks = fields
+        .iter()
+        .filter_map(|&(ref field_ident, ref field)| {
+            field.missing_required_check(&field::proto_name(field_ident))
+        })
+        .collect::<Vec<_>>();
+    let missing_required_fields =
+        if nested_required.is_empty() && missing_required_checks.is_empty() {
+            quote!()
+        } else {
+            quote! {
+                fn missing_required_fields(
+                    buf: &[u8],
+                    path: &str,
+                    missing: &mut ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+                ) where
+                    Self: Sized,
+                {
+                    #(#missing_required_values)*
+                    let mut tags = ::prost::alloc::vec::Vec::new();
+                    for field in ::prost::encoding::RawFields::new(buf).flatten() {
+                        match field.tag {
+                            #(#missing_required_arms)*
+                            _ => (),
+                        }
+                        tags.push(field.tag);
+                    }
+                    #(#missing_required_nested)*
+                    #(#missing_required_checks)*
+                }
+            }
+        };
+
+    // Unrecognized fields are either stored in the unknown fields container, and re-emitted after
+    // the known fields, or skipped.
+    let encode_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(self.#field_ident.encode_raw(buf);));
+    let encoded_len_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(+ self.#field_ident.encoded_len()));
+    let merge_unknown = match unknown_fields {
+        Some(ref field_ident) => quote!(self.#field_ident.merge_field(tag, wire_type, buf, ctx)),
+        None => quote!(::prost::encoding::skip_field(wire_type, tag, buf, ctx)),
+    };
+    let clear_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(self.#field_ident.clear();));
+    let default_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(#field_ident: ::core::default::Default::default(),));
+    let debug_unknown = unknown_fields.as_ref().map(|field_ident| {
+        quote!(let builder = builder.field(stringify!(#field_ident), &self.#field_ident);)
+    });
+
     let struct_name = if fields.is_empty() {
         quote!()
     } else {
@@ -133,6 +351,13 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         .iter()
         .map(|&(ref field_ident, ref field)| field.clear(quote!(self.#field_ident)));
 
+    let is_default = fields
+        .iter()
+        .map(|&(ref field_ident, ref field)| field.is_default(quote!(self.#field_ident)));
+    let is_default_unknown = unknown_fields
+        .as_ref()
+        .map(|field_ident| quote!(&& self.#field_ident.is_empty()));
+
     let default = fields.iter().map(|&(ref field_ident, ref field)| {
         let value = field.default();
         quote!(#field_ident: #value,)
@@ -153,6 +378,122 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         }
     };
 
+    let encoded_len_by_field = if cfg!(feature = "encoded-len-by-field") {
+        let lens = unsorted_fields.iter().map(|&(ref field_ident, ref field)| {
+            let encoded_len = field.encoded_len(quote!(self.#field_ident));
+            quote!((stringify!(#field_ident), #encoded_len))
+        });
+        let len_unknown = unknown_fields
+            .as_ref()
+            .map(|field_ident| quote!((stringify!(#field_ident), self.#field_ident.encoded_len())));
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Returns the contribution of each field to `encoded_len`, in declaration order.
+                ///
+                /// Each length includes the keys and length delimiters of the field, so the lengths
+                /// sum to `encoded_len`. Oneofs are reported under the name of their field.
+                pub fn encoded_len_by_field(
+                    &self,
+                ) -> ::prost::alloc::vec::Vec<(&'static str, usize)> {
+                    ::prost::alloc::vec![#(#lens,)* #len_unknown]
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let descriptor = if cfg!(feature = "descriptors") || type_attrs.reflect {
+        let descriptors = unsorted_fields
+            .iter()
+            .flat_map(|&(ref field_ident, ref field)| {
+                field.descriptors(
+                    &field::proto_name(field_ident),
+                    &field::json_name(field_ident),
+                )
+            });
+        quote! {
+            impl #impl_generics ::prost::descriptor::Descriptor for #ident #ty_generics
+            #where_clause {
+                fn fields() -> &'static [::prost::descriptor::FieldDescriptor] {
+                    const FIELDS: &[::prost::descriptor::FieldDescriptor] = &[#(#descriptors,)*];
+                    FIELDS
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let reflect = if type_attrs.reflect {
+        let tags = fields.iter().map(|&(_, ref field)| {
+            let tags = field.tags().into_iter().map(|tag| quote!(#tag));
+            quote!(#(#tags)|*)
+        });
+        let gets = fields
+            .iter()
+            .map(|&(ref field_ident, ref field)| field.reflect_get(quote!(self.#field_ident)));
+        let sets = fields
+            .iter()
+            .map(|&(ref field_ident, ref field)| field.reflect_set(quote!(self.#field_ident)));
+        let get_tags = tags.clone();
+        let set_tags = tags;
+        quote! {
+            impl #impl_generics ::prost::reflect::Reflect for #ident #ty_generics #where_clause {
+                fn field_descriptors(&self) -> &'static [::prost::descriptor::FieldDescriptor] {
+                    <Self as ::prost::descriptor::Descriptor>::fields()
+                }
+
+                fn get_field(
+                    &self,
+                    tag: u32,
+                ) -> ::core::option::Option<::prost::reflect::Value> {
+                    match tag {
+                        #(#get_tags => #gets,)*
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                #[allow(unused_variables)]
+                fn set_field(
+                    &mut self,
+                    tag: u32,
+                    value: ::prost::reflect::Value,
+                ) -> ::core::result::Result<(), ::prost::reflect::ReflectError> {
+                    match tag {
+                        #(#set_tags => #sets,)*
+                        _ => ::core::result::Result::Err(
+                            ::prost::reflect::ReflectError::UnknownField(tag),
+                        ),
+                    }
+                }
+
+                fn type_name(&self) -> &'static str {
+                    ::core::any::type_name::<Self>()
+                }
+
+                fn into_any(
+                    self: ::prost::alloc::boxed::Box<Self>,
+                ) -> ::prost::alloc::boxed::Box<dyn ::core::any::Any> {
+                    self
+                }
+
+                fn assign_any(
+                    &mut self,
+                    value: ::prost::alloc::boxed::Box<dyn ::core::any::Any>,
+                ) -> ::core::result::Result<
+                    (),
+                    ::prost::alloc::boxed::Box<dyn ::core::any::Any>,
+                > {
+                    *self = *value.downcast::<Self>()?;
+                    ::core::result::Result::Ok(())
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let debugs = unsorted_fields.iter().map(|&(ref field_ident, ref field)| {
         let wrapper = field.debug(quote!(self.#field_ident));
         let call = if is_struct {
@@ -173,13 +514,180 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(f.debug_tuple(stringify!(#ident)))
     };
 
+    let json = if type_attrs.json {
+        let body = match well_known_type {
+            Some(ref well_known_type) => well_known_type.json(),
+            None => {
+                let fields = unsorted_fields.iter().map(|&(ref field_ident, ref field)| {
+                    field.json(&field::json_name(field_ident), quote!(self.#field_ident))
+                });
+                quote! {
+                    #[allow(unused_mut)]
+                    let mut map = ::prost::json::Map::new();
+                    #(#fields)*
+                    ::prost::json::Value::Object(map)
+                }
+            }
+        };
+        let from_json_body = match well_known_type {
+            Some(ref well_known_type) => well_known_type.from_json(),
+            None => {
+                let mut arms = Vec::new();
+                let mut oneofs = Vec::new();
+                for &(ref field_ident, ref field) in &unsorted_fields {
+                    if let Field::Oneof(ref oneof) = *field {
+                        oneofs.push(oneof.merge_json(quote!(self.#field_ident)));
+                        continue;
+                    }
+                    let names = json_names(
+                        field::json_name(field_ident),
+                        field::proto_name(field_ident),
+                    );
+                    let from_json = field.from_json();
+                    arms.push(quote! {
+                        #names => {
+                            self.#field_ident = ::prost::json::decode::field(name, #from_json)?;
+                        }
+                    });
+                }
+                quote! {
+                    for (name, value) in ::prost::json::decode::object(value)? {
+                        if value.is_null() {
+                            continue;
+                        }
+                        match name.as_str() {
+                            #(#arms)*
+                            _ => {
+                                let merged = #(#oneofs ||)* false;
+                                if !merged {
+                                    ::prost::json::decode::unknown_field(name, options)?;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        let to_json_generics = bounded_generics(generics, quote!(::prost::json::ToJson));
+        let (to_json_impl_generics, _, to_json_where_clause) = to_json_generics.split_for_impl();
+        let from_json_generics = bounded_generics(
+            generics,
+            quote!(::prost::json::FromJson + ::core::default::Default),
+        );
+        let (from_json_impl_generics, _, from_json_where_clause) =
+            from_json_generics.split_for_impl();
+        quote! {
+            impl #to_json_impl_generics ::prost::json::ToJson for #ident #ty_generics
+            #to_json_where_clause {
+                fn to_json(&self) -> ::prost::json::Value {
+                    #body
+                }
+            }
+
+            impl #from_json_impl_generics ::prost::json::FromJson for #ident #ty_generics
+            #from_json_where_clause {
+                #[allow(unused_variables)]
+                fn merge_json(
+                    &mut self,
+                    value: &::prost::json::Value,
+                    options: ::prost::json::FromJsonOptions,
+                ) -> ::core::result::Result<(), ::prost::json::JsonError> {
+                    #from_json_body
+                    ::core::result::Result::Ok(())
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let text = if type_attrs.text_format {
+        let fields = fields.iter().map(|&(ref field_ident, ref field)| {
+            field.text(&field::proto_name(field_ident), quote!(self.#field_ident))
+        });
+        let mut arms = Vec::new();
+        let mut oneofs = Vec::new();
+        for &(ref field_ident, ref field) in &unsorted_fields {
+            if let Field::Oneof(ref oneof) = *field {
+                oneofs.push(oneof.merge_text(quote!(self.#field_ident)));
+                continue;
+            }
+            let name = field::proto_name(field_ident);
+            let merge_text = field.merge_text(quote!(self.#field_ident));
+            arms.push(quote! {
+                #name => {
+                    #merge_text
+                    ::core::result::Result::Ok(true)
+                }
+            });
+        }
+        let text_generics = bounded_generics(generics, quote!(::prost::text_format::ToText));
+        let (text_impl_generics, _, text_where_clause) = text_generics.split_for_impl();
+        let from_text_generics = bounded_generics(
+            generics,
+            quote!(::prost::text_format::FromText + ::core::default::Default),
+        );
+        let (from_text_impl_generics, _, from_text_where_clause) =
+            from_text_generics.split_for_impl();
+        quote! {
+            impl #text_impl_generics ::prost::text_format::ToText for #ident #ty_generics
+            #text_where_clause {
+                #[allow(unused_variables)]
+                fn write_text(&self, printer: &mut ::prost::text_format::Printer) {
+                    #(#fields)*
+                }
+            }
+
+            impl #from_text_impl_generics ::prost::text_format::FromText for #ident #ty_generics
+            #from_text_where_clause {
+                #[allow(unused_variables)]
+                fn merge_text_field(
+                    &mut self,
+                    name: &str,
+                    parser: &mut ::prost::text_format::Parser,
+                ) -> ::core::result::Result<bool, ::prost::text_format::ParseError> {
+                    match name {
+                        #(#arms)*
+                        _ => ::core::result::Result::Ok(#(#oneofs ||)* false),
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let expanded = quote! {
         impl #impl_generics ::prost::Message for #ident #ty_generics #where_clause {
             #[allow(unused_variables)]
             fn encode_raw<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
                 #(#encode)*
+                #encode_unknown
             }
 
+            #[allow(unused_variables)]
+            fn encode_raw_deterministic<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
+                #(#encode_deterministic)*
+                #encode_unknown
+            }
+
+            #[allow(unused_variables)]
+            fn encode_raw_with_options<B>(
+                &self,
+                buf: &mut B,
+                options: &::prost::encoding::EncodeOptions,
+            ) where B: ::prost::bytes::BufMut {
+                #(#encode_with_options)*
+                #encode_unknown
+            }
+
+            #[allow(unused_variables)]
+            fn encoded_len_with_options(&self, options: &::prost::encoding::EncodeOptions) -> usize {
+                0 #(+ #encoded_len_with_options)* #encoded_len_unknown
+            }
+
+            #missing_required_fields
+
             #[allow(unused_variables)]
             fn merge_field<B>(
                 &mut self,
@@ -192,17 +700,22 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
                 #struct_name
                 match tag {
                     #(#merge)*
-                    _ => ::prost::encoding::skip_field(wire_type, tag, buf, ctx),
+                    _ => #merge_unknown,
                 }
             }
 
             #[inline]
             fn encoded_len(&self) -> usize {
-                0 #(+ #encoded_len)*
+                0 #(+ #encoded_len)* #encoded_len_unknown
+            }
+
+            fn is_default(&self) -> bool {
+                true #(&& #is_default)* #is_default_unknown
             }
 
             fn clear(&mut self) {
                 #(#clear;)*
+                #clear_unknown
             }
         }
 
@@ -210,6 +723,7 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             fn default() -> Self {
                 #ident {
                     #(#default)*
+                    #default_unknown
                 }
             }
         }
@@ -218,11 +732,20 @@ fn try_message(input: TokenStream) -> Result<TokenStream, Error> {
             fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
                 let mut builder = #debug_builder;
                 #(#debugs;)*
+                #debug_unknown
                 builder.finish()
             }
         }
 
         #methods
+
+        #encoded_len_by_field
+
+        #descriptor
+        #reflect
+
+        #json
+        #text
     };
 
     Ok(expanded.into())
@@ -247,8 +770,9 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
     };
 
     // Map the variants into 'fields'.
-    let mut variants: Vec<(Ident, Expr)> = Vec::new();
+    let mut variants: Vec<(Ident, Expr, String)> = Vec::new();
     for Variant {
+        attrs,
         ident,
         fields,
         discriminant,
@@ -262,8 +786,23 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
             }
         }
 
+        let mut name = None;
+        for attr in field::prost_attrs(attrs)? {
+            match attr {
+                Meta::NameValue(MetaNameValue {
+                    ref path,
+                    lit: Lit::Str(ref lit),
+                    ..
+                }) if path.is_ident("name") => {
+                    field::set_option(&mut name, lit.value(), "duplicate name attributes")?
+                }
+                _ => bail!("invalid enumeration variant attribute: {:?}", attr),
+            }
+        }
+        let name = name.unwrap_or_else(|| ident.to_string());
+
         match discriminant {
-            Some((_, expr)) => variants.push((ident, expr)),
+            Some((_, expr)) => variants.push((ident, expr, name)),
             None => bail!("Enumeration variants must have a disriminant"),
         }
     }
@@ -276,10 +815,16 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
 
     let is_valid = variants
         .iter()
-        .map(|&(_, ref value)| quote!(#value => true));
-    let from = variants.iter().map(
-        |&(ref variant, ref value)| quote!(#value => ::core::option::Option::Some(#ident::#variant)),
-    );
+        .map(|&(_, ref value, _)| quote!(#value => true));
+    let from = variants.iter().map(|&(ref variant, ref value, _)| {
+        quote!(#value => ::core::option::Option::Some(#ident::#variant))
+    });
+    let as_str_name = variants
+        .iter()
+        .map(|&(ref variant, _, ref name)| quote!(#ident::#variant => #name));
+    let from_str_name = variants.iter().map(|&(ref variant, _, ref name)| {
+        quote!(#name => ::core::option::Option::Some(#ident::#variant))
+    });
 
     let is_valid_doc = format!("Returns `true` if `value` is a variant of `{}`.", ident);
     let from_i32_doc = format!(
@@ -287,6 +832,22 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
         ident
     );
 
+    let try_from_i32_doc = format!(
+        "Converts an `i32` to a `{}`, or returns the value as an `UnknownEnumValue` error if it is \
+         not a valid variant.",
+        ident
+    );
+
+    let as_str_name_doc = format!(
+        "Returns the name of the `{}` variant, as used in the Protobuf definition.",
+        ident
+    );
+    let from_str_name_doc = format!(
+        "Converts a Protobuf variant name to a `{}`, or `None` if `value` is not a valid variant \
+         name.",
+        ident
+    );
+
     let expanded = quote! {
         impl #impl_generics #ident #ty_generics #where_clause {
             #[doc=#is_valid_doc]
@@ -304,6 +865,28 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
                     _ => ::core::option::Option::None,
                 }
             }
+
+            #[doc=#try_from_i32_doc]
+            pub fn try_from_i32(
+                value: i32,
+            ) -> ::core::result::Result<#ident, ::prost::UnknownEnumValue> {
+                #ident::from_i32(value).ok_or(::prost::UnknownEnumValue(value))
+            }
+
+            #[doc=#as_str_name_doc]
+            pub fn as_str_name(&self) -> &'static str {
+                match *self {
+                    #(#as_str_name,)*
+                }
+            }
+
+            #[doc=#from_str_name_doc]
+            pub fn from_str_name(value: &str) -> ::core::option::Option<#ident> {
+                match value {
+                    #(#from_str_name,)*
+                    _ => ::core::option::Option::None,
+                }
+            }
         }
 
         impl #impl_generics ::core::default::Default for #ident #ty_generics #where_clause {
@@ -317,6 +900,14 @@ fn try_enumeration(input: TokenStream) -> Result<TokenStream, Error> {
                 value as i32
             }
         }
+
+        impl #impl_generics ::core::convert::TryFrom::<i32> for #ident #ty_generics #where_clause {
+            type Error = ::prost::UnknownEnumValue;
+
+            fn try_from(value: i32) -> ::core::result::Result<#ident, ::prost::UnknownEnumValue> {
+                #ident::try_from_i32(value)
+            }
+        }
     };
 
     Ok(expanded.into())
@@ -331,6 +922,10 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
     let input: DeriveInput = syn::parse(input)?;
 
     let ident = input.ident;
+    let type_attrs = TypeAttrs::new(&input.attrs)?;
+    if type_attrs.well_known_type.is_some() {
+        bail!("invalid oneof attribute: well_known");
+    }
 
     let variants = match input.data {
         Data::Enum(DataEnum { variants, .. }) => variants,
@@ -390,6 +985,16 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#ident::#variant_ident(ref value) => { #encode })
     });
 
+    let encode_deterministic = fields.iter().map(|&(ref variant_ident, ref field)| {
+        let encode = field.encode_deterministic(quote!(*value));
+        quote!(#ident::#variant_ident(ref value) => { #encode })
+    });
+
+    let encode_with_options = fields.iter().map(|&(ref variant_ident, ref field)| {
+        let encode = field.encode_with_options(quote!(*value));
+        quote!(#ident::#variant_ident(ref value) => { #encode })
+    });
+
     let merge = fields.iter().map(|&(ref variant_ident, ref field)| {
         let tag = field.tags()[0];
         let merge = field.merge(quote!(value));
@@ -414,6 +1019,11 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         quote!(#ident::#variant_ident(ref value) => #encoded_len)
     });
 
+    let encoded_len_with_options = fields.iter().map(|&(ref variant_ident, ref field)| {
+        let encoded_len = field.encoded_len_with_options(quote!(*value));
+        quote!(#ident::#variant_ident(ref value) => #encoded_len)
+    });
+
     let debug = fields.iter().map(|&(ref variant_ident, ref field)| {
         let wrapper = field.debug(quote!(*value));
         quote!(#ident::#variant_ident(ref value) => {
@@ -424,6 +1034,213 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
         })
     });
 
+    let reflect = if type_attrs.reflect {
+        let gets = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let tag = field.tags()[0];
+            let (module, _) = field.reflect_module();
+            quote! {
+                #ident::#variant_ident(ref value) if tag == #tag => {
+                    ::core::option::Option::Some(#module::get(value))
+                }
+            }
+        });
+        let sets = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let tag = field.tags()[0];
+            let (module, _) = field.reflect_module();
+            quote! {
+                #tag => ::prost::reflect::set_owned(value, #module::set).map(|value| {
+                    *field = ::core::option::Option::Some(#ident::#variant_ident(value))
+                })
+            }
+        });
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Returns the value of the variant with the given tag, if it is set.
+                #[doc(hidden)]
+                pub fn reflect_get(
+                    &self,
+                    tag: u32,
+                ) -> ::core::option::Option<::prost::reflect::Value> {
+                    match *self {
+                        #(#gets)*
+                        _ => ::core::option::Option::None,
+                    }
+                }
+
+                /// Sets the oneof to the variant with the given tag.
+                #[doc(hidden)]
+                pub fn reflect_set(
+                    field: &mut ::core::option::Option<#ident #ty_generics>,
+                    tag: u32,
+                    value: ::prost::reflect::Value,
+                ) -> ::core::result::Result<(), ::prost::reflect::ReflectError> {
+                    match tag {
+                        #(#sets,)*
+                        _ => ::core::result::Result::Err(
+                            ::prost::reflect::ReflectError::UnknownField(tag),
+                        ),
+                    }
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let missing_required = fields.iter().filter_map(|&(ref variant_ident, ref field)| {
+        let tag = field.tags()[0];
+        let name = field::variant_proto_name(variant_ident);
+        match *field {
+            Field::Message(..) | Field::Group(..) => Some(quote! {
+                #tag => ::prost::encoding::message::missing_required_fields(
+                    |value: &Self| match *value {
+                        #ident::#variant_ident(ref value) => ::core::option::Option::Some(value),
+                        _ => ::core::option::Option::None,
+                    },
+                    buf,
+                    &::prost::alloc::format!("{}{}.", path, #name),
+                    missing,
+                ),
+            }),
+            _ => None,
+        }
+    });
+
+    let descriptor = if cfg!(feature = "descriptors") || type_attrs.reflect {
+        let descriptors = fields.iter().flat_map(|&(ref variant_ident, ref field)| {
+            field.descriptors(
+                &field::variant_proto_name(variant_ident),
+                &field::variant_json_name(variant_ident),
+            )
+        });
+        let len = fields.len();
+        quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// The descriptors of the variants, referenced by the descriptors of messages.
+                #[doc(hidden)]
+                pub const FIELDS: [::prost::descriptor::FieldDescriptor; #len] =
+                    [#(#descriptors,)*];
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let json = if type_attrs.json {
+        let json = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let json = field.json(&field::variant_json_name(variant_ident), quote!(*value));
+            quote!(#ident::#variant_ident(ref value) => { #json })
+        });
+        let merge_json = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let names = json_names(
+                field::variant_json_name(variant_ident),
+                field::variant_proto_name(variant_ident),
+            );
+            let from_json = field.from_json();
+            quote! {
+                #names => {
+                    let value = ::prost::json::decode::field(name, #from_json)?;
+                    *field = ::core::option::Option::Some(#ident::#variant_ident(value));
+                }
+            }
+        });
+        let to_json_generics = bounded_generics(generics, quote!(::prost::json::ToJson));
+        let (to_json_impl_generics, _, to_json_where_clause) = to_json_generics.split_for_impl();
+        let from_json_generics = bounded_generics(
+            generics,
+            quote!(::prost::json::FromJson + ::core::default::Default),
+        );
+        let (from_json_impl_generics, _, from_json_where_clause) =
+            from_json_generics.split_for_impl();
+        quote! {
+            impl #to_json_impl_generics #ident #ty_generics #to_json_where_clause {
+                /// Inserts the JSON representation of the occupied variant into `map`.
+                pub fn to_json_fields(
+                    &self,
+                    map: &mut ::prost::json::Map<::prost::alloc::string::String, ::prost::json::Value>,
+                ) {
+                    match *self {
+                        #(#json,)*
+                    }
+                }
+            }
+
+            impl #from_json_impl_generics #ident #ty_generics #from_json_where_clause {
+                /// Merges the JSON value of the message field `name` into `field`, if `name` is
+                /// the JSON or Protobuf name of a variant. Returns `false` otherwise.
+                #[allow(unused_variables)]
+                pub fn merge_json_field(
+                    field: &mut ::core::option::Option<#ident #ty_generics>,
+                    name: &str,
+                    value: &::prost::json::Value,
+                    options: ::prost::json::FromJsonOptions,
+                ) -> ::core::result::Result<bool, ::prost::json::JsonError> {
+                    match name {
+                        #(#merge_json)*
+                        _ => return ::core::result::Result::Ok(false),
+                    }
+                    ::core::result::Result::Ok(true)
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
+    let text = if type_attrs.text_format {
+        let text = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let text = field.text(&field::variant_proto_name(variant_ident), quote!(*value));
+            quote!(#ident::#variant_ident(ref value) => { #text })
+        });
+        let merge_text = fields.iter().map(|&(ref variant_ident, ref field)| {
+            let name = field::variant_proto_name(variant_ident);
+            let parse_text_value = field.parse_text_value();
+            quote! {
+                #name => {
+                    let value = #parse_text_value?;
+                    *field = ::core::option::Option::Some(#ident::#variant_ident(value));
+                }
+            }
+        });
+        let text_generics = bounded_generics(generics, quote!(::prost::text_format::ToText));
+        let (text_impl_generics, _, text_where_clause) = text_generics.split_for_impl();
+        let from_text_generics = bounded_generics(
+            generics,
+            quote!(::prost::text_format::FromText + ::core::default::Default),
+        );
+        let (from_text_impl_generics, _, from_text_where_clause) =
+            from_text_generics.split_for_impl();
+        quote! {
+            impl #text_impl_generics #ident #ty_generics #text_where_clause {
+                /// Writes the occupied variant to the text format `printer`.
+                pub fn write_text_fields(&self, printer: &mut ::prost::text_format::Printer) {
+                    match *self {
+                        #(#text,)*
+                    }
+                }
+            }
+
+            impl #from_text_impl_generics #ident #ty_generics #from_text_where_clause {
+                /// Parses the value of the message field `name` from the text format `parser`
+                /// into `field`, if `name` is the Protobuf name of a variant. Returns `false`
+                /// otherwise.
+                pub fn merge_text_field(
+                    field: &mut ::core::option::Option<#ident #ty_generics>,
+                    name: &str,
+                    parser: &mut ::prost::text_format::Parser,
+                ) -> ::core::result::Result<bool, ::prost::text_format::ParseError> {
+                    match name {
+                        #(#merge_text)*
+                        _ => return ::core::result::Result::Ok(false),
+                    }
+                    ::core::result::Result::Ok(true)
+                }
+            }
+        }
+    } else {
+        quote!()
+    };
+
     let expanded = quote! {
         impl #impl_generics #ident #ty_generics #where_clause {
             pub fn encode<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
@@ -432,6 +1249,40 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                 }
             }
 
+            pub fn encode_deterministic<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
+                match *self {
+                    #(#encode_deterministic,)*
+                }
+            }
+
+            #[doc(hidden)]
+            #[allow(unused_variables)]
+            pub fn encode_with_options<B>(
+                &self,
+                buf: &mut B,
+                options: &::prost::encoding::EncodeOptions,
+            ) where B: ::prost::bytes::BufMut {
+                match *self {
+                    #(#encode_with_options,)*
+                }
+            }
+
+            /// Checks the required fields of the message held by the variant with tag `tag`, which
+            /// was decoded from `buf`.
+            #[doc(hidden)]
+            #[allow(unused_variables, unreachable_patterns)]
+            pub fn missing_required_fields(
+                tag: u32,
+                buf: &[u8],
+                path: &str,
+                missing: &mut ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+            ) {
+                match tag {
+                    #(#missing_required)*
+                    _ => (),
+                }
+            }
+
             pub fn merge<B>(
                 field: &mut ::core::option::Option<#ident #ty_generics>,
                 tag: u32,
@@ -452,6 +1303,43 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                     #(#encoded_len,)*
                 }
             }
+
+            #[doc(hidden)]
+            #[allow(unused_variables)]
+            pub fn encoded_len_with_options(
+                &self,
+                options: &::prost::encoding::EncodeOptions,
+            ) -> usize {
+                match *self {
+                    #(#encoded_len_with_options,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::prost::Oneof for #ident #ty_generics #where_clause {
+            fn encode<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
+                Self::encode(self, buf)
+            }
+
+            fn encode_deterministic<B>(&self, buf: &mut B) where B: ::prost::bytes::BufMut {
+                Self::encode_deterministic(self, buf)
+            }
+
+            fn merge<B>(
+                field: &mut ::core::option::Option<Self>,
+                tag: u32,
+                wire_type: ::prost::encoding::WireType,
+                buf: &mut B,
+                ctx: ::prost::encoding::DecodeContext,
+            ) -> ::core::result::Result<(), ::prost::DecodeError>
+            where B: ::prost::bytes::Buf {
+                Self::merge(field, tag, wire_type, buf, ctx)
+            }
+
+            #[inline]
+            fn encoded_len(&self) -> usize {
+                Self::encoded_len(self)
+            }
         }
 
         impl #impl_generics ::core::fmt::Debug for #ident #ty_generics #where_clause {
@@ -461,6 +1349,12 @@ fn try_oneof(input: TokenStream) -> Result<TokenStream, Error> {
                 }
             }
         }
+
+        #descriptor
+        #reflect
+
+        #json
+        #text
     };
 
     Ok(expanded.into())
@@ -1,5 +1,8 @@
 /// The version number of protocol compiler.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Version {
     #[prost(int32, optional, tag="1")]
     pub major: ::core::option::Option<i32>,
@@ -14,6 +17,9 @@ pub struct Version {
 }
 /// An encoded CodeGeneratorRequest is written to the plugin's stdin.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct CodeGeneratorRequest {
     /// The .proto files that were explicitly listed on the command-line.  The
     /// code generator should generate code only for these files.  Each file's
@@ -45,6 +51,9 @@ pub struct CodeGeneratorRequest {
 }
 /// The plugin writes an encoded CodeGeneratorResponse to stdout.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct CodeGeneratorResponse {
     /// Error message.  If non-empty, code generation failed.  The plugin process
     /// should exit with status code zero even if it reports an error in this way.
@@ -67,6 +76,9 @@ pub struct CodeGeneratorResponse {
 pub mod code_generator_response {
     /// Represents a single generated file.
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct File {
         /// The file name, relative to the output directory.  The name must not
         /// contain "." or ".." components and must be relative, not be absolute (so,
@@ -133,7 +145,9 @@ pub mod code_generator_response {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Feature {
+        #[prost(name = "FEATURE_NONE")]
         None = 0,
+        #[prost(name = "FEATURE_PROTO3_OPTIONAL")]
         Proto3Optional = 1,
     }
 }
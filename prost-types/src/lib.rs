@@ -12,45 +12,196 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 use core::convert::TryFrom;
+use core::fmt;
 use core::i32;
 use core::i64;
 use core::time;
 
+use prost::{DecodeError, Message, Name};
 
 include!("protobuf.rs");
 pub mod compiler {
     include!("compiler.rs");
 }
 
+// The well-known types are checked in rather than generated by the build, so their `Name`
+// implementations are written out here.
+macro_rules! impl_name {
+    ($($ty:ident),*) => {
+        $(
+            impl Name for $ty {
+                const NAME: &'static str = stringify!($ty);
+                const PACKAGE: &'static str = "google.protobuf";
+            }
+        )*
+    };
+}
+
+impl_name!(
+    Any,
+    Api,
+    Duration,
+    Enum,
+    EnumValue,
+    Field,
+    FieldMask,
+    ListValue,
+    Method,
+    Mixin,
+    Option,
+    SourceContext,
+    Struct,
+    Timestamp,
+    Type,
+    Value
+);
+
+impl Any {
+    /// Packs a message into an `Any`, along with the type URL of the message type.
+    pub fn pack<M>(message: &M) -> Any
+    where
+        M: Name,
+    {
+        Any {
+            type_url: M::type_url(),
+            value: message.encode_to_vec(),
+        }
+    }
+
+    /// Unpacks the message held by the `Any`.
+    ///
+    /// The message type is identified by the last segment of the type URL, which holds its
+    /// fully-qualified name, so type URLs with any prefix are accepted.
+    pub fn unpack<M>(&self) -> Result<M, AnyError>
+    where
+        M: Name + Default,
+    {
+        let name = self.type_url.rsplit('/').next().unwrap_or("");
+        if name != M::full_name() {
+            return Err(AnyError::TypeUrlMismatch {
+                expected: M::type_url(),
+                found: self.type_url.clone(),
+            });
+        }
+        M::decode(&self.value[..]).map_err(AnyError::Decode)
+    }
+
+    /// Packs a message into an `Any`. Equivalent to `Any::pack`.
+    pub fn from_msg<M>(message: &M) -> Any
+    where
+        M: Name,
+    {
+        Any::pack(message)
+    }
+
+    /// Unpacks the message held by the `Any`, like `Any::unpack`, but reports a type URL mismatch
+    /// as a `DecodeError`, for callers which only deal in decode errors.
+    pub fn to_msg<M>(&self) -> Result<M, DecodeError>
+    where
+        M: Name + Default,
+    {
+        self.unpack().map_err(DecodeError::from)
+    }
+}
+
+/// An error unpacking a message from an `Any`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnyError {
+    /// The `Any` holds a different message type than the one requested.
+    TypeUrlMismatch {
+        /// The type URL of the requested message type.
+        expected: prost::alloc::string::String,
+        /// The type URL of the `Any`.
+        found: prost::alloc::string::String,
+    },
+    /// The message held by the `Any` failed to decode.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for AnyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            AnyError::TypeUrlMismatch {
+                ref expected,
+                ref found,
+            } => write!(f, "unexpected type URL: {} (expected {})", found, expected),
+            AnyError::Decode(ref error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AnyError {
+    fn source(&self) -> core::option::Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            AnyError::TypeUrlMismatch { .. } => None,
+            AnyError::Decode(ref error) => Some(error),
+        }
+    }
+}
+
+impl From<AnyError> for DecodeError {
+    fn from(error: AnyError) -> DecodeError {
+        match error {
+            AnyError::TypeUrlMismatch { .. } => {
+                DecodeError::new(prost::alloc::format!("{}", error))
+            }
+            AnyError::Decode(error) => error,
+        }
+    }
+}
+
 // The Protobuf `Duration` and `Timestamp` types can't delegate to the standard library equivalents
-// because the Protobuf versions are signed. To make them easier to work with, `From` conversions
-// are defined in both directions.
+// because the Protobuf versions are signed. To make them easier to work with, conversions are
+// defined in both directions; those to the standard library types fail for values they can't
+// represent.
 
 const NANOS_PER_SECOND: i32 = 1_000_000_000;
 
 impl Duration {
     /// Normalizes the duration to a canonical format.
     ///
+    /// Nanos outside of `-999_999_999..=999_999_999` are carried into the seconds, and the nanos
+    /// are given the same sign as the seconds. If the carry overflows an `i64`, the seconds
+    /// saturate at its bounds, which silently changes the duration; use `try_normalize` to detect
+    /// this.
+    ///
+    /// Durations are encoded as they are, so call this before encoding a duration built by hand
+    /// to produce canonical output.
+    ///
     /// Based on [`google::protobuf::util::CreateNormalized`][1].
     /// [1]: https://github.com/google/protobuf/blob/v3.3.2/src/google/protobuf/util/time_util.cc#L79-L100
     pub fn normalize(&mut self) {
-        // Make sure nanos is in the range.
-        if self.nanos <= -NANOS_PER_SECOND || self.nanos >= NANOS_PER_SECOND {
-            self.seconds += (self.nanos / NANOS_PER_SECOND) as i64;
+        if !self.try_normalize() {
+            // The carry has the sign of the nanos, which are kept the same.
+            self.seconds = if self.nanos < 0 { i64::MIN } else { i64::MAX };
             self.nanos %= NANOS_PER_SECOND;
         }
+    }
+
+    /// Normalizes the duration like `normalize`, returning `false` and leaving the duration
+    /// unchanged if the seconds would overflow.
+    pub fn try_normalize(&mut self) -> bool {
+        // Make sure nanos is in the range.
+        let carry = i64::from(self.nanos / NANOS_PER_SECOND);
+        let mut seconds = match self.seconds.checked_add(carry) {
+            Some(seconds) => seconds,
+            None => return false,
+        };
+        let mut nanos = self.nanos % NANOS_PER_SECOND;
 
-        // nanos should have the same sign as seconds.
-        if self.seconds < 0 && self.nanos > 0 {
-            self.seconds += 1;
-            self.nanos -= NANOS_PER_SECOND;
-        } else if self.seconds > 0 && self.nanos < 0 {
-            self.seconds -= 1;
-            self.nanos += NANOS_PER_SECOND;
+        // nanos should have the same sign as seconds. Moving the seconds towards zero can't
+        // overflow.
+        if seconds < 0 && nanos > 0 {
+            seconds += 1;
+            nanos -= NANOS_PER_SECOND;
+        } else if seconds > 0 && nanos < 0 {
+            seconds -= 1;
+            nanos += NANOS_PER_SECOND;
         }
-        // TODO: should this be checked?
-        // debug_assert!(self.seconds >= -315_576_000_000 && self.seconds <= 315_576_000_000,
-        //               "invalid duration: {:?}", self);
+        self.seconds = seconds;
+        self.nanos = nanos;
+        true
     }
 }
 
@@ -76,48 +227,99 @@ impl From<time::Duration> for Duration {
 }
 
 impl TryFrom<Duration> for time::Duration {
-    type Error = time::Duration;
+    type Error = DurationError;
 
-    /// Converts a `Duration` to a result containing a positive (`Ok`) or negative (`Err`)
-    /// `std::time::Duration`.
-    fn try_from(mut duration: Duration) -> Result<time::Duration, time::Duration> {
-        duration.normalize();
-        if duration.seconds >= 0 {
+    /// Converts a `Duration` to a `std::time::Duration`, failing if the duration is negative.
+    fn try_from(mut duration: Duration) -> Result<time::Duration, DurationError> {
+        if !duration.try_normalize() {
+            return Err(DurationError::OutOfRange);
+        }
+        if duration.seconds >= 0 && duration.nanos >= 0 {
             Ok(time::Duration::new(
                 duration.seconds as u64,
                 duration.nanos as u32,
             ))
         } else {
-            Err(time::Duration::new(
-                (-duration.seconds) as u64,
-                (-duration.nanos) as u32,
-            ))
+            // `wrapping_neg` and the cast give the magnitude of `i64::MIN` too.
+            Err(DurationError::NegativeDuration(time::Duration::new(
+                duration.seconds.wrapping_neg() as u64,
+                duration.nanos.wrapping_neg() as u32,
+            )))
+        }
+    }
+}
+
+/// An error converting a `Duration` to a `std::time::Duration`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DurationError {
+    /// The duration is negative. Holds the magnitude of the duration.
+    NegativeDuration(time::Duration),
+    /// The nanoseconds carried into the seconds overflow an `i64`.
+    OutOfRange,
+}
+
+impl fmt::Display for DurationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DurationError::NegativeDuration(duration) => {
+                write!(f, "duration is negative: -{:?}", duration)
+            }
+            DurationError::OutOfRange => f.write_str("duration is out of range"),
         }
     }
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for DurationError {}
+
 impl Timestamp {
     /// Normalizes the timestamp to a canonical format.
     ///
+    /// Nanos outside of `0..=999_999_999` are carried into the seconds. If the carry overflows an
+    /// `i64`, the seconds saturate at its bounds, which silently changes the timestamp; use
+    /// `try_normalize` to detect this.
+    ///
+    /// Timestamps are encoded as they are, so call this before encoding a timestamp built by hand
+    /// to produce canonical output.
+    ///
     /// Based on [`google::protobuf::util::CreateNormalized`][1].
     /// [1]: https://github.com/google/protobuf/blob/v3.3.2/src/google/protobuf/util/time_util.cc#L59-L77
-    #[cfg(feature = "std")]
     pub fn normalize(&mut self) {
-        // Make sure nanos is in the range.
-        if self.nanos <= -NANOS_PER_SECOND || self.nanos >= NANOS_PER_SECOND {
-            self.seconds += (self.nanos / NANOS_PER_SECOND) as i64;
-            self.nanos %= NANOS_PER_SECOND;
+        if !self.try_normalize() {
+            // The seconds overflow in the direction of the sign of the nanos.
+            if self.nanos < 0 {
+                self.seconds = i64::MIN;
+                self.nanos = self.nanos % NANOS_PER_SECOND + NANOS_PER_SECOND;
+            } else {
+                self.seconds = i64::MAX;
+                self.nanos %= NANOS_PER_SECOND;
+            }
         }
+    }
+
+    /// Normalizes the timestamp like `normalize`, returning `false` and leaving the timestamp
+    /// unchanged if the seconds would overflow.
+    pub fn try_normalize(&mut self) -> bool {
+        // Make sure nanos is in the range.
+        let carry = i64::from(self.nanos / NANOS_PER_SECOND);
+        let mut seconds = match self.seconds.checked_add(carry) {
+            Some(seconds) => seconds,
+            None => return false,
+        };
+        let mut nanos = self.nanos % NANOS_PER_SECOND;
 
         // For Timestamp nanos should be in the range [0, 999999999].
-        if self.nanos < 0 {
-            self.seconds -= 1;
-            self.nanos += NANOS_PER_SECOND;
+        if nanos < 0 {
+            seconds = match seconds.checked_sub(1) {
+                Some(seconds) => seconds,
+                None => return false,
+            };
+            nanos += NANOS_PER_SECOND;
         }
-
-        // TODO: should this be checked?
-        // debug_assert!(self.seconds >= -62_135_596_800 && self.seconds <= 253_402_300_799,
-        //               "invalid timestamp: {:?}", self);
+        self.seconds = seconds;
+        self.nanos = nanos;
+        true
     }
 }
 
@@ -145,19 +347,87 @@ impl From<std::time::SystemTime> for Timestamp {
 }
 
 #[cfg(feature = "std")]
-impl From<Timestamp> for std::time::SystemTime {
-    fn from(mut timestamp: Timestamp) -> std::time::SystemTime {
-        timestamp.normalize();
-        let system_time = if timestamp.seconds >= 0 {
-            std::time::UNIX_EPOCH + time::Duration::from_secs(timestamp.seconds as u64)
+impl TryFrom<Timestamp> for std::time::SystemTime {
+    type Error = TimestampError;
+
+    /// Converts a `Timestamp` to a `SystemTime`, failing if it is outside of the range supported
+    /// by the platform.
+    fn try_from(timestamp: Timestamp) -> Result<std::time::SystemTime, TimestampError> {
+        let mut normalized = timestamp.clone();
+        if !normalized.try_normalize() {
+            return Err(TimestampError::OutOfSystemRange(timestamp));
+        }
+        let system_time = if normalized.seconds >= 0 {
+            let seconds = time::Duration::from_secs(normalized.seconds as u64);
+            std::time::UNIX_EPOCH.checked_add(seconds)
         } else {
-            std::time::UNIX_EPOCH - time::Duration::from_secs((-timestamp.seconds) as u64)
+            // `wrapping_neg` and the cast give the magnitude of `i64::MIN` too.
+            let seconds = time::Duration::from_secs(normalized.seconds.wrapping_neg() as u64);
+            std::time::UNIX_EPOCH.checked_sub(seconds)
         };
+        system_time
+            .and_then(|system_time| {
+                system_time.checked_add(time::Duration::from_nanos(normalized.nanos as u64))
+            })
+            .ok_or(TimestampError::OutOfSystemRange(timestamp))
+    }
+}
 
-        system_time + time::Duration::from_nanos(timestamp.nanos as u64)
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(date_time: chrono::DateTime<chrono::Utc>) -> Timestamp {
+        // The sub-second nanos exceed one second during a leap second.
+        let nanos = date_time.timestamp_subsec_nanos() as i32;
+        Timestamp {
+            seconds: date_time.timestamp() + i64::from(nanos / NANOS_PER_SECOND),
+            nanos: nanos % NANOS_PER_SECOND,
+        }
     }
 }
 
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = TimestampError;
+
+    /// Converts a `Timestamp` to a `DateTime<Utc>`, failing if it is outside of the range
+    /// supported by `chrono`.
+    fn try_from(timestamp: Timestamp) -> Result<chrono::DateTime<chrono::Utc>, TimestampError> {
+        use chrono::TimeZone;
+
+        let mut normalized = timestamp.clone();
+        if !normalized.try_normalize() {
+            return Err(TimestampError::OutOfSystemRange(timestamp));
+        }
+        chrono::Utc
+            .timestamp_opt(normalized.seconds, normalized.nanos as u32)
+            .single()
+            .ok_or(TimestampError::OutOfSystemRange(timestamp))
+    }
+}
+
+/// An error converting a `Timestamp` to a system or `chrono` time.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TimestampError {
+    /// The timestamp is outside of the range of the target type. Holds the original timestamp.
+    OutOfSystemRange(Timestamp),
+}
+
+impl fmt::Display for TimestampError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            TimestampError::OutOfSystemRange(ref timestamp) => write!(
+                f,
+                "timestamp is out of range: {}s {}ns",
+                timestamp.seconds, timestamp.nanos
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TimestampError {}
+
 #[cfg(test)]
 mod tests {
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
@@ -172,10 +442,241 @@ mod tests {
         fn check_system_time_roundtrip(
             system_time in SystemTime::arbitrary(),
         ) {
-            prop_assert_eq!(SystemTime::from(Timestamp::from(system_time)), system_time);
+            prop_assert_eq!(
+                SystemTime::try_from(Timestamp::from(system_time)).unwrap(),
+                system_time
+            );
         }
     }
 
+    #[test]
+    fn any_roundtrip() {
+        let duration = super::Duration {
+            seconds: 10,
+            nanos: 5,
+        };
+        let any = Any::pack(&duration);
+        assert_eq!(any.type_url, "type.googleapis.com/google.protobuf.Duration");
+        assert_eq!(any.unpack::<super::Duration>().unwrap(), duration);
+
+        assert_eq!(Any::from_msg(&duration), any);
+        assert_eq!(any.to_msg::<super::Duration>().unwrap(), duration);
+        let error = any.to_msg::<Timestamp>().unwrap_err();
+        assert_eq!(error.kind(), prost::DecodeErrorKind::Other);
+        assert_eq!(
+            any.unpack::<Timestamp>().unwrap_err(),
+            AnyError::TypeUrlMismatch {
+                expected: "type.googleapis.com/google.protobuf.Timestamp".into(),
+                found: "type.googleapis.com/google.protobuf.Duration".into(),
+            }
+        );
+        let invalid = Any {
+            type_url: any.type_url.clone(),
+            value: vec![0x0f],
+        };
+        assert!(matches!(
+            invalid.unpack::<super::Duration>(),
+            Err(AnyError::Decode(_))
+        ));
+
+        // The type URL prefix is not checked.
+        let any = Any {
+            type_url: "example.com/types/google.protobuf.Duration".into(),
+            value: any.value,
+        };
+        assert_eq!(any.unpack::<super::Duration>().unwrap(), duration);
+    }
+
+    #[test]
+    fn check_normalize() {
+        let mut duration = super::Duration {
+            seconds: 1,
+            nanos: 1_500_000_000,
+        };
+        duration.normalize();
+        assert_eq!(
+            duration,
+            super::Duration {
+                seconds: 2,
+                nanos: 500_000_000
+            }
+        );
+        let mut duration = super::Duration {
+            seconds: 1,
+            nanos: -1_500_000_000,
+        };
+        duration.normalize();
+        assert_eq!(
+            duration,
+            super::Duration {
+                seconds: 0,
+                nanos: -500_000_000
+            }
+        );
+
+        let mut timestamp = Timestamp {
+            seconds: 1,
+            nanos: 1_500_000_000,
+        };
+        timestamp.normalize();
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: 2,
+                nanos: 500_000_000
+            }
+        );
+        let mut timestamp = Timestamp {
+            seconds: 0,
+            nanos: -1_500_000_000,
+        };
+        timestamp.normalize();
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: -2,
+                nanos: 500_000_000
+            }
+        );
+
+        // The seconds saturate instead of overflowing.
+        let mut timestamp = Timestamp {
+            seconds: i64::MAX,
+            nanos: 1_500_000_000,
+        };
+        assert!(!timestamp.clone().try_normalize());
+        timestamp.normalize();
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: i64::MAX,
+                nanos: 500_000_000
+            }
+        );
+        let mut timestamp = Timestamp {
+            seconds: i64::MIN,
+            nanos: -1,
+        };
+        assert!(!timestamp.clone().try_normalize());
+        timestamp.normalize();
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: i64::MIN,
+                nanos: 999_999_999
+            }
+        );
+        let mut duration = super::Duration {
+            seconds: i64::MIN,
+            nanos: -1_500_000_000,
+        };
+        let unchanged = duration.clone();
+        assert!(!duration.try_normalize());
+        assert_eq!(duration, unchanged);
+        duration.normalize();
+        assert_eq!(
+            duration,
+            super::Duration {
+                seconds: i64::MIN,
+                nanos: -500_000_000
+            }
+        );
+    }
+
+    #[test]
+    fn check_duration_conversions() {
+        let duration = super::Duration {
+            seconds: 1,
+            nanos: 1_500_000_000,
+        };
+        assert_eq!(
+            Duration::try_from(duration),
+            Ok(Duration::new(2, 500_000_000))
+        );
+
+        let duration = super::Duration {
+            seconds: -1,
+            nanos: -500,
+        };
+        assert_eq!(
+            Duration::try_from(duration),
+            Err(DurationError::NegativeDuration(Duration::new(1, 500)))
+        );
+        let duration = super::Duration {
+            seconds: i64::MIN,
+            nanos: 0,
+        };
+        assert_eq!(
+            Duration::try_from(duration),
+            Err(DurationError::NegativeDuration(Duration::from_secs(
+                1 << 63
+            )))
+        );
+
+        let duration = super::Duration {
+            seconds: i64::MAX,
+            nanos: NANOS_PER_SECOND,
+        };
+        assert_eq!(Duration::try_from(duration), Err(DurationError::OutOfRange));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn check_timestamp_out_of_range() {
+        let timestamp = Timestamp {
+            seconds: i64::MAX,
+            nanos: NANOS_PER_SECOND,
+        };
+        assert_eq!(
+            SystemTime::try_from(timestamp.clone()),
+            Err(TimestampError::OutOfSystemRange(timestamp))
+        );
+
+        let timestamp = Timestamp {
+            seconds: i64::MIN,
+            nanos: -1,
+        };
+        assert_eq!(
+            SystemTime::try_from(timestamp.clone()),
+            Err(TimestampError::OutOfSystemRange(timestamp))
+        );
+
+        let timestamp = Timestamp {
+            seconds: 1,
+            nanos: -1,
+        };
+        assert_eq!(
+            SystemTime::try_from(timestamp),
+            Ok(UNIX_EPOCH + Duration::new(0, 999_999_999))
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn check_chrono_conversions() {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let date_time = Utc.timestamp_opt(-1_234, 5_678).single().unwrap();
+        let timestamp = Timestamp::from(date_time);
+        assert_eq!(
+            timestamp,
+            Timestamp {
+                seconds: -1_234,
+                nanos: 5_678
+            }
+        );
+        assert_eq!(DateTime::<Utc>::try_from(timestamp), Ok(date_time));
+
+        let timestamp = Timestamp {
+            seconds: i64::MAX,
+            nanos: 0,
+        };
+        assert_eq!(
+            DateTime::<Utc>::try_from(timestamp.clone()),
+            Err(TimestampError::OutOfSystemRange(timestamp))
+        );
+    }
+
     #[cfg(feature = "std")]
     #[test]
     fn check_timestamp_negative_seconds() {
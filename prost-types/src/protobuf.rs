@@ -1,12 +1,18 @@
 /// The protocol compiler can output a FileDescriptorSet containing the .proto
 /// files it parses.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FileDescriptorSet {
     #[prost(message, repeated, tag="1")]
     pub file: ::prost::alloc::vec::Vec<FileDescriptorProto>,
 }
 /// Describes a complete .proto file.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FileDescriptorProto {
     /// file name, relative to root of source tree
     #[prost(string, optional, tag="1")]
@@ -48,6 +54,9 @@ pub struct FileDescriptorProto {
 }
 /// Describes a message type.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct DescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -75,6 +84,9 @@ pub struct DescriptorProto {
 /// Nested message and enum types in `DescriptorProto`.
 pub mod descriptor_proto {
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct ExtensionRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -89,6 +101,9 @@ pub mod descriptor_proto {
     /// fields or extension ranges in the same message. Reserved ranges may
     /// not overlap.
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct ReservedRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -99,6 +114,9 @@ pub mod descriptor_proto {
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct ExtensionRangeOptions {
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag="999")]
@@ -106,6 +124,9 @@ pub struct ExtensionRangeOptions {
 }
 /// Describes a field within a message.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FieldDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -178,48 +199,72 @@ pub mod field_descriptor_proto {
     pub enum Type {
         /// 0 is reserved for errors.
         /// Order is weird for historical reasons.
+        #[prost(name = "TYPE_DOUBLE")]
         Double = 1,
+        #[prost(name = "TYPE_FLOAT")]
         Float = 2,
         /// Not ZigZag encoded.  Negative numbers take 10 bytes.  Use TYPE_SINT64 if
         /// negative values are likely.
+        #[prost(name = "TYPE_INT64")]
         Int64 = 3,
+        #[prost(name = "TYPE_UINT64")]
         Uint64 = 4,
         /// Not ZigZag encoded.  Negative numbers take 10 bytes.  Use TYPE_SINT32 if
         /// negative values are likely.
+        #[prost(name = "TYPE_INT32")]
         Int32 = 5,
+        #[prost(name = "TYPE_FIXED64")]
         Fixed64 = 6,
+        #[prost(name = "TYPE_FIXED32")]
         Fixed32 = 7,
+        #[prost(name = "TYPE_BOOL")]
         Bool = 8,
+        #[prost(name = "TYPE_STRING")]
         String = 9,
         /// Tag-delimited aggregate.
         /// Group type is deprecated and not supported in proto3. However, Proto3
         /// implementations should still be able to parse the group wire format and
         /// treat group fields as unknown fields.
+        #[prost(name = "TYPE_GROUP")]
         Group = 10,
         /// Length-delimited aggregate.
+        #[prost(name = "TYPE_MESSAGE")]
         Message = 11,
         /// New in version 2.
+        #[prost(name = "TYPE_BYTES")]
         Bytes = 12,
+        #[prost(name = "TYPE_UINT32")]
         Uint32 = 13,
+        #[prost(name = "TYPE_ENUM")]
         Enum = 14,
+        #[prost(name = "TYPE_SFIXED32")]
         Sfixed32 = 15,
+        #[prost(name = "TYPE_SFIXED64")]
         Sfixed64 = 16,
         /// Uses ZigZag encoding.
+        #[prost(name = "TYPE_SINT32")]
         Sint32 = 17,
         /// Uses ZigZag encoding.
+        #[prost(name = "TYPE_SINT64")]
         Sint64 = 18,
     }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum Label {
         /// 0 is reserved for errors
+        #[prost(name = "LABEL_OPTIONAL")]
         Optional = 1,
+        #[prost(name = "LABEL_REQUIRED")]
         Required = 2,
+        #[prost(name = "LABEL_REPEATED")]
         Repeated = 3,
     }
 }
 /// Describes a oneof.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct OneofDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -228,6 +273,9 @@ pub struct OneofDescriptorProto {
 }
 /// Describes an enum type.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct EnumDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -254,6 +302,9 @@ pub mod enum_descriptor_proto {
     /// is inclusive such that it can appropriately represent the entire int32
     /// domain.
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct EnumReservedRange {
         /// Inclusive.
         #[prost(int32, optional, tag="1")]
@@ -265,6 +316,9 @@ pub mod enum_descriptor_proto {
 }
 /// Describes a value within an enum.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct EnumValueDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -275,6 +329,9 @@ pub struct EnumValueDescriptorProto {
 }
 /// Describes a service.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct ServiceDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -285,6 +342,9 @@ pub struct ServiceDescriptorProto {
 }
 /// Describes a method of a service.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct MethodDescriptorProto {
     #[prost(string, optional, tag="1")]
     pub name: ::core::option::Option<::prost::alloc::string::String>,
@@ -336,6 +396,9 @@ pub struct MethodDescriptorProto {
 //   to automatically assign option numbers.
 
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FileOptions {
     /// Sets the Java package where classes generated from this .proto will be
     /// placed.  By default, the proto package is used, but this is often
@@ -451,16 +514,22 @@ pub mod file_options {
     #[repr(i32)]
     pub enum OptimizeMode {
         /// Generate complete code for parsing, serialization,
+        #[prost(name = "SPEED")]
         Speed = 1,
         /// etc.
         ///
         /// Use ReflectionOps to implement these methods.
+        #[prost(name = "CODE_SIZE")]
         CodeSize = 2,
         /// Generate code using MessageLite and the lite runtime.
+        #[prost(name = "LITE_RUNTIME")]
         LiteRuntime = 3,
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct MessageOptions {
     /// Set true to use the old proto1 MessageSet wire format for extensions.
     /// This is provided for backwards-compatibility with the MessageSet wire
@@ -521,6 +590,9 @@ pub struct MessageOptions {
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FieldOptions {
     /// The ctype option instructs the C++ code generator to use a different
     /// representation of the field than it normally would.  See the specific
@@ -597,28 +669,40 @@ pub mod field_options {
     #[repr(i32)]
     pub enum CType {
         /// Default mode.
+        #[prost(name = "STRING")]
         String = 0,
+        #[prost(name = "CORD")]
         Cord = 1,
+        #[prost(name = "STRING_PIECE")]
         StringPiece = 2,
     }
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum JsType {
         /// Use the default type.
+        #[prost(name = "JS_NORMAL")]
         JsNormal = 0,
         /// Use JavaScript strings.
+        #[prost(name = "JS_STRING")]
         JsString = 1,
         /// Use JavaScript numbers.
+        #[prost(name = "JS_NUMBER")]
         JsNumber = 2,
     }
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct OneofOptions {
     /// The parser stores options it doesn't recognize here. See above.
     #[prost(message, repeated, tag="999")]
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct EnumOptions {
     /// Set this option to true to allow mapping different tag names to the same
     /// value.
@@ -635,6 +719,9 @@ pub struct EnumOptions {
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct EnumValueOptions {
     /// Is this enum value deprecated?
     /// Depending on the target platform, this can emit Deprecated annotations
@@ -647,6 +734,9 @@ pub struct EnumValueOptions {
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct ServiceOptions {
     // Note:  Field numbers 1 through 32 are reserved for Google's internal RPC
     //   framework.  We apologize for hoarding these numbers to ourselves, but
@@ -664,6 +754,9 @@ pub struct ServiceOptions {
     pub uninterpreted_option: ::prost::alloc::vec::Vec<UninterpretedOption>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct MethodOptions {
     // Note:  Field numbers 1 through 32 are reserved for Google's internal RPC
     //   framework.  We apologize for hoarding these numbers to ourselves, but
@@ -690,10 +783,13 @@ pub mod method_options {
     #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
     #[repr(i32)]
     pub enum IdempotencyLevel {
+        #[prost(name = "IDEMPOTENCY_UNKNOWN")]
         IdempotencyUnknown = 0,
         /// implies idempotent
+        #[prost(name = "NO_SIDE_EFFECTS")]
         NoSideEffects = 1,
         /// idempotent, but may have side effects
+        #[prost(name = "IDEMPOTENT")]
         Idempotent = 2,
     }
 }
@@ -704,6 +800,9 @@ pub mod method_options {
 /// or produced by Descriptor::CopyTo()) will never have UninterpretedOptions
 /// in them.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct UninterpretedOption {
     #[prost(message, repeated, tag="2")]
     pub name: ::prost::alloc::vec::Vec<uninterpreted_option::NamePart>,
@@ -730,6 +829,9 @@ pub mod uninterpreted_option {
     /// E.g.,{ ["foo", false], ["bar.baz", true], ["qux", false] } represents
     /// "foo.(bar.baz).qux".
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct NamePart {
         #[prost(string, required, tag="1")]
         pub name_part: ::prost::alloc::string::String,
@@ -743,6 +845,9 @@ pub mod uninterpreted_option {
 /// Encapsulates information about the original source file from which a
 /// FileDescriptorProto was generated.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct SourceCodeInfo {
     /// A Location identifies a piece of source code in a .proto file which
     /// corresponds to a particular definition.  This information is intended
@@ -793,6 +898,9 @@ pub struct SourceCodeInfo {
 /// Nested message and enum types in `SourceCodeInfo`.
 pub mod source_code_info {
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct Location {
         /// Identifies which part of the FileDescriptorProto was defined at this
         /// location.
@@ -885,6 +993,9 @@ pub mod source_code_info {
 /// file. A GeneratedCodeInfo message is associated with only one generated
 /// source file, but may contain references to different source .proto files.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct GeneratedCodeInfo {
     /// An Annotation connects some span of text in generated code to an element
     /// of its generating .proto file.
@@ -894,6 +1005,9 @@ pub struct GeneratedCodeInfo {
 /// Nested message and enum types in `GeneratedCodeInfo`.
 pub mod generated_code_info {
     #[derive(Clone, PartialEq, ::prost::Message)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub struct Annotation {
         /// Identifies the element in the original source .proto file. This field
         /// is formatted the same as SourceCodeInfo.Location.path.
@@ -997,6 +1111,9 @@ pub mod generated_code_info {
 ///     }
 ///
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Any {
     /// A URL/resource name that uniquely identifies the type of the serialized
     /// protocol buffer message. This string must contain at least
@@ -1035,6 +1152,9 @@ pub struct Any {
 /// `SourceContext` represents information about the source of a
 /// protobuf element, like the file in which it is defined.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct SourceContext {
     /// The path-qualified name of the .proto file that contained the associated
     /// protobuf element.  For example: `"google/protobuf/source_context.proto"`.
@@ -1043,6 +1163,9 @@ pub struct SourceContext {
 }
 /// A protocol buffer message type.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Type {
     /// The fully qualified message name.
     #[prost(string, tag="1")]
@@ -1065,6 +1188,9 @@ pub struct Type {
 }
 /// A single field of a message type.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Field {
     /// The field type.
     #[prost(enumeration="field::Kind", tag="1")]
@@ -1106,42 +1232,61 @@ pub mod field {
     #[repr(i32)]
     pub enum Kind {
         /// Field type unknown.
+        #[prost(name = "TYPE_UNKNOWN")]
         TypeUnknown = 0,
         /// Field type double.
+        #[prost(name = "TYPE_DOUBLE")]
         TypeDouble = 1,
         /// Field type float.
+        #[prost(name = "TYPE_FLOAT")]
         TypeFloat = 2,
         /// Field type int64.
+        #[prost(name = "TYPE_INT64")]
         TypeInt64 = 3,
         /// Field type uint64.
+        #[prost(name = "TYPE_UINT64")]
         TypeUint64 = 4,
         /// Field type int32.
+        #[prost(name = "TYPE_INT32")]
         TypeInt32 = 5,
         /// Field type fixed64.
+        #[prost(name = "TYPE_FIXED64")]
         TypeFixed64 = 6,
         /// Field type fixed32.
+        #[prost(name = "TYPE_FIXED32")]
         TypeFixed32 = 7,
         /// Field type bool.
+        #[prost(name = "TYPE_BOOL")]
         TypeBool = 8,
         /// Field type string.
+        #[prost(name = "TYPE_STRING")]
         TypeString = 9,
         /// Field type group. Proto2 syntax only, and deprecated.
+        #[prost(name = "TYPE_GROUP")]
         TypeGroup = 10,
         /// Field type message.
+        #[prost(name = "TYPE_MESSAGE")]
         TypeMessage = 11,
         /// Field type bytes.
+        #[prost(name = "TYPE_BYTES")]
         TypeBytes = 12,
         /// Field type uint32.
+        #[prost(name = "TYPE_UINT32")]
         TypeUint32 = 13,
         /// Field type enum.
+        #[prost(name = "TYPE_ENUM")]
         TypeEnum = 14,
         /// Field type sfixed32.
+        #[prost(name = "TYPE_SFIXED32")]
         TypeSfixed32 = 15,
         /// Field type sfixed64.
+        #[prost(name = "TYPE_SFIXED64")]
         TypeSfixed64 = 16,
         /// Field type sint32.
+        #[prost(name = "TYPE_SINT32")]
         TypeSint32 = 17,
         /// Field type sint64.
+        #[prost(name = "TYPE_SINT64")]
         TypeSint64 = 18,
     }
     /// Whether a field is optional, required, or repeated.
@@ -1149,17 +1294,24 @@ pub mod field {
     #[repr(i32)]
     pub enum Cardinality {
         /// For fields with unknown cardinality.
+        #[prost(name = "CARDINALITY_UNKNOWN")]
         Unknown = 0,
         /// For optional fields.
+        #[prost(name = "CARDINALITY_OPTIONAL")]
         Optional = 1,
         /// For required fields. Proto2 syntax only.
+        #[prost(name = "CARDINALITY_REQUIRED")]
         Required = 2,
         /// For repeated fields.
+        #[prost(name = "CARDINALITY_REPEATED")]
         Repeated = 3,
     }
 }
 /// Enum type definition.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Enum {
     /// Enum type name.
     #[prost(string, tag="1")]
@@ -1179,6 +1331,9 @@ pub struct Enum {
 }
 /// Enum value definition.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct EnumValue {
     /// Enum value name.
     #[prost(string, tag="1")]
@@ -1193,6 +1348,9 @@ pub struct EnumValue {
 /// A protocol buffer option, which can be attached to a message, field,
 /// enumeration, etc.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Option {
     /// The option's name. For protobuf built-in options (options defined in
     /// descriptor.proto), this is the short name. For example, `"map_entry"`.
@@ -1212,8 +1370,10 @@ pub struct Option {
 #[repr(i32)]
 pub enum Syntax {
     /// Syntax `proto2`.
+    #[prost(name = "SYNTAX_PROTO2")]
     Proto2 = 0,
     /// Syntax `proto3`.
+    #[prost(name = "SYNTAX_PROTO3")]
     Proto3 = 1,
 }
 /// Api is a light-weight descriptor for an API Interface.
@@ -1226,6 +1386,9 @@ pub enum Syntax {
 /// this message itself. See https://cloud.google.com/apis/design/glossary for
 /// detailed terminology.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Api {
     /// The fully qualified name of this interface, including package name
     /// followed by the interface's simple name.
@@ -1273,6 +1436,9 @@ pub struct Api {
 }
 /// Method represents a method of an API interface.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Method {
     /// The simple name of this method.
     #[prost(string, tag="1")]
@@ -1375,6 +1541,9 @@ pub struct Method {
 ///       ...
 ///     }
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Mixin {
     /// The fully qualified name of the interface which is included.
     #[prost(string, tag="1")]
@@ -1445,6 +1614,10 @@ pub struct Mixin {
 ///
 ///
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[prost(well_known = "Duration")]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Duration {
     /// Signed seconds of the span of time. Must be from -315,576,000,000
     /// to +315,576,000,000 inclusive. Note: these bounds are computed from:
@@ -1660,6 +1833,10 @@ pub struct Duration {
 /// request should verify the included field paths, and return an
 /// `INVALID_ARGUMENT` error if any path is unmappable.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[prost(well_known = "FieldMask")]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct FieldMask {
     /// The set of field mask paths.
     #[prost(string, repeated, tag="1")]
@@ -1674,6 +1851,9 @@ pub struct FieldMask {
 ///
 /// The JSON representation for `Struct` is JSON object.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Struct {
     /// Unordered map of dynamically typed values.
     #[prost(btree_map="string, message", tag="1")]
@@ -1686,6 +1866,9 @@ pub struct Struct {
 ///
 /// The JSON representation for `Value` is JSON value.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Value {
     /// The kind of value.
     #[prost(oneof="value::Kind", tags="1, 2, 3, 4, 5, 6")]
@@ -1695,6 +1878,9 @@ pub struct Value {
 pub mod value {
     /// The kind of value.
     #[derive(Clone, PartialEq, ::prost::Oneof)]
+    #[cfg_attr(feature = "json", prost(json))]
+    #[cfg_attr(feature = "text-format", prost(text_format))]
+    #[cfg_attr(feature = "reflect", prost(reflect))]
     pub enum Kind {
         /// Represents a null value.
         #[prost(enumeration="super::NullValue", tag="1")]
@@ -1720,6 +1906,9 @@ pub mod value {
 ///
 /// The JSON representation for `ListValue` is JSON array.
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct ListValue {
     /// Repeated field of dynamically typed values.
     #[prost(message, repeated, tag="1")]
@@ -1733,6 +1922,7 @@ pub struct ListValue {
 #[repr(i32)]
 pub enum NullValue {
     /// Null value.
+    #[prost(name = "NULL_VALUE")]
     NullValue = 0,
 }
 /// A Timestamp represents a point in time independent of any time zone or local
@@ -1829,6 +2019,10 @@ pub enum NullValue {
 ///
 ///
 #[derive(Clone, PartialEq, ::prost::Message)]
+#[prost(well_known = "Timestamp")]
+#[cfg_attr(feature = "json", prost(json))]
+#[cfg_attr(feature = "text-format", prost(text_format))]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Timestamp {
     /// Represents seconds of UTC time since Unix epoch
     /// 1970-01-01T00:00:00Z. Must be from 0001-01-01T00:00:00Z to
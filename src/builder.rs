@@ -0,0 +1,235 @@
+//! Incremental construction and in-place mutation of `ByteString` values.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::str;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use bytestring::ByteString;
+
+/// A growable buffer for building a `ByteString`.
+///
+/// The builder is backed by a `BytesMut`, and only ever appends whole UTF-8 sequences, so
+/// `finish` freezes the buffer into a `ByteString` without validating it again. Values can be
+/// appended with `push_str` and `push`, or formatted through the `fmt::Write` implementation.
+///
+/// ```
+/// use core::fmt::Write;
+/// use prost::builder::ByteStringBuilder;
+///
+/// let mut builder = ByteStringBuilder::with_capacity(16);
+/// builder.push_str("answer");
+/// builder.push('=');
+/// write!(builder, "{}", 42).unwrap();
+/// assert_eq!(builder.finish(), "answer=42");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ByteStringBuilder {
+    buf: BytesMut,
+}
+
+impl ByteStringBuilder {
+    /// Creates a new, empty builder.
+    pub fn new() -> ByteStringBuilder {
+        ByteStringBuilder::default()
+    }
+
+    /// Creates a new, empty builder which can hold `capacity` bytes without reallocating.
+    pub fn with_capacity(capacity: usize) -> ByteStringBuilder {
+        ByteStringBuilder {
+            buf: BytesMut::with_capacity(capacity),
+        }
+    }
+
+    /// Appends `s`.
+    pub fn push_str(&mut self, s: &str) {
+        self.buf.put_slice(s.as_bytes());
+    }
+
+    /// Appends `c`, encoded as UTF-8.
+    pub fn push(&mut self, c: char) {
+        let mut scratch = [0; 4];
+        self.push_str(c.encode_utf8(&mut scratch));
+    }
+
+    /// Returns the length of the built string in bytes.
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns `true` if nothing has been appended.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the string built so far.
+    pub fn as_str(&self) -> &str {
+        // SAFETY: only whole UTF-8 sequences are appended to the buffer.
+        unsafe { core::str::from_utf8_unchecked(&self.buf) }
+    }
+
+    /// Converts the builder into a `ByteString`, without copying the contents.
+    pub fn finish(self) -> ByteString {
+        let mut value = ByteString::new();
+        // SAFETY: only whole UTF-8 sequences are appended to the buffer, so the frozen bytes are
+        // valid UTF-8 and can back the string.
+        unsafe {
+            *value.as_mut_bytes() = self.buf.freeze();
+        }
+        value
+    }
+}
+
+impl fmt::Write for ByteStringBuilder {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        self.push(c);
+        Ok(())
+    }
+}
+
+impl Extend<char> for ByteStringBuilder {
+    fn extend<I: IntoIterator<Item = char>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.buf.reserve(iter.size_hint().0);
+        iter.for_each(|c| self.push(c));
+    }
+}
+
+impl<'a> Extend<&'a str> for ByteStringBuilder {
+    fn extend<I: IntoIterator<Item = &'a str>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.buf.reserve(iter.size_hint().0);
+        iter.for_each(|s| self.push_str(s));
+    }
+}
+
+impl Extend<String> for ByteStringBuilder {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.buf.reserve(iter.size_hint().0);
+        iter.for_each(|s| self.push_str(&s));
+    }
+}
+
+/// Returns a guard which gives mutable access to the bytes of `value`.
+///
+/// This is the safe alternative to the unsafe `ByteString::as_mut_bytes`. The guard holds a copy
+/// of the contents in a `Vec<u8>`, which can be modified freely. When the guard is dropped, the
+/// bytes are validated as UTF-8 and stored back into `value`. If they aren't valid UTF-8, `value`
+/// is cleared instead, so it never holds invalid data.
+///
+/// ```
+/// use prost::builder::make_mut;
+/// use prost::bytestring::ByteString;
+///
+/// let mut value = ByteString::from("hello");
+/// make_mut(&mut value).extend_from_slice(b", world");
+/// assert_eq!(value, "hello, world");
+///
+/// make_mut(&mut value).push(0xFF);
+/// assert_eq!(value, "");
+/// ```
+pub fn make_mut(value: &mut ByteString) -> ByteStringMut<'_> {
+    let bytes: &[u8] = value.as_ref();
+    let buf = Vec::from(bytes);
+    ByteStringMut { value, buf }
+}
+
+/// A guard giving mutable access to the bytes of a `ByteString`, returned by `make_mut`.
+///
+/// Dereferences to the `Vec<u8>` holding the new contents. On drop the contents are stored back
+/// into the string if they are valid UTF-8, and the string is cleared otherwise.
+#[derive(Debug)]
+pub struct ByteStringMut<'a> {
+    value: &'a mut ByteString,
+    buf: Vec<u8>,
+}
+
+impl ByteStringMut<'_> {
+    /// Returns the bytes which will be stored back into the string.
+    pub fn as_mut_vec(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Deref for ByteStringMut<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for ByteStringMut<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for ByteStringMut<'_> {
+    fn drop(&mut self) {
+        let buf = mem::take(&mut self.buf);
+        if str::from_utf8(&buf).is_err() {
+            *self.value = ByteString::new();
+            return;
+        }
+        // SAFETY: the bytes were just checked to be valid UTF-8, so they can back the string.
+        unsafe {
+            *self.value.as_mut_bytes() = Bytes::from(buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn push() {
+        let mut builder = ByteStringBuilder::new();
+        assert!(builder.is_empty());
+        builder.push_str("caf");
+        builder.push('é');
+        builder.push('\u{1F980}');
+        assert_eq!(builder.as_str(), "café\u{1F980}");
+        assert_eq!(builder.len(), 9);
+
+        let value = builder.finish();
+        assert_eq!(value, "café\u{1F980}");
+    }
+
+    #[test]
+    fn extend() {
+        let mut builder = ByteStringBuilder::new();
+        builder.extend("ab".chars());
+        builder.extend(vec!["c", "dé"]);
+        builder.extend(vec![String::from("f")]);
+        assert_eq!(builder.finish(), "abcdéf");
+    }
+
+    #[test]
+    fn mutate_in_place() {
+        let mut value = ByteString::from("café");
+        {
+            let mut guard = make_mut(&mut value);
+            assert_eq!(&guard[..], "café".as_bytes());
+            guard.truncate(3);
+            guard.as_mut_vec().extend_from_slice("é!".as_bytes());
+        }
+        assert_eq!(value, "café!");
+
+        // Cutting a UTF-8 sequence in half leaves invalid data, so the string is cleared.
+        make_mut(&mut value).truncate(4);
+        assert_eq!(value, "");
+    }
+}
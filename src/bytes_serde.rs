@@ -0,0 +1,110 @@
+//! Serde support for `Bytes` fields.
+//!
+//! The functions in this module can be used with `#[serde(with = "prost::bytes_serde")]` on a
+//! `Bytes` field, for instance one generated by `prost-build`, with the attribute added through
+//! `prost_build::Config::field_attribute`.
+//!
+//! Human-readable formats, like JSON, represent the bytes as a standard base64 string with
+//! padding, as in the proto3 JSON mapping. Other formats represent them as a byte sequence.
+//! Deserialization accepts base64 in the standard or URL-safe alphabet, with or without padding,
+//! as well as byte sequences in any format.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bytes::Bytes;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::Serializer;
+
+use crate::base64;
+
+/// Serializes `value` as a base64 string in human-readable formats, and as bytes otherwise.
+pub fn serialize<S>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if serializer.is_human_readable() {
+        serializer.serialize_str(&base64::encode(value))
+    } else {
+        serializer.serialize_bytes(value)
+    }
+}
+
+/// Deserializes a `Bytes` value from a base64 string or a byte sequence.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(BytesVisitor)
+    } else {
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a base64 string or a byte sequence")
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Bytes, E>
+    where
+        E: de::Error,
+    {
+        base64::decode(value)
+            .map(Bytes::from)
+            .ok_or_else(|| E::invalid_value(de::Unexpected::Str(value), &self))
+    }
+
+    fn visit_bytes<E>(self, value: &[u8]) -> Result<Bytes, E>
+    where
+        E: de::Error,
+    {
+        Ok(Bytes::copy_from_slice(value))
+    }
+
+    fn visit_byte_buf<E>(self, value: Vec<u8>) -> Result<Bytes, E>
+    where
+        E: de::Error,
+    {
+        Ok(Bytes::from(value))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Bytes, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        // The size hint comes from the input, so it is only trusted up to a small bound.
+        let mut value = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            value.push(byte);
+        }
+        Ok(Bytes::from(value))
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+
+    use serde_json::Value;
+
+    #[test]
+    fn base64_roundtrip() {
+        let value = Bytes::from_static(&[0xFB, 0xFF, b'f']);
+        let json = serialize(&value, serde_json::value::Serializer).unwrap();
+        assert_eq!(json, Value::from("+/9m"));
+        assert_eq!(deserialize(json).unwrap(), value);
+
+        assert_eq!(deserialize(Value::from("-_9m")).unwrap(), value);
+        assert_eq!(
+            deserialize(Value::from(vec![0xFB, 0xFF, 102])).unwrap(),
+            value
+        );
+        deserialize(Value::from("not base64!")).unwrap_err();
+    }
+}
@@ -0,0 +1,185 @@
+//! Caching of encoded messages.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::{Deref, DerefMut};
+
+use bytes::{Buf, BufMut, Bytes};
+
+use crate::encoding::{DecodeContext, EncodeOptions, WireType};
+use crate::{DecodeError, Message};
+
+/// A message which caches its encoded form.
+///
+/// Encoding a `CachedMessage` copies the cached bytes instead of encoding the message again, and
+/// `encoded_len` returns the length of the cached bytes. This is useful when the same message is
+/// encoded many times, for instance when sending it to many subscribers.
+///
+/// The cache is filled by `CachedMessage::new` and by `encoded`. Mutably borrowing the message,
+/// through `DerefMut` or by merging into the `CachedMessage`, invalidates the cache, after which
+/// the message is encoded as usual until the cache is filled again.
+///
+/// ```
+/// use prost::cache::CachedMessage;
+/// use prost::Message;
+///
+/// let mut message = CachedMessage::new(String::from("foo"));
+/// assert!(message.is_cached());
+/// assert_eq!(message.encode_to_vec(), String::from("foo").encode_to_vec());
+///
+/// message.push_str("bar");
+/// assert!(!message.is_cached());
+/// assert_eq!(message.encoded(), &String::from("foobar").encode_to_vec()[..]);
+/// assert!(message.is_cached());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CachedMessage<M> {
+    message: M,
+    encoded: Option<Bytes>,
+}
+
+impl<M> CachedMessage<M>
+where
+    M: Message,
+{
+    /// Creates a new `CachedMessage`, encoding the message to fill the cache.
+    pub fn new(message: M) -> CachedMessage<M> {
+        let mut cached = CachedMessage {
+            message,
+            encoded: None,
+        };
+        cached.encoded();
+        cached
+    }
+
+    /// Returns the encoded message, encoding it first if the cache has been invalidated.
+    pub fn encoded(&mut self) -> &Bytes {
+        let message = &self.message;
+        self.encoded
+            .get_or_insert_with(|| Bytes::from(message.encode_to_vec()))
+    }
+
+    /// Returns `true` if the encoded message is cached.
+    pub fn is_cached(&self) -> bool {
+        self.encoded.is_some()
+    }
+
+    /// Unwraps the message, discarding the cache.
+    pub fn into_inner(self) -> M {
+        self.message
+    }
+}
+
+impl<M> Deref for CachedMessage<M> {
+    type Target = M;
+
+    fn deref(&self) -> &M {
+        &self.message
+    }
+}
+
+impl<M> DerefMut for CachedMessage<M> {
+    /// Mutably borrows the message, invalidating the cache.
+    fn deref_mut(&mut self) -> &mut M {
+        self.encoded = None;
+        &mut self.message
+    }
+}
+
+impl<M> PartialEq for CachedMessage<M>
+where
+    M: PartialEq,
+{
+    fn eq(&self, other: &CachedMessage<M>) -> bool {
+        self.message == other.message
+    }
+}
+
+impl<M> From<M> for CachedMessage<M>
+where
+    M: Message,
+{
+    fn from(message: M) -> CachedMessage<M> {
+        CachedMessage::new(message)
+    }
+}
+
+impl<M> Message for CachedMessage<M>
+where
+    M: Message,
+{
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        match self.encoded {
+            Some(ref encoded) => buf.put_slice(encoded),
+            None => self.message.encode_raw(buf),
+        }
+    }
+    fn encode_raw_deterministic<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        // The cached bytes are not necessarily in deterministic order.
+        self.message.encode_raw_deterministic(buf)
+    }
+    fn encode_raw_with_options<B>(&self, buf: &mut B, options: &EncodeOptions)
+    where
+        B: BufMut,
+    {
+        // The cached bytes were encoded with the default options.
+        self.message.encode_raw_with_options(buf, options)
+    }
+    fn missing_required_fields(buf: &[u8], path: &str, missing: &mut Vec<String>) {
+        M::missing_required_fields(buf, path, missing)
+    }
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        self.encoded = None;
+        self.message.merge_field(tag, wire_type, buf, ctx)
+    }
+    fn encoded_len(&self) -> usize {
+        match self.encoded {
+            Some(ref encoded) => encoded.len(),
+            None => self.message.encoded_len(),
+        }
+    }
+    fn encoded_len_with_options(&self, options: &EncodeOptions) -> usize {
+        self.message.encoded_len_with_options(options)
+    }
+    fn clear(&mut self) {
+        self.encoded = None;
+        self.message.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::String;
+
+    #[test]
+    fn invalidate_on_merge() {
+        let mut message = CachedMessage::new(String::from("foo"));
+        let encoded = String::from("bar").encode_to_vec();
+        message.merge(&encoded[..]).unwrap();
+        assert!(!message.is_cached());
+        assert_eq!(*message, "bar");
+        assert_eq!(message.encode_to_vec(), encoded);
+        assert_eq!(message.encoded(), &encoded[..]);
+
+        message.clear();
+        assert!(!message.is_cached());
+        assert_eq!(message.encoded_len(), 0);
+    }
+}
@@ -0,0 +1,168 @@
+//! Framing of length-delimited messages with `tokio_util::codec`.
+
+use std::io;
+use std::marker::PhantomData;
+
+use bytes::{Buf, BytesMut};
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::encoding::{decode_varint, encoded_len_varint};
+use crate::error::DecodeErrorKind;
+use crate::stream::DEFAULT_MAX_FRAME_LEN;
+use crate::{DecodeError, Message};
+
+/// A codec for streams of length-delimited messages of type `M`.
+///
+/// Each frame is a message prefixed by its length as a varint, the format written by
+/// `Message::encode_length_delimited`, or `writeDelimitedTo` in the Java implementation. The
+/// decoder returns `Ok(None)` until a whole frame has been buffered, reserving space for the rest
+/// of the frame once its length is known.
+///
+/// Frames longer than the maximum frame length, 8 MiB by default, are rejected by both the encoder
+/// and the decoder, so that a corrupt or malicious length prefix can't make the decoder buffer an
+/// arbitrary amount of data. Since the boundary of the next frame is unknown after a decoding
+/// error, the stream should not be read further.
+///
+/// ```
+/// use bytes::BytesMut;
+/// use prost::codec::ProstCodec;
+/// use tokio_util::codec::{Decoder, Encoder};
+///
+/// let mut codec = ProstCodec::<String>::new();
+/// let mut buf = BytesMut::new();
+/// codec.encode(String::from("foo"), &mut buf).unwrap();
+///
+/// let mut partial = buf.split_to(2);
+/// assert_eq!(codec.decode(&mut partial).unwrap(), None);
+/// partial.unsplit(buf);
+/// assert_eq!(codec.decode(&mut partial).unwrap(), Some(String::from("foo")));
+/// ```
+#[derive(Debug)]
+pub struct ProstCodec<M> {
+    max_frame_len: usize,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M> ProstCodec<M> {
+    /// Creates a new codec with the default maximum frame length.
+    pub fn new() -> ProstCodec<M> {
+        ProstCodec {
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            _message: PhantomData,
+        }
+    }
+
+    /// Limits the length of the messages which are encoded and decoded to `len` bytes, excluding
+    /// the length delimiter.
+    pub fn max_frame_len(mut self, len: usize) -> ProstCodec<M> {
+        self.max_frame_len = len;
+        self
+    }
+}
+
+impl<M> Default for ProstCodec<M> {
+    fn default() -> ProstCodec<M> {
+        ProstCodec::new()
+    }
+}
+
+impl<M> Decoder for ProstCodec<M>
+where
+    M: Message + Default,
+{
+    type Item = M;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<M>, io::Error> {
+        // The length delimiter is complete once a byte without the continuation bit, or the
+        // maximum of ten bytes, has been buffered.
+        if src.len() < 10 && src.iter().all(|&byte| byte >= 0x80) {
+            return Ok(None);
+        }
+        let mut delimiter = &src[..];
+        let len = decode_varint(&mut delimiter)?;
+        if len > self.max_frame_len as u64 {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::InvalidLength,
+                format!(
+                    "frame of {} bytes exceeds the maximum frame length of {} bytes",
+                    len, self.max_frame_len
+                ),
+            )
+            .into());
+        }
+        let delimiter_len = src.len() - delimiter.len();
+        let frame_len = delimiter_len + len as usize;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(delimiter_len);
+        let frame = src.split_to(len as usize).freeze();
+        Ok(Some(M::decode(frame)?))
+    }
+}
+
+impl<M> Encoder<M> for ProstCodec<M>
+where
+    M: Message,
+{
+    type Error = io::Error;
+
+    fn encode(&mut self, item: M, dst: &mut BytesMut) -> Result<(), io::Error> {
+        let len = item.encoded_len();
+        if len > self.max_frame_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "message of {} bytes exceeds the maximum frame length of {} bytes",
+                    len, self.max_frame_len
+                ),
+            ));
+        }
+        dst.reserve(len + encoded_len_varint(len as u64));
+        item.encode_length_delimited(dst)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_frames() {
+        let value = "x".repeat(300);
+        let mut codec = ProstCodec::<String>::new();
+        let mut encoded = BytesMut::new();
+        codec.encode(value.clone(), &mut encoded).unwrap();
+        codec.encode(String::new(), &mut encoded).unwrap();
+
+        // Feed the frames one byte at a time, including the two-byte length delimiter.
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in encoded.iter() {
+            buf.extend_from_slice(&[*byte]);
+            if let Some(message) = codec.decode(&mut buf).unwrap() {
+                decoded.push(message);
+            }
+        }
+        assert_eq!(decoded, vec![value, String::new()]);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn max_frame_len() {
+        let mut codec = ProstCodec::<String>::new().max_frame_len(4);
+        let mut buf = BytesMut::new();
+        let error = codec.encode("x".repeat(4), &mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(buf.is_empty());
+
+        // The length is checked before the frame is buffered.
+        let mut buf = BytesMut::from(&b"\x80\x08"[..]);
+        let error = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
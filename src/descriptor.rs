@@ -0,0 +1,66 @@
+//! Static metadata about the fields of messages.
+//!
+//! The metadata is much smaller than a `FileDescriptorProto`, but describes how each field is
+//! named and encoded, which is enough to drive generic serializers and validators. Derived
+//! messages implement `Descriptor` when the `descriptors` feature is enabled, and messages marked
+//! `#[prost(reflect)]` always do.
+
+use crate::encoding::WireType;
+use crate::Message;
+
+/// The Protobuf type of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FieldKind {
+    Double,
+    Float,
+    Int32,
+    Int64,
+    Uint32,
+    Uint64,
+    Sint32,
+    Sint64,
+    Fixed32,
+    Fixed64,
+    Sfixed32,
+    Sfixed64,
+    Bool,
+    String,
+    Bytes,
+    Enumeration,
+    Message,
+    Group,
+    Map,
+}
+
+/// Describes a field of a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FieldDescriptor {
+    /// The name of the field in the `.proto` file.
+    pub name: &'static str,
+    /// The name of the field in the proto3 JSON mapping, in `lowerCamelCase`.
+    pub json_name: &'static str,
+    /// The field tag.
+    pub tag: u32,
+    /// The wire type the field is encoded with. This is `LengthDelimited` for packed repeated
+    /// fields and maps.
+    pub wire_type: WireType,
+    /// The type of the field.
+    pub kind: FieldKind,
+    /// Whether the field is repeated. Map fields are repeated.
+    pub repeated: bool,
+    /// The name of the oneof the field belongs to, if any.
+    pub oneof: Option<&'static str>,
+}
+
+/// A message type with static field metadata.
+pub trait Descriptor: Message {
+    /// Returns the descriptors of the fields of the message, in declaration order. The variants of
+    /// a oneof are listed in place of the oneof field.
+    fn fields() -> &'static [FieldDescriptor];
+
+    /// Returns the descriptor of the field with the given tag.
+    fn field(tag: u32) -> Option<&'static FieldDescriptor> {
+        Self::fields().iter().find(|field| field.tag == tag)
+    }
+}
@@ -4,19 +4,27 @@
 
 #![allow(clippy::implicit_hasher, clippy::ptr_arg)]
 
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
 use alloc::format;
 use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::cmp::min;
 use core::convert::TryFrom;
+use core::fmt;
 use core::mem;
+use core::ops::RangeInclusive;
 use core::str;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use core::u32;
 use core::usize;
 
 use ::bytes::{Buf, BufMut, Bytes};
 
+use crate::error::DecodeErrorKind;
+use crate::wire;
+pub use crate::wire::{WireType, MAX_TAG, MIN_TAG};
 use crate::DecodeError;
 use crate::Message;
 
@@ -68,7 +76,7 @@ where
     let bytes = buf.chunk();
     let len = bytes.len();
     if len == 0 {
-        return Err(DecodeError::new("invalid varint"));
+        return Err(DecodeError::invalid_varint());
     }
 
     let byte = unsafe { *bytes.get_unchecked(0) };
@@ -76,7 +84,14 @@ where
         buf.advance(1);
         Ok(u64::from(byte))
     } else if len > 10 || bytes[len - 1] < 0x80 {
-        let (value, advance) = unsafe { decode_varint_slice(bytes) }?;
+        #[cfg(feature = "swar-varint")]
+        let (value, advance) = if len >= 10 {
+            decode_varint_swar(bytes)?
+        } else {
+            unsafe { wire::decode_varint_slice(bytes) }?
+        };
+        #[cfg(not(feature = "swar-varint"))]
+        let (value, advance) = unsafe { wire::decode_varint_slice(bytes) }?;
         buf.advance(advance);
         Ok(value)
     } else {
@@ -87,88 +102,59 @@ where
 /// Decodes a LEB128-encoded variable length integer from the slice, returning the value and the
 /// number of bytes read.
 ///
-/// Based loosely on [`ReadVarint64FromArray`][1].
+/// Rather than inspecting one byte at a time, the first eight bytes are loaded into a single
+/// word, the length of the varint is found from the position of the first byte without the
+/// continuation bit, and the 7-bit groups are packed together with a fixed sequence of masks and
+/// shifts ("SIMD within a register"). Only varints longer than eight bytes take a branch per byte.
 ///
-/// ## Safety
+/// The result is identical to `wire::decode_varint_slice`, including for overlong and overflowing
+/// encodings.
 ///
-/// The caller must ensure that `bytes` is non-empty and either `bytes.len() >= 10` or the last
-/// element in bytes is < `0x80`.
+/// ## Panics
 ///
-/// [1]: https://github.com/google/protobuf/blob/3.3.x/src/google/protobuf/io/coded_stream.cc#L365-L406
+/// Panics if `bytes.len() < 10`.
+#[cfg(any(feature = "swar-varint", test))]
 #[inline]
-unsafe fn decode_varint_slice(bytes: &[u8]) -> Result<(u64, usize), DecodeError> {
-    // Fully unrolled varint decoding loop. Splitting into 32-bit pieces gives better performance.
-
-    let mut b: u8;
-    let mut part0: u32;
-    b = *bytes.get_unchecked(0);
-    part0 = u32::from(b);
-    if b < 0x80 {
-        return Ok((u64::from(part0), 1));
-    };
-    part0 -= 0x80;
-    b = *bytes.get_unchecked(1);
-    part0 += u32::from(b) << 7;
-    if b < 0x80 {
-        return Ok((u64::from(part0), 2));
-    };
-    part0 -= 0x80 << 7;
-    b = *bytes.get_unchecked(2);
-    part0 += u32::from(b) << 14;
-    if b < 0x80 {
-        return Ok((u64::from(part0), 3));
-    };
-    part0 -= 0x80 << 14;
-    b = *bytes.get_unchecked(3);
-    part0 += u32::from(b) << 21;
-    if b < 0x80 {
-        return Ok((u64::from(part0), 4));
-    };
-    part0 -= 0x80 << 21;
-    let value = u64::from(part0);
+fn decode_varint_swar(bytes: &[u8]) -> Result<(u64, usize), DecodeError> {
+    const CONTINUATION_BITS: u64 = 0x8080_8080_8080_8080;
+
+    /// Packs the low 7 bits of each byte of `word` into the low 56 bits of the result.
+    #[inline(always)]
+    fn pack(word: u64) -> u64 {
+        let word = word & !CONTINUATION_BITS;
+        let word = (word & 0x007F_007F_007F_007F) | ((word & 0x7F00_7F00_7F00_7F00) >> 1);
+        let word = (word & 0x0000_3FFF_0000_3FFF) | ((word & 0x3FFF_0000_3FFF_0000) >> 2);
+        (word & 0x0000_0000_0FFF_FFFF) | ((word & 0x0FFF_FFFF_0000_0000) >> 4)
+    }
 
-    let mut part1: u32;
-    b = *bytes.get_unchecked(4);
-    part1 = u32::from(b);
-    if b < 0x80 {
-        return Ok((value + (u64::from(part1) << 28), 5));
-    };
-    part1 -= 0x80;
-    b = *bytes.get_unchecked(5);
-    part1 += u32::from(b) << 7;
-    if b < 0x80 {
-        return Ok((value + (u64::from(part1) << 28), 6));
-    };
-    part1 -= 0x80 << 7;
-    b = *bytes.get_unchecked(6);
-    part1 += u32::from(b) << 14;
-    if b < 0x80 {
-        return Ok((value + (u64::from(part1) << 28), 7));
-    };
-    part1 -= 0x80 << 14;
-    b = *bytes.get_unchecked(7);
-    part1 += u32::from(b) << 21;
-    if b < 0x80 {
-        return Ok((value + (u64::from(part1) << 28), 8));
-    };
-    part1 -= 0x80 << 21;
-    let value = value + ((u64::from(part1)) << 28);
+    let bytes = &bytes[..10];
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    let word = u64::from_le_bytes(word);
+
+    // The high bit of each byte in `ends` is set if the byte terminates the varint.
+    let ends = !word & CONTINUATION_BITS;
+    if ends != 0 {
+        let len = (ends.trailing_zeros() as usize + 1) / 8;
+        let mask = u64::MAX >> (64 - 8 * len);
+        return Ok((pack(word & mask), len));
+    }
 
-    let mut part2: u32;
-    b = *bytes.get_unchecked(8);
-    part2 = u32::from(b);
-    if b < 0x80 {
-        return Ok((value + (u64::from(part2) << 56), 9));
-    };
-    part2 -= 0x80;
-    b = *bytes.get_unchecked(9);
-    part2 += u32::from(b) << 7;
+    let mut value = pack(word);
+    let b = bytes[8];
+    value |= u64::from(b & 0x7F) << 56;
     if b < 0x80 {
-        return Ok((value + (u64::from(part2) << 56), 10));
-    };
+        return Ok((value, 9));
+    }
+    let b = bytes[9];
+    value |= u64::from(b & 0x7F) << 63;
+    if b < 0x80 && !wire::overflows_u64(9, b) {
+        return Ok((value, 10));
+    }
 
-    // We have overrun the maximum size of a varint (10 bytes). Assume the data is corrupt.
-    Err(DecodeError::new("invalid varint"))
+    // We have overrun the maximum size of a varint (10 bytes), or the value doesn't fit in a
+    // `u64`. Assume the data is corrupt.
+    Err(DecodeError::invalid_varint())
 }
 
 /// Decodes a LEB128-encoded variable length integer from the buffer, advancing the buffer as
@@ -183,46 +169,266 @@ where
         let byte = buf.get_u8();
         value |= u64::from(byte & 0x7F) << (count * 7);
         if byte <= 0x7F {
+            if wire::overflows_u64(count, byte) {
+                break;
+            }
             return Ok(value);
         }
     }
 
-    Err(DecodeError::new("invalid varint"))
+    Err(DecodeError::invalid_varint())
 }
 
 /// Additional information passed to every decode/merge function.
 ///
 /// The context should be passed by value and can be freely cloned. When passing
 /// to a function which is decoding a nested object, then use `enter_recursion`.
+///
+/// A context can also limit the number and size of the elements decoded into
+/// repeated fields and maps, to guard against small inputs which expand into
+/// huge collections. The limits are shared by all clones of the context, and
+/// are unlimited by default.
+///
+/// A configured context can be reused for many top-level decodes by passing a
+/// clone of it to each `decode_with_context` or `merge_with_context` call.
+/// Cloning is cheap, since the limits and interner are reference counted, and
+/// each decode tracks its recursion depth starting from the context it is
+/// given. The element and size budgets are not reset though: they bound the
+/// total decoded by all clones together, so create a new context for each
+/// message to limit messages separately.
 #[derive(Clone, Debug)]
 pub struct DecodeContext {
     /// How many times we can recurse in the current decode stack before we hit
     /// the recursion limit.
     ///
-    /// The recursion limit is defined by `RECURSION_LIMIT` and cannot be
-    /// customized. The recursion limit can be ignored by building the Prost
-    /// crate with the `no-recursion-limit` feature.
+    /// The recursion limit defaults to `RECURSION_LIMIT` and can be customized
+    /// with `DecodeContext::with_recursion_limit`. The recursion limit can be
+    /// ignored by building the Prost crate with the `no-recursion-limit` feature.
     #[cfg(not(feature = "no-recursion-limit"))]
     recurse_count: u32,
+
+    /// The configured recursion limit, reported when it is reached.
+    #[cfg(not(feature = "no-recursion-limit"))]
+    recursion_limit: u32,
+
+    /// The remaining element budget, if `max_elements` or `max_total_bytes` is
+    /// set.
+    budget: Option<Arc<Budget>>,
+
+    /// The interner for decoded `Bytes` and `ByteString` values, if
+    /// `with_interner` is set.
+    interner: Option<Interner>,
+
+    /// Whether singular numeric fields accept packed values, see `lenient`.
+    lenient: bool,
+
+    /// Whether varints must be minimally encoded, see `reject_overlong_varints`.
+    canonical_varints: bool,
+}
+
+/// The remaining number and size of the elements which may be decoded into
+/// repeated fields and maps.
+#[derive(Debug)]
+struct Budget {
+    elements: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+/// A function which deduplicates decoded `Bytes` and `ByteString` values.
+#[derive(Clone)]
+struct Interner(Arc<dyn Fn(&[u8]) -> Bytes + Send + Sync>);
+
+impl Interner {
+    /// Interns the next `len` bytes of `buf`, which must have at least `len`
+    /// bytes remaining.
+    fn intern<B>(&self, buf: &mut B, len: usize) -> Bytes
+    where
+        B: Buf,
+    {
+        if buf.chunk().len() >= len {
+            let value = (self.0)(&buf.chunk()[..len]);
+            buf.advance(len);
+            value
+        } else {
+            (self.0)(&buf.copy_to_bytes(len)[..])
+        }
+    }
+}
+
+impl fmt::Debug for Interner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Interner(..)")
+    }
 }
 
 impl Default for DecodeContext {
-    #[cfg(not(feature = "no-recursion-limit"))]
     #[inline]
     fn default() -> DecodeContext {
+        #[cfg(not(feature = "no-recursion-limit"))]
+        let limit = crate::RECURSION_LIMIT;
+        #[cfg(feature = "no-recursion-limit")]
+        let limit = 0;
+        DecodeContext::with_recursion_limit(limit)
+    }
+}
+
+impl DecodeContext {
+    /// Creates a new `DecodeContext` with the default recursion limit and no
+    /// other limits, equivalent to `DecodeContext::default()`.
+    #[inline]
+    pub fn new() -> DecodeContext {
+        DecodeContext::default()
+    }
+
+    /// Creates a new `DecodeContext` which allows decoding messages nested at
+    /// most `limit` levels deep.
+    ///
+    /// The default limit is 100, matching the C++ implementation. `limit` is
+    /// ignored when the Prost crate is built with the `no-recursion-limit` feature.
+    #[cfg(not(feature = "no-recursion-limit"))]
+    #[inline]
+    pub fn with_recursion_limit(limit: u32) -> DecodeContext {
         DecodeContext {
-            recurse_count: crate::RECURSION_LIMIT,
+            recurse_count: limit,
+            recursion_limit: limit,
+            budget: None,
+            interner: None,
+            lenient: false,
+            canonical_varints: false,
         }
     }
 
     #[cfg(feature = "no-recursion-limit")]
     #[inline]
-    fn default() -> DecodeContext {
-        DecodeContext {}
+    pub fn with_recursion_limit(_limit: u32) -> DecodeContext {
+        DecodeContext {
+            budget: None,
+            interner: None,
+            lenient: false,
+            canonical_varints: false,
+        }
+    }
+
+    /// Limits the total number of elements decoded into repeated fields, and
+    /// entries decoded into maps, to `limit`.
+    ///
+    /// Exceeding the limit fails the decode before the element is decoded into
+    /// the collection.
+    pub fn max_elements(self, limit: usize) -> DecodeContext {
+        let bytes = self
+            .budget
+            .as_ref()
+            .map_or(usize::MAX, |budget| budget.bytes.load(Ordering::Relaxed));
+        self.with_budget(limit, bytes)
+    }
+
+    /// Limits the total size in bytes of the elements decoded into repeated
+    /// fields and maps to `limit`.
+    ///
+    /// Each element is counted as its in-memory size, `mem::size_of` of the
+    /// element type, or of the key and value types for map entries. Heap data
+    /// owned by the elements is not counted, since it is already bounded by the
+    /// length of the input. Exceeding the limit fails the decode before the
+    /// element is decoded into the collection.
+    pub fn max_total_bytes(self, limit: usize) -> DecodeContext {
+        let elements = self
+            .budget
+            .as_ref()
+            .map_or(usize::MAX, |budget| budget.elements.load(Ordering::Relaxed));
+        self.with_budget(elements, limit)
+    }
+
+    /// Decodes `Bytes` and `ByteString` fields through `interner`.
+    ///
+    /// The interner is called with the contents of each decoded value, and
+    /// must return a `Bytes` with the same contents, typically a clone of a
+    /// previously seen value. This allows messages with many repeated values to
+    /// share their storage. `ByteString` values are still checked to be valid
+    /// UTF-8. Other `BytesAdapter` and `StringAdapter` types ignore the
+    /// interner.
+    pub fn with_interner(
+        mut self,
+        interner: Arc<dyn Fn(&[u8]) -> Bytes + Send + Sync>,
+    ) -> DecodeContext {
+        self.interner = Some(Interner(interner));
+        self
+    }
+
+    /// Relaxes the wire type checks of singular numeric fields, to tolerate
+    /// compatible schema changes.
+    ///
+    /// A lenient context accepts a packed repeated field for a singular numeric
+    /// field, keeping the last element, as if the field had been changed from
+    /// repeated to singular. Changes between the varint types (`int32`,
+    /// `int64`, `uint32`, `uint64`, `bool` and enumerations) are accepted in
+    /// strict mode as well, since they share a wire type; values out of range
+    /// of the field type are truncated. Changing between `sint32`/`sint64` and
+    /// the other varint types decodes, but the value is not meaningful, since
+    /// the wire format can't distinguish the encodings. 32-bit and 64-bit
+    /// fixed-width fields are never interchangeable. By default, contexts are
+    /// strict.
+    pub fn lenient(mut self) -> DecodeContext {
+        self.lenient = true;
+        self
+    }
+
+    /// Rejects varints which are longer than the minimal encoding of their
+    /// value, such as `0x80 0x00` for 0.
+    ///
+    /// The Protobuf specification tolerates the redundant continuation bytes,
+    /// so by default they are accepted, but a canonical decoder can reject them
+    /// to ensure that equal messages have a single encoding, for instance when
+    /// verifying signatures. The check applies to field keys, length
+    /// delimiters, and the values of varint fields, including skipped fields.
+    /// An overlong varint fails the decode with an `InvalidVarint` error.
+    pub fn reject_overlong_varints(mut self) -> DecodeContext {
+        self.canonical_varints = true;
+        self
+    }
+
+    /// Decodes a varint with `decode_varint`, rejecting an overlong encoding if
+    /// `reject_overlong_varints` is set.
+    ///
+    /// `Message` implementations, including generated views, read varints
+    /// through the context so that the option applies to them.
+    #[inline]
+    pub fn decode_varint<B>(&self, buf: &mut B) -> Result<u64, DecodeError>
+    where
+        B: Buf,
+    {
+        if !self.canonical_varints {
+            return decode_varint(buf);
+        }
+        let remaining = buf.remaining();
+        let value = decode_varint(buf)?;
+        check_canonical(remaining - buf.remaining(), encoded_len_varint(value))?;
+        Ok(value)
+    }
+
+    /// Decodes a field key with `decode_key`, rejecting an overlong encoding if
+    /// `reject_overlong_varints` is set.
+    #[inline]
+    pub fn decode_key<B>(&self, buf: &mut B) -> Result<(u32, WireType), DecodeError>
+    where
+        B: Buf,
+    {
+        if !self.canonical_varints {
+            return decode_key(buf);
+        }
+        let remaining = buf.remaining();
+        let (tag, wire_type) = decode_key(buf)?;
+        check_canonical(remaining - buf.remaining(), key_len(tag))?;
+        Ok((tag, wire_type))
+    }
+
+    fn with_budget(mut self, elements: usize, bytes: usize) -> DecodeContext {
+        self.budget = Some(Arc::new(Budget {
+            elements: AtomicUsize::new(elements),
+            bytes: AtomicUsize::new(bytes),
+        }));
+        self
     }
-}
 
-impl DecodeContext {
     /// Call this function before recursively decoding.
     ///
     /// There is no `exit` function since this function creates a new `DecodeContext`
@@ -233,13 +439,23 @@ impl DecodeContext {
     pub(crate) fn enter_recursion(&self) -> DecodeContext {
         DecodeContext {
             recurse_count: self.recurse_count - 1,
+            recursion_limit: self.recursion_limit,
+            budget: self.budget.clone(),
+            interner: self.interner.clone(),
+            lenient: self.lenient,
+            canonical_varints: self.canonical_varints,
         }
     }
 
     #[cfg(feature = "no-recursion-limit")]
     #[inline]
     pub(crate) fn enter_recursion(&self) -> DecodeContext {
-        DecodeContext {}
+        DecodeContext {
+            budget: self.budget.clone(),
+            interner: self.interner.clone(),
+            lenient: self.lenient,
+            canonical_varints: self.canonical_varints,
+        }
     }
 
     /// Checks whether the recursion limit has been reached in the stack of
@@ -251,7 +467,10 @@ impl DecodeContext {
     #[inline]
     pub(crate) fn limit_reached(&self) -> Result<(), DecodeError> {
         if self.recurse_count == 0 {
-            Err(DecodeError::new("recursion limit reached"))
+            Err(DecodeError::with_kind(
+                DecodeErrorKind::RecursionLimit,
+                format!("recursion limit reached (limit: {})", self.recursion_limit),
+            ))
         } else {
             Ok(())
         }
@@ -262,10 +481,124 @@ impl DecodeContext {
     pub(crate) fn limit_reached(&self) -> Result<(), DecodeError> {
         Ok(())
     }
+
+    /// Charges `count` elements of `size` bytes each against the element
+    /// budget, before they are added to a repeated field or map.
+    ///
+    /// Returns `Err<DecodeError>` if `max_elements` or `max_total_bytes` is
+    /// exceeded.
+    #[inline]
+    pub(crate) fn charge_elements(&self, count: usize, size: usize) -> Result<(), DecodeError> {
+        let budget = match self.budget {
+            Some(ref budget) => budget,
+            None => return Ok(()),
+        };
+        // The budget may be shared with decodes on other threads, so each counter is reserved
+        // atomically, and the elements are given back if the bytes can't be reserved as well.
+        budget
+            .elements
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |elements| {
+                elements.checked_sub(count)
+            })
+            .map_err(|_| {
+                DecodeError::with_kind(DecodeErrorKind::ElementLimit, "element limit exceeded")
+            })?;
+        let total = count.checked_mul(size);
+        let reserved = budget
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bytes| {
+                total.and_then(|total| bytes.checked_sub(total))
+            });
+        if reserved.is_err() {
+            budget.elements.fetch_add(count, Ordering::Relaxed);
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::ElementLimit,
+                "element size limit exceeded",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Caps a capacity hint of `count` elements of `size` bytes each at what
+    /// is left of the element budget, so that a collection is not grown
+    /// beyond what `charge_elements` would allow.
+    #[inline]
+    pub(crate) fn capacity_hint(&self, count: usize, size: usize) -> usize {
+        let budget = match self.budget {
+            Some(ref budget) => budget,
+            None => return count,
+        };
+        let elements = budget.elements.load(Ordering::Relaxed);
+        let bytes = budget.bytes.load(Ordering::Relaxed);
+        let by_size = if size == 0 { count } else { bytes / size };
+        count.min(elements).min(by_size)
+    }
+}
+
+/// Options for `Message::encode_with_options`.
+///
+/// By default, the options produce the same encoding as `Message::encode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// Whether singular scalar fields are written at their default value, see
+    /// `emit_defaults`.
+    emit_defaults: bool,
+}
+
+impl EncodeOptions {
+    /// Creates new `EncodeOptions`, equivalent to `EncodeOptions::default()`.
+    #[inline]
+    pub fn new() -> EncodeOptions {
+        EncodeOptions::default()
+    }
+
+    /// Sets whether singular scalar fields without presence are written even
+    /// when they hold their default value.
+    ///
+    /// Proto3 omits such fields, which is how `encode` behaves, but some peers
+    /// treat an absent field differently from one set to its default. When
+    /// enabled, the fields are written in the messages nested in message,
+    /// group and oneof fields as well. Map entries, and `optional` and
+    /// repeated fields, are encoded as usual. Decoding a message written this
+    /// way yields the same message, since the defaults are merged as values.
+    #[inline]
+    pub fn emit_defaults(mut self, emit_defaults: bool) -> EncodeOptions {
+        self.emit_defaults = emit_defaults;
+        self
+    }
+
+    /// Returns `true` if scalar fields are written at their default value.
+    #[inline]
+    pub fn emits_defaults(&self) -> bool {
+        self.emit_defaults
+    }
+}
+
+/// Checks that a varint read in `len` bytes is not longer than its minimal
+/// encoding of `minimal_len` bytes.
+#[inline]
+fn check_canonical(len: usize, minimal_len: usize) -> Result<(), DecodeError> {
+    if len > minimal_len {
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidVarint,
+            "overlong varint",
+        ));
+    }
+    Ok(())
 }
 
 /// Returns the encoded length of the value in LEB128 variable length format.
 /// The returned value will be between 1 and 10, inclusive.
+///
+/// The length is calculated without encoding the value, so this can be used to size buffers
+/// when combining Protobuf encoding with custom framing.
+///
+/// ```
+/// assert_eq!(prost::encoded_len_varint(0), 1);
+/// assert_eq!(prost::encoded_len_varint(127), 1);
+/// assert_eq!(prost::encoded_len_varint(128), 2);
+/// assert_eq!(prost::encoded_len_varint(u64::MAX), 10);
+/// ```
 #[inline]
 pub fn encoded_len_varint(value: u64) -> usize {
     // Based on [VarintSize64][1].
@@ -273,19 +606,26 @@ pub fn encoded_len_varint(value: u64) -> usize {
     ((((value | 1).leading_zeros() ^ 63) * 9 + 73) / 64) as usize
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-#[repr(u8)]
-pub enum WireType {
-    Varint = 0,
-    SixtyFourBit = 1,
-    LengthDelimited = 2,
-    StartGroup = 3,
-    EndGroup = 4,
-    ThirtyTwoBit = 5,
-}
+/// The field tags reserved for the Protocol Buffers implementation, which may not be used in a
+/// schema.
+pub const RESERVED_TAGS: RangeInclusive<u32> = 19000..=19999;
 
-pub const MIN_TAG: u32 = 1;
-pub const MAX_TAG: u32 = (1 << 29) - 1;
+/// Returns `true` if `tag` may be used as a field number in a schema, i.e. if it is in the range
+/// `MIN_TAG..=MAX_TAG` and not in `RESERVED_TAGS`.
+///
+/// Reserved tags are still accepted by `encode_key` and `decode_key`, since they can appear on
+/// the wire, for instance in unknown fields written by other implementations.
+///
+/// ```
+/// assert!(prost::is_valid_tag(1));
+/// assert!(!prost::is_valid_tag(0));
+/// assert!(!prost::is_valid_tag(19000));
+/// assert!(!prost::is_valid_tag(prost::MAX_TAG + 1));
+/// ```
+#[inline]
+pub fn is_valid_tag(tag: u32) -> bool {
+    (MIN_TAG..=MAX_TAG).contains(&tag) && !RESERVED_TAGS.contains(&tag)
+}
 
 impl TryFrom<u64> for WireType {
     type Error = DecodeError;
@@ -299,16 +639,29 @@ impl TryFrom<u64> for WireType {
             3 => Ok(WireType::StartGroup),
             4 => Ok(WireType::EndGroup),
             5 => Ok(WireType::ThirtyTwoBit),
-            _ => Err(DecodeError::new(format!(
-                "invalid wire type value: {}",
-                value
-            ))),
+            _ => Err(DecodeError::with_kind(
+                DecodeErrorKind::InvalidWireType,
+                format!("invalid wire type value: {}", value),
+            )),
         }
     }
 }
 
+impl TryFrom<u8> for WireType {
+    type Error = DecodeError;
+
+    #[inline]
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        WireType::try_from(u64::from(value))
+    }
+}
+
 /// Encodes a Protobuf field key, which consists of a wire type designator and
 /// the field tag.
+///
+/// The tag must be in the range `MIN_TAG..=MAX_TAG`; this is checked only in debug builds.
+///
+/// This function is part of the stable API, and is re-exported as `prost::encode_key`.
 #[inline]
 pub fn encode_key<B>(tag: u32, wire_type: WireType, buf: &mut B)
 where
@@ -321,27 +674,25 @@ where
 
 /// Decodes a Protobuf field key, which consists of a wire type designator and
 /// the field tag.
+///
+/// An error is returned if the key is not a valid varint, if the tag is not in the range
+/// `MIN_TAG..=MAX_TAG`, or if the wire type is unknown.
+///
+/// This function is part of the stable API, and is re-exported as `prost::decode_key`.
 #[inline(always)]
 pub fn decode_key<B>(buf: &mut B) -> Result<(u32, WireType), DecodeError>
 where
     B: Buf,
 {
     let key = decode_varint(buf)?;
-    if key > u64::from(u32::MAX) {
-        return Err(DecodeError::new(format!("invalid key value: {}", key)));
-    }
-    let wire_type = WireType::try_from(key & 0x07)?;
-    let tag = key as u32 >> 3;
-
-    if tag < MIN_TAG {
-        return Err(DecodeError::new("invalid tag value: 0"));
-    }
-
-    Ok((tag, wire_type))
+    Ok(wire::split_key(key)?)
 }
 
 /// Returns the width of an encoded Protobuf field key with the given tag.
 /// The returned width will be between 1 and 5 bytes (inclusive).
+///
+/// The wire type occupies the low three bits of the key, so the width doesn't depend on it. This
+/// is the number of bytes `encode_key` writes for the tag.
 #[inline]
 pub fn key_len(tag: u32) -> usize {
     encoded_len_varint(u64::from(tag << 3))
@@ -352,43 +703,94 @@ pub fn key_len(tag: u32) -> usize {
 #[inline]
 pub fn check_wire_type(expected: WireType, actual: WireType) -> Result<(), DecodeError> {
     if expected != actual {
-        return Err(DecodeError::new(format!(
-            "invalid wire type: {:?} (expected {:?})",
-            actual, expected
-        )));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedWireType {
+                expected,
+                found: actual,
+            },
+            format!("invalid wire type: {:?} (expected {:?})", actual, expected),
+        ));
     }
     Ok(())
 }
 
+/// Decodes the length delimiter of a length-delimited value.
+///
+/// An error is returned if the length is greater than the remaining bytes in the buffer. The
+/// length is converted to `usize` with a checked conversion, so that on 32-bit and 16-bit targets
+/// a large length is rejected rather than truncated.
+#[inline]
+fn decode_len<B>(buf: &mut B, ctx: &DecodeContext) -> Result<usize, DecodeError>
+where
+    B: Buf,
+{
+    let len = ctx.decode_varint(buf)?;
+    checked_len(len, buf.remaining()).ok_or_else(DecodeError::buffer_underflow)
+}
+
+/// Converts a decoded length to `L`, the `usize` of the target, returning `None` if it doesn't fit
+/// or is greater than `remaining`.
+///
+/// The conversion is generic so that the behavior on targets with a narrower `usize` can be tested
+/// on any host.
+#[inline]
+fn checked_len<L>(len: u64, remaining: L) -> Option<L>
+where
+    L: TryFrom<u64> + PartialOrd,
+{
+    L::try_from(len).ok().filter(|len| *len <= remaining)
+}
+
 /// Helper function which abstracts reading a length delimiter prefix followed
 /// by decoding values until the length of bytes is exhausted.
 pub fn merge_loop<T, M, B>(
     value: &mut T,
     buf: &mut B,
     ctx: DecodeContext,
-    mut merge: M,
+    merge: M,
 ) -> Result<(), DecodeError>
 where
     M: FnMut(&mut T, &mut B, DecodeContext) -> Result<(), DecodeError>,
     B: Buf,
 {
-    let len = decode_varint(buf)?;
-    let remaining = buf.remaining();
-    if len > remaining as u64 {
-        return Err(DecodeError::new("buffer underflow"));
-    }
+    let len = decode_len(buf, &ctx)?;
+    merge_len(value, buf, len, ctx, merge)
+}
 
-    let limit = remaining - len as usize;
+/// Decodes values with `merge` until the next `len` bytes of `buf` are consumed.
+fn merge_len<T, M, B>(
+    value: &mut T,
+    buf: &mut B,
+    len: usize,
+    ctx: DecodeContext,
+    mut merge: M,
+) -> Result<(), DecodeError>
+where
+    M: FnMut(&mut T, &mut B, DecodeContext) -> Result<(), DecodeError>,
+    B: Buf,
+{
+    let limit = buf.remaining() - len;
     while buf.remaining() > limit {
         merge(value, buf, ctx.clone())?;
     }
 
     if buf.remaining() != limit {
-        return Err(DecodeError::new("delimited length exceeded"));
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidLength,
+            "delimited length exceeded",
+        ));
     }
     Ok(())
 }
 
+/// Skips the value of a field with the given wire type and tag, whose key has already been
+/// decoded from `buf`.
+///
+/// This is how derived messages skip unknown fields, so schema-less consumers can use it along
+/// with `decode_key` to walk messages with the same semantics. Groups are skipped up to their
+/// matching end group key, and each level of nested groups counts against the recursion limit of
+/// `ctx`. A length-delimited value longer than the rest of `buf` is rejected with a
+/// `BufferUnderflow` error, without advancing past the end of the buffer.
 pub fn skip_field<B>(
     wire_type: WireType,
     tag: u32,
@@ -400,33 +802,145 @@ where
 {
     ctx.limit_reached()?;
     let len = match wire_type {
-        WireType::Varint => decode_varint(buf).map(|_| 0)?,
+        WireType::Varint => ctx.decode_varint(buf).map(|_| 0)?,
         WireType::ThirtyTwoBit => 4,
         WireType::SixtyFourBit => 8,
-        WireType::LengthDelimited => decode_varint(buf)?,
+        WireType::LengthDelimited => ctx.decode_varint(buf)?,
         WireType::StartGroup => loop {
-            let (inner_tag, inner_wire_type) = decode_key(buf)?;
+            let (inner_tag, inner_wire_type) = ctx.decode_key(buf)?;
             match inner_wire_type {
                 WireType::EndGroup => {
                     if inner_tag != tag {
-                        return Err(DecodeError::new("unexpected end group tag"));
+                        return Err(DecodeError::unexpected_end_group());
                     }
                     break 0;
                 }
                 _ => skip_field(inner_wire_type, inner_tag, buf, ctx.enter_recursion())?,
             }
         },
-        WireType::EndGroup => return Err(DecodeError::new("unexpected end group tag")),
+        WireType::EndGroup => return Err(DecodeError::unexpected_end_group()),
     };
 
     if len > buf.remaining() as u64 {
-        return Err(DecodeError::new("buffer underflow"));
+        return Err(DecodeError::buffer_underflow());
     }
 
     buf.advance(len as usize);
     Ok(())
 }
 
+/// A single field of an encoded message, as returned by `RawFields`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawField<'a> {
+    /// The field tag.
+    pub tag: u32,
+    /// The field wire type.
+    pub wire_type: WireType,
+    /// The encoded field value, without the field key.
+    ///
+    /// For `Varint` fields this is the varint bytes, for `SixtyFourBit` and `ThirtyTwoBit` fields
+    /// the fixed-width bytes, and for `LengthDelimited` fields the payload without the length
+    /// prefix. Nested messages are not decoded. For `StartGroup` fields this is the encoded
+    /// fields between the start and end group keys.
+    pub value: &'a [u8],
+}
+
+/// An iterator over the fields of an encoded message, which does not require the message schema.
+///
+/// Each item is the next field in the buffer. Once an error is returned, the iterator yields no
+/// further items.
+#[derive(Clone, Debug)]
+pub struct RawFields<'a> {
+    buf: &'a [u8],
+    ctx: DecodeContext,
+}
+
+impl<'a> RawFields<'a> {
+    /// Creates an iterator over the fields in `buf`.
+    pub fn new(buf: &'a [u8]) -> RawFields<'a> {
+        RawFields::with_context(buf, DecodeContext::default())
+    }
+
+    /// Creates an iterator over the fields in `buf`, which limits the nesting of groups and
+    /// checks varints with the options of `ctx`.
+    pub fn with_context(buf: &'a [u8], ctx: DecodeContext) -> RawFields<'a> {
+        RawFields { buf, ctx }
+    }
+
+    /// Returns the remaining, not yet iterated, portion of the buffer.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    fn next_field(&mut self) -> Result<RawField<'a>, DecodeError> {
+        let mut buf = self.buf;
+        let (tag, wire_type) = self.ctx.decode_key(&mut buf)?;
+        let start = buf;
+        let value = match wire_type {
+            WireType::Varint => {
+                self.ctx.decode_varint(&mut buf)?;
+                &start[..start.len() - buf.len()]
+            }
+            WireType::SixtyFourBit | WireType::ThirtyTwoBit | WireType::LengthDelimited => {
+                let len = match wire_type {
+                    WireType::SixtyFourBit => 8,
+                    WireType::ThirtyTwoBit => 4,
+                    _ => self.ctx.decode_varint(&mut buf)?,
+                };
+                if len > buf.len() as u64 {
+                    return Err(DecodeError::buffer_underflow());
+                }
+                let (value, rest) = buf.split_at(len as usize);
+                buf = rest;
+                value
+            }
+            WireType::StartGroup => {
+                let start = buf;
+                loop {
+                    let before = buf;
+                    let (inner_tag, inner_wire_type) = self.ctx.decode_key(&mut buf)?;
+                    if inner_wire_type == WireType::EndGroup {
+                        if inner_tag != tag {
+                            return Err(DecodeError::unexpected_end_group());
+                        }
+                        break &start[..start.len() - before.len()];
+                    }
+                    skip_field(
+                        inner_wire_type,
+                        inner_tag,
+                        &mut buf,
+                        self.ctx.enter_recursion(),
+                    )?;
+                }
+            }
+            WireType::EndGroup => return Err(DecodeError::unexpected_end_group()),
+        };
+        self.buf = buf;
+        Ok(RawField {
+            tag,
+            wire_type,
+            value,
+        })
+    }
+}
+
+impl<'a> Iterator for RawFields<'a> {
+    type Item = Result<RawField<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<RawField<'a>, DecodeError>> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let result = self.next_field();
+        if result.is_err() {
+            self.buf = &[];
+        }
+        Some(result)
+    }
+}
+
+impl<'a> core::iter::FusedIterator for RawFields<'a> {}
+
 /// Helper macro which emits an `encode_repeated` function for the type.
 macro_rules! encode_repeated {
     ($ty:ty) => {
@@ -442,23 +956,33 @@ macro_rules! encode_repeated {
 }
 
 /// Helper macro which emits a `merge_repeated` function for the numeric type.
+///
+/// The function accepts both the packed and the unpacked encoding, regardless of how the field
+/// is declared, as required by the encoding guide.
 macro_rules! merge_repeated_numeric {
     ($ty:ty,
      $wire_type:expr,
+     $max_width:expr,
      $merge:ident,
      $merge_repeated:ident) => {
-        pub fn $merge_repeated<B>(
+        pub fn $merge_repeated<C, B>(
             wire_type: WireType,
-            values: &mut Vec<$ty>,
+            values: &mut C,
             buf: &mut B,
             ctx: DecodeContext,
         ) -> Result<(), DecodeError>
         where
+            C: RepeatedContainer<$ty>,
             B: Buf,
         {
             if wire_type == WireType::LengthDelimited {
-                // Packed.
-                merge_loop(values, buf, ctx, |values, buf, ctx| {
+                // Packed. Every element is encoded in at most `$max_width` bytes, so the length
+                // of the field gives a lower bound on the number of elements, which is exact for
+                // fixed-width types.
+                let len = decode_len(buf, &ctx)?;
+                values.reserve(ctx.capacity_hint(len / $max_width, mem::size_of::<$ty>()));
+                merge_len(values, buf, len, ctx, |values, buf, ctx| {
+                    ctx.charge_elements(1, mem::size_of::<$ty>())?;
                     let mut value = Default::default();
                     $merge($wire_type, &mut value, buf, ctx)?;
                     values.push(value);
@@ -467,6 +991,7 @@ macro_rules! merge_repeated_numeric {
             } else {
                 // Unpacked.
                 check_wire_type($wire_type, wire_type)?;
+                ctx.charge_elements(1, mem::size_of::<$ty>())?;
                 let mut value = Default::default();
                 $merge(wire_type, &mut value, buf, ctx)?;
                 values.push(value);
@@ -500,13 +1025,30 @@ macro_rules! varint {
                 encode_varint($to_uint64, buf);
             }
 
-            pub fn merge<B>(wire_type: WireType, value: &mut $ty, buf: &mut B, _ctx: DecodeContext) -> Result<(), DecodeError> where B: Buf {
+            pub fn merge<B>(wire_type: WireType, value: &mut $ty, buf: &mut B, ctx: DecodeContext) -> Result<(), DecodeError> where B: Buf {
+                if wire_type == WireType::LengthDelimited && ctx.lenient {
+                    // A packed repeated field which has been made singular; the last element wins.
+                    return merge_loop(value, buf, ctx, |value, buf, ctx| {
+                        merge(WireType::Varint, value, buf, ctx)
+                    });
+                }
                 check_wire_type(WireType::Varint, wire_type)?;
-                let $from_uint64_value = decode_varint(buf)?;
+                let $from_uint64_value = ctx.decode_varint(buf)?;
                 *value = $from_uint64;
                 Ok(())
             }
 
+            /// Merges a value into an optional field. An empty packed run accepted by a lenient
+            /// context leaves the field as it was.
+            pub fn merge_optional<B>(wire_type: WireType, value: &mut Option<$ty>, buf: &mut B, ctx: DecodeContext) -> Result<(), DecodeError> where B: Buf {
+                if wire_type == WireType::LengthDelimited && ctx.lenient {
+                    return merge_loop(value, buf, ctx, |value, buf, ctx| {
+                        merge(WireType::Varint, value.get_or_insert_with(Default::default), buf, ctx)
+                    });
+                }
+                merge(wire_type, value.get_or_insert_with(Default::default), buf, ctx)
+            }
+
             encode_repeated!($ty);
 
             pub fn encode_packed<B>(tag: u32, values: &[$ty], buf: &mut B) where B: BufMut {
@@ -523,7 +1065,7 @@ macro_rules! varint {
                 }
             }
 
-            merge_repeated_numeric!($ty, WireType::Varint, merge, merge_repeated);
+            merge_repeated_numeric!($ty, WireType::Varint, 10, merge, merge_repeated);
 
             #[inline]
             pub fn encoded_len(tag: u32, $to_uint64_value: &$ty) -> usize {
@@ -630,19 +1172,54 @@ macro_rules! fixed_width {
                 wire_type: WireType,
                 value: &mut $ty,
                 buf: &mut B,
-                _ctx: DecodeContext,
+                ctx: DecodeContext,
             ) -> Result<(), DecodeError>
             where
                 B: Buf,
             {
+                if wire_type == WireType::LengthDelimited && ctx.lenient {
+                    // A packed repeated field which has been made singular; the last element wins.
+                    return merge_loop(value, buf, ctx, |value, buf, ctx| {
+                        merge($wire_type, value, buf, ctx)
+                    });
+                }
                 check_wire_type($wire_type, wire_type)?;
                 if buf.remaining() < $width {
-                    return Err(DecodeError::new("buffer underflow"));
+                    return Err(DecodeError::buffer_underflow());
                 }
                 *value = buf.$get();
                 Ok(())
             }
 
+            /// Merges a value into an optional field. An empty packed run accepted by a lenient
+            /// context leaves the field as it was.
+            pub fn merge_optional<B>(
+                wire_type: WireType,
+                value: &mut Option<$ty>,
+                buf: &mut B,
+                ctx: DecodeContext,
+            ) -> Result<(), DecodeError>
+            where
+                B: Buf,
+            {
+                if wire_type == WireType::LengthDelimited && ctx.lenient {
+                    return merge_loop(value, buf, ctx, |value, buf, ctx| {
+                        merge(
+                            $wire_type,
+                            value.get_or_insert_with(Default::default),
+                            buf,
+                            ctx,
+                        )
+                    });
+                }
+                merge(
+                    wire_type,
+                    value.get_or_insert_with(Default::default),
+                    buf,
+                    ctx,
+                )
+            }
+
             encode_repeated!($ty);
 
             pub fn encode_packed<B>(tag: u32, values: &[$ty], buf: &mut B)
@@ -662,7 +1239,7 @@ macro_rules! fixed_width {
                 }
             }
 
-            merge_repeated_numeric!($ty, $wire_type, merge, merge_repeated);
+            merge_repeated_numeric!($ty, $wire_type, $width, merge, merge_repeated);
 
             #[inline]
             pub fn encoded_len(tag: u32, _: &$ty) -> usize {
@@ -778,6 +1355,7 @@ macro_rules! length_delimited {
             B: Buf,
         {
             check_wire_type(WireType::LengthDelimited, wire_type)?;
+            ctx.charge_elements(1, mem::size_of::<$ty>())?;
             let mut value = Default::default();
             merge(wire_type, &mut value, buf, ctx)?;
             values.push(value);
@@ -804,17 +1382,54 @@ pub mod string;
 
 pub trait BytesAdapter: sealed::BytesAdapter {}
 
-mod sealed {
-    use super::{Buf, BufMut};
+/// A collection which repeated numeric fields can be decoded into.
+///
+/// Implemented for `Vec`, and for `smallvec::SmallVec` when the `smallvec` feature is enabled.
+/// A `SmallVec` stores its elements inline up to its capacity, and only allocates once a field
+/// has more elements than that. The JSON and text format conversions still require `Vec` fields.
+pub trait RepeatedContainer<T>: sealed::RepeatedContainer<T> {}
+
+pub(crate) mod sealed {
+    use super::{Buf, BufMut, DecodeContext, DecodeError};
 
     pub trait BytesAdapter: Default + Sized + 'static {
         fn len(&self) -> usize;
 
+        /// Checks that the buffer can hold `len` bytes.
+        ///
+        /// Called before `replace_with`, so that fixed-capacity buffers can reject oversized
+        /// values with an error.
+        fn check_capacity(&self, _len: usize) -> Result<(), DecodeError> {
+            Ok(())
+        }
+
         /// Replace contents of this buffer with the contents of another buffer.
         fn replace_with<B>(&mut self, buf: B)
         where
             B: Buf;
 
+        /// Replace contents of this buffer with the next `len` bytes of another buffer.
+        ///
+        /// Called by `bytes::merge` after checking that `len` bytes remain, so that adapters
+        /// can avoid copying the value.
+        fn replace_with_len<B>(&mut self, buf: &mut B, len: usize)
+        where
+            B: Buf,
+        {
+            self.replace_with(buf.take(len))
+        }
+
+        /// Replace contents of this buffer with the next `len` bytes of another buffer, using
+        /// the interner of `ctx` if one is set.
+        ///
+        /// Only adapters which share their storage, like `Bytes`, make use of the interner.
+        fn replace_with_ctx<B>(&mut self, buf: &mut B, len: usize, _ctx: &DecodeContext)
+        where
+            B: Buf,
+        {
+            self.replace_with_len(buf, len)
+        }
+
         /// Appends this buffer to the (contents of) other buffer.
         fn append_to<B>(&self, buf: &mut B)
         where
@@ -824,6 +1439,14 @@ mod sealed {
             self.len() == 0
         }
     }
+
+    pub trait RepeatedContainer<T> {
+        /// Appends a decoded element.
+        fn push(&mut self, value: T);
+
+        /// Reserves capacity for at least `additional` more elements.
+        fn reserve(&mut self, additional: usize);
+    }
 }
 
 impl BytesAdapter for Bytes {}
@@ -840,11 +1463,30 @@ impl sealed::BytesAdapter for Bytes {
         *self = buf.copy_to_bytes(buf.remaining());
     }
 
-    fn append_to<B>(&self, buf: &mut B)
+    fn replace_with_len<B>(&mut self, buf: &mut B, len: usize)
     where
-        B: BufMut,
+        B: Buf,
     {
-        buf.put(self.clone())
+        // Unlike `Take`, `Bytes` implements `copy_to_bytes` by slicing, so decoding from a
+        // `Bytes` buffer shares its allocation rather than copying the value.
+        *self = buf.copy_to_bytes(len);
+    }
+
+    fn replace_with_ctx<B>(&mut self, buf: &mut B, len: usize, ctx: &DecodeContext)
+    where
+        B: Buf,
+    {
+        match ctx.interner {
+            Some(ref interner) => *self = interner.intern(buf, len),
+            None => self.replace_with_len(buf, len),
+        }
+    }
+
+    fn append_to<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        buf.put(self.clone())
     }
 }
 
@@ -872,8 +1514,109 @@ impl sealed::BytesAdapter for Vec<u8> {
     }
 }
 
+impl BytesAdapter for Box<[u8]> {}
+
+impl sealed::BytesAdapter for Box<[u8]> {
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn replace_with<B>(&mut self, buf: B)
+    where
+        B: Buf,
+    {
+        // Allocate exactly the required capacity, so that `into_boxed_slice` doesn't reallocate.
+        let mut value = Vec::with_capacity(buf.remaining());
+        value.put(buf);
+        *self = value.into_boxed_slice();
+    }
+
+    fn append_to<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        buf.put_slice(self)
+    }
+}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> BytesAdapter for heapless::Vec<u8, N> {}
+
+#[cfg(feature = "heapless")]
+impl<const N: usize> sealed::BytesAdapter for heapless::Vec<u8, N> {
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    fn check_capacity(&self, len: usize) -> Result<(), DecodeError> {
+        if len > N {
+            return Err(DecodeError::with_kind(
+                DecodeErrorKind::CapacityExceeded,
+                "bytes field exceeds fixed capacity",
+            ));
+        }
+        Ok(())
+    }
+
+    fn replace_with<B>(&mut self, mut buf: B)
+    where
+        B: Buf,
+    {
+        // `check_capacity` has been called, so `buf` fits; any excess is dropped rather than
+        // panicking regardless.
+        self.clear();
+        while buf.has_remaining() && self.len() < N {
+            let chunk = buf.chunk();
+            let len = chunk.len().min(N - self.len());
+            let _ = self.extend_from_slice(&chunk[..len]);
+            buf.advance(len);
+        }
+    }
+
+    fn append_to<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        buf.put(self.as_slice())
+    }
+}
+
+impl<T> RepeatedContainer<T> for Vec<T> {}
+
+impl<T> sealed::RepeatedContainer<T> for Vec<T> {
+    fn push(&mut self, value: T) {
+        Vec::push(self, value)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        Vec::reserve(self, additional)
+    }
+}
+
+#[cfg(feature = "smallvec")]
+impl<A> RepeatedContainer<A::Item> for smallvec::SmallVec<A> where A: smallvec::Array {}
+
+#[cfg(feature = "smallvec")]
+impl<A> sealed::RepeatedContainer<A::Item> for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+{
+    fn push(&mut self, value: A::Item) {
+        smallvec::SmallVec::push(self, value)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        smallvec::SmallVec::reserve(self, additional)
+    }
+}
+
 pub mod bytes;
 
+#[cfg(feature = "fixed128")]
+pub mod fixed128;
+
+pub mod view;
+
 pub mod message {
     use super::*;
 
@@ -887,6 +1630,26 @@ pub mod message {
         msg.encode_raw(buf);
     }
 
+    pub fn encode_deterministic<M, B>(tag: u32, msg: &M, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(msg.encoded_len() as u64, buf);
+        msg.encode_raw_deterministic(buf);
+    }
+
+    pub fn encode_with_options<M, B>(tag: u32, msg: &M, buf: &mut B, options: &EncodeOptions)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::LengthDelimited, buf);
+        encode_varint(msg.encoded_len_with_options(options) as u64, buf);
+        msg.encode_raw_with_options(buf, options);
+    }
+
     pub fn merge<M, B>(
         wire_type: WireType,
         msg: &mut M,
@@ -904,7 +1667,7 @@ pub mod message {
             buf,
             ctx.enter_recursion(),
             |msg: &mut M, buf: &mut B, ctx| {
-                let (tag, wire_type) = decode_key(buf)?;
+                let (tag, wire_type) = ctx.decode_key(buf)?;
                 msg.merge_field(tag, wire_type, buf, ctx)
             },
         )
@@ -931,6 +1694,7 @@ pub mod message {
         B: Buf,
     {
         check_wire_type(WireType::LengthDelimited, wire_type)?;
+        ctx.charge_elements(1, mem::size_of::<M>())?;
         let mut msg = M::default();
         merge(WireType::LengthDelimited, &mut msg, buf, ctx)?;
         messages.push(msg);
@@ -958,6 +1722,55 @@ pub mod message {
                 .map(|len| len + encoded_len_varint(len as u64))
                 .sum::<usize>()
     }
+
+    #[inline]
+    pub fn encoded_len_with_options<M>(tag: u32, msg: &M, options: &EncodeOptions) -> usize
+    where
+        M: Message,
+    {
+        let len = msg.encoded_len_with_options(options);
+        key_len(tag) + encoded_len_varint(len as u64) + len
+    }
+
+    /// Appends the paths of the `required` fields which are missing from `buf`, the encoded value
+    /// of a message field, to `missing`.
+    ///
+    /// `field` only names the type of the message, as the field of `S` which holds it.
+    pub fn missing_required_fields<S, M>(
+        _field: fn(&S) -> Option<&M>,
+        buf: &[u8],
+        path: &str,
+        missing: &mut Vec<String>,
+    ) where
+        M: Message,
+    {
+        M::missing_required_fields(buf, path, missing)
+    }
+
+    /// Adds the encoded message value of the map entry `entry` to `entries`, for
+    /// `Message::missing_required_fields`.
+    ///
+    /// As when decoding, the entry replaces an earlier entry with the same key, and a missing value
+    /// stands for the default message. Keys are compared by their encoding, with a default key
+    /// which is written out matching one which is left out.
+    pub fn push_map_entry<'a>(entries: &mut Vec<(&'a [u8], Vec<u8>)>, entry: &'a [u8]) {
+        let mut key: &[u8] = &[];
+        let mut value = Vec::new();
+        for field in RawFields::new(entry).flatten() {
+            match field.tag {
+                1 if field.wire_type == WireType::LengthDelimited
+                    || field.value.iter().any(|&byte| byte != 0) =>
+                {
+                    key = field.value
+                }
+                1 => key = &[],
+                2 => value.extend_from_slice(field.value),
+                _ => (),
+            }
+        }
+        entries.retain(|&(other, _)| other != key);
+        entries.push((key, value));
+    }
 }
 
 pub mod group {
@@ -973,6 +1786,26 @@ pub mod group {
         encode_key(tag, WireType::EndGroup, buf);
     }
 
+    pub fn encode_deterministic<M, B>(tag: u32, msg: &M, buf: &mut B)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::StartGroup, buf);
+        msg.encode_raw_deterministic(buf);
+        encode_key(tag, WireType::EndGroup, buf);
+    }
+
+    pub fn encode_with_options<M, B>(tag: u32, msg: &M, buf: &mut B, options: &EncodeOptions)
+    where
+        M: Message,
+        B: BufMut,
+    {
+        encode_key(tag, WireType::StartGroup, buf);
+        msg.encode_raw_with_options(buf, options);
+        encode_key(tag, WireType::EndGroup, buf);
+    }
+
     pub fn merge<M, B>(
         tag: u32,
         wire_type: WireType,
@@ -988,10 +1821,10 @@ pub mod group {
 
         ctx.limit_reached()?;
         loop {
-            let (field_tag, field_wire_type) = decode_key(buf)?;
+            let (field_tag, field_wire_type) = ctx.decode_key(buf)?;
             if field_wire_type == WireType::EndGroup {
                 if field_tag != tag {
-                    return Err(DecodeError::new("unexpected end group tag"));
+                    return Err(DecodeError::unexpected_end_group());
                 }
                 return Ok(());
             }
@@ -1022,6 +1855,7 @@ pub mod group {
         B: Buf,
     {
         check_wire_type(WireType::StartGroup, wire_type)?;
+        ctx.charge_elements(1, mem::size_of::<M>())?;
         let mut msg = M::default();
         merge(tag, WireType::StartGroup, &mut msg, buf, ctx)?;
         messages.push(msg);
@@ -1043,6 +1877,42 @@ pub mod group {
     {
         2 * key_len(tag) * messages.len() + messages.iter().map(Message::encoded_len).sum::<usize>()
     }
+
+    #[inline]
+    pub fn encoded_len_with_options<M>(tag: u32, msg: &M, options: &EncodeOptions) -> usize
+    where
+        M: Message,
+    {
+        2 * key_len(tag) + msg.encoded_len_with_options(options)
+    }
+}
+
+/// Returns `true` if `a` and `b` contain equal elements with the same multiplicities, in any
+/// order.
+///
+/// Used by generated `semantic_eq` methods. Only `PartialEq` is required of the elements, so each
+/// element of `a` is matched against the unmatched elements of `b`, which takes quadratic time.
+pub fn set_eq<T>(a: &[T], b: &[T]) -> bool
+where
+    T: PartialEq,
+{
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut matched = alloc::vec![false; b.len()];
+    a.iter().all(|x| {
+        let position = b
+            .iter()
+            .zip(&matched)
+            .position(|(y, &matched)| !matched && x == y);
+        match position {
+            Some(position) => {
+                matched[position] = true;
+                true
+            }
+            None => false,
+        }
+    })
 }
 
 /// Rust doesn't have a `Map` trait, so macros are currently the best way to be
@@ -1139,20 +2009,55 @@ macro_rules! map {
             VL: Fn(u32, &V) -> usize,
         {
             for (key, val) in values.iter() {
-                let skip_key = key == &K::default();
-                let skip_val = val == val_default;
+                encode_entry(
+                    &key_encode,
+                    &key_encoded_len,
+                    &val_encode,
+                    &val_encoded_len,
+                    val_default,
+                    tag,
+                    key,
+                    val,
+                    buf,
+                );
+            }
+        }
 
-                let len = (if skip_key { 0 } else { key_encoded_len(1, key) })
-                    + (if skip_val { 0 } else { val_encoded_len(2, val) });
+        /// Encodes a single map entry as a length-delimited key/value message.
+        #[inline]
+        #[allow(clippy::too_many_arguments)]
+        fn encode_entry<K, V, B, KE, KL, VE, VL>(
+            key_encode: &KE,
+            key_encoded_len: &KL,
+            val_encode: &VE,
+            val_encoded_len: &VL,
+            val_default: &V,
+            tag: u32,
+            key: &K,
+            val: &V,
+            buf: &mut B,
+        ) where
+            K: Default + PartialEq,
+            V: PartialEq,
+            B: BufMut,
+            KE: Fn(u32, &K, &mut B),
+            KL: Fn(u32, &K) -> usize,
+            VE: Fn(u32, &V, &mut B),
+            VL: Fn(u32, &V) -> usize,
+        {
+            let skip_key = key == &K::default();
+            let skip_val = val == val_default;
 
-                encode_key(tag, WireType::LengthDelimited, buf);
-                encode_varint(len as u64, buf);
-                if !skip_key {
-                    key_encode(1, key, buf);
-                }
-                if !skip_val {
-                    val_encode(2, val, buf);
-                }
+            let len = (if skip_key { 0 } else { key_encoded_len(1, key) })
+                + (if skip_val { 0 } else { val_encoded_len(2, val) });
+
+            encode_key(tag, WireType::LengthDelimited, buf);
+            encode_varint(len as u64, buf);
+            if !skip_key {
+                key_encode(1, key, buf);
+            }
+            if !skip_val {
+                val_encode(2, val, buf);
             }
         }
 
@@ -1174,6 +2079,7 @@ macro_rules! map {
             KM: Fn(WireType, &mut K, &mut B, DecodeContext) -> Result<(), DecodeError>,
             VM: Fn(WireType, &mut V, &mut B, DecodeContext) -> Result<(), DecodeError>,
         {
+            ctx.charge_elements(1, mem::size_of::<K>() + mem::size_of::<V>())?;
             let mut key = Default::default();
             let mut val = val_default;
             ctx.limit_reached()?;
@@ -1182,7 +2088,7 @@ macro_rules! map {
                 buf,
                 ctx.enter_recursion(),
                 |&mut (ref mut key, ref mut val), buf, ctx| {
-                    let (tag, wire_type) = decode_key(buf)?;
+                    let (tag, wire_type) = ctx.decode_key(buf)?;
                     match tag {
                         1 => key_merge(wire_type, key, buf, ctx),
                         2 => val_merge(wire_type, val, buf, ctx),
@@ -1232,180 +2138,115 @@ macro_rules! map {
     };
 }
 
+/// Macro which emits map encoding functions which emit entries in key order, for map types whose
+/// iteration order is not the key order.
+macro_rules! sorted_map {
+    ($map_ty:ident) => {
+        /// Generic protobuf map encode function which emits entries in key order.
+        ///
+        /// Unlike `encode`, the output does not depend on the iteration order of the map.
+        pub fn encode_sorted<K, V, B, KE, KL, VE, VL>(
+            key_encode: KE,
+            key_encoded_len: KL,
+            val_encode: VE,
+            val_encoded_len: VL,
+            tag: u32,
+            values: &$map_ty<K, V>,
+            buf: &mut B,
+        ) where
+            K: Default + Eq + Hash + Ord,
+            V: Default + PartialEq,
+            B: BufMut,
+            KE: Fn(u32, &K, &mut B),
+            KL: Fn(u32, &K) -> usize,
+            VE: Fn(u32, &V, &mut B),
+            VL: Fn(u32, &V) -> usize,
+        {
+            encode_with_default_sorted(
+                key_encode,
+                key_encoded_len,
+                val_encode,
+                val_encoded_len,
+                &V::default(),
+                tag,
+                values,
+                buf,
+            )
+        }
+
+        /// Generic protobuf map encode function which emits entries in key order, with an overriden
+        /// value default.
+        ///
+        /// This is necessary because enumeration values can have a default value other
+        /// than 0 in proto2.
+        pub fn encode_with_default_sorted<K, V, B, KE, KL, VE, VL>(
+            key_encode: KE,
+            key_encoded_len: KL,
+            val_encode: VE,
+            val_encoded_len: VL,
+            val_default: &V,
+            tag: u32,
+            values: &$map_ty<K, V>,
+            buf: &mut B,
+        ) where
+            K: Default + Eq + Hash + Ord,
+            V: PartialEq,
+            B: BufMut,
+            KE: Fn(u32, &K, &mut B),
+            KL: Fn(u32, &K) -> usize,
+            VE: Fn(u32, &V, &mut B),
+            VL: Fn(u32, &V) -> usize,
+        {
+            let mut entries = values.iter().collect::<Vec<_>>();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            for (key, val) in entries {
+                encode_entry(
+                    &key_encode,
+                    &key_encoded_len,
+                    &val_encode,
+                    &val_encoded_len,
+                    val_default,
+                    tag,
+                    key,
+                    val,
+                    buf,
+                );
+            }
+        }
+    };
+}
+
 #[cfg(feature = "std")]
 pub mod hash_map {
     use std::collections::HashMap;
     map!(HashMap);
+    sorted_map!(HashMap);
 }
 
 pub mod btree_map {
     map!(BTreeMap);
 }
 
+/// Encoding functions for `IndexMap` fields, which are decoded in the order the entries appear
+/// on the wire, and encoded in their insertion order.
+#[cfg(feature = "indexmap")]
+pub mod index_map {
+    use indexmap::IndexMap;
+    map!(IndexMap);
+    sorted_map!(IndexMap);
+}
+
 #[cfg(test)]
 mod test {
     use alloc::string::ToString;
-    use core::borrow::Borrow;
-    use core::fmt::Debug;
+    use alloc::vec;
     use core::u64;
 
-    use ::bytes::{Bytes, BytesMut};
     use proptest::{prelude::*, test_runner::TestCaseResult};
 
     use crate::encoding::*;
 
-    pub fn check_type<T, B>(
-        value: T,
-        tag: u32,
-        wire_type: WireType,
-        encode: fn(u32, &B, &mut BytesMut),
-        merge: fn(WireType, &mut T, &mut Bytes, DecodeContext) -> Result<(), DecodeError>,
-        encoded_len: fn(u32, &B) -> usize,
-    ) -> TestCaseResult
-    where
-        T: Debug + Default + PartialEq + Borrow<B>,
-        B: ?Sized,
-    {
-        prop_assume!(MIN_TAG <= tag && tag <= MAX_TAG);
-
-        let expected_len = encoded_len(tag, value.borrow());
-
-        let mut buf = BytesMut::with_capacity(expected_len);
-        encode(tag, value.borrow(), &mut buf);
-
-        let mut buf = buf.freeze();
-
-        prop_assert_eq!(
-            buf.remaining(),
-            expected_len,
-            "encoded_len wrong; expected: {}, actual: {}",
-            expected_len,
-            buf.remaining()
-        );
-
-        if !buf.has_remaining() {
-            // Short circuit for empty packed values.
-            return Ok(());
-        }
-
-        let (decoded_tag, decoded_wire_type) =
-            decode_key(&mut buf).map_err(|error| TestCaseError::fail(error.to_string()))?;
-        prop_assert_eq!(
-            tag,
-            decoded_tag,
-            "decoded tag does not match; expected: {}, actual: {}",
-            tag,
-            decoded_tag
-        );
-
-        prop_assert_eq!(
-            wire_type,
-            decoded_wire_type,
-            "decoded wire type does not match; expected: {:?}, actual: {:?}",
-            wire_type,
-            decoded_wire_type,
-        );
-
-        match wire_type {
-            WireType::SixtyFourBit if buf.remaining() != 8 => Err(TestCaseError::fail(format!(
-                "64bit wire type illegal remaining: {}, tag: {}",
-                buf.remaining(),
-                tag
-            ))),
-            WireType::ThirtyTwoBit if buf.remaining() != 4 => Err(TestCaseError::fail(format!(
-                "32bit wire type illegal remaining: {}, tag: {}",
-                buf.remaining(),
-                tag
-            ))),
-            _ => Ok(()),
-        }?;
-
-        let mut roundtrip_value = T::default();
-        merge(
-            wire_type,
-            &mut roundtrip_value,
-            &mut buf,
-            DecodeContext::default(),
-        )
-        .map_err(|error| TestCaseError::fail(error.to_string()))?;
-
-        prop_assert!(
-            !buf.has_remaining(),
-            "expected buffer to be empty, remaining: {}",
-            buf.remaining()
-        );
-
-        prop_assert_eq!(value, roundtrip_value);
-
-        Ok(())
-    }
-
-    pub fn check_collection_type<T, B, E, M, L>(
-        value: T,
-        tag: u32,
-        wire_type: WireType,
-        encode: E,
-        mut merge: M,
-        encoded_len: L,
-    ) -> TestCaseResult
-    where
-        T: Debug + Default + PartialEq + Borrow<B>,
-        B: ?Sized,
-        E: FnOnce(u32, &B, &mut BytesMut),
-        M: FnMut(WireType, &mut T, &mut Bytes, DecodeContext) -> Result<(), DecodeError>,
-        L: FnOnce(u32, &B) -> usize,
-    {
-        prop_assume!(MIN_TAG <= tag && tag <= MAX_TAG);
-
-        let expected_len = encoded_len(tag, value.borrow());
-
-        let mut buf = BytesMut::with_capacity(expected_len);
-        encode(tag, value.borrow(), &mut buf);
-
-        let mut buf = buf.freeze();
-
-        prop_assert_eq!(
-            buf.remaining(),
-            expected_len,
-            "encoded_len wrong; expected: {}, actual: {}",
-            expected_len,
-            buf.remaining()
-        );
-
-        let mut roundtrip_value = Default::default();
-        while buf.has_remaining() {
-            let (decoded_tag, decoded_wire_type) =
-                decode_key(&mut buf).map_err(|error| TestCaseError::fail(error.to_string()))?;
-
-            prop_assert_eq!(
-                tag,
-                decoded_tag,
-                "decoded tag does not match; expected: {}, actual: {}",
-                tag,
-                decoded_tag
-            );
-
-            prop_assert_eq!(
-                wire_type,
-                decoded_wire_type,
-                "decoded wire type does not match; expected: {:?}, actual: {:?}",
-                wire_type,
-                decoded_wire_type
-            );
-
-            merge(
-                wire_type,
-                &mut roundtrip_value,
-                &mut buf,
-                DecodeContext::default(),
-            )
-            .map_err(|error| TestCaseError::fail(error.to_string()))?;
-        }
-
-        prop_assert_eq!(value, roundtrip_value);
-
-        Ok(())
-    }
+    pub use crate::test_util::{check_collection_type, check_type};
 
     #[test]
     fn string_merge_invalid_utf8() {
@@ -1502,6 +2343,509 @@ mod test {
         );
     }
 
+    #[test]
+    fn varint_overflow() {
+        // The tenth byte may only hold the 64th bit.
+        let overflow = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+        let mut padded = overflow.to_vec();
+        padded.extend_from_slice(&[0x00; 6]);
+
+        assert_eq!(
+            decode_varint(&mut &overflow[..]).unwrap_err().kind(),
+            DecodeErrorKind::InvalidVarint
+        );
+        decode_varint(&mut &padded[..]).unwrap_err();
+        decode_varint_slow(&mut &overflow[..]).unwrap_err();
+        decode_varint_swar(&padded).unwrap_err();
+        assert_eq!(
+            wire::decode_varint(&mut &overflow[..]),
+            Err(wire::WireError::InvalidVarint)
+        );
+
+        let mut max = overflow;
+        max[9] = 0x01;
+        padded[9] = 0x01;
+        assert_eq!(decode_varint(&mut &max[..]).unwrap(), u64::MAX);
+        assert_eq!(decode_varint_swar(&padded).unwrap(), (u64::MAX, 10));
+        assert_eq!(wire::decode_varint(&mut &max[..]), Ok(u64::MAX));
+    }
+
+    proptest! {
+        #[test]
+        fn varint_swar(bytes in prop::collection::vec(any::<u8>(), 10..16), len in 0usize..10) {
+            // Terminate the varint within the first ten bytes.
+            let mut bytes = bytes;
+            bytes[len] &= 0x7F;
+            let expected = unsafe { wire::decode_varint_slice(&bytes) }.map_err(DecodeError::from);
+            prop_assert_eq!(decode_varint_swar(&bytes), expected);
+        }
+
+        #[test]
+        fn varint_swar_overlong(bytes in prop::array::uniform10(0x80u8..=0xFF)) {
+            prop_assert!(decode_varint_swar(&bytes).is_err());
+            prop_assert!(unsafe { wire::decode_varint_slice(&bytes) }.is_err());
+        }
+
+        #[test]
+        fn skip_arbitrary_fields(bytes in prop::collection::vec(any::<u8>(), 0..64)) {
+            check_skip_fields(&bytes)?;
+        }
+
+        #[test]
+        fn skip_arbitrary_groups(bytes in prop::collection::vec(group_byte(), 0..64)) {
+            check_skip_fields(&bytes)?;
+        }
+    }
+
+    /// Bytes which make up keys and values of (possibly nested, mismatched or unterminated)
+    /// groups with tags 1 and 2.
+    fn group_byte() -> impl Strategy<Value = u8> {
+        prop::sample::select(vec![0x0b, 0x0c, 0x13, 0x14, 0x08, 0x0a, 0x01, 0x80])
+    }
+
+    /// Skips every field in `bytes`, checking that decoding fails cleanly rather than panicking or
+    /// reading past the end of the buffer.
+    fn check_skip_fields(bytes: &[u8]) -> TestCaseResult {
+        let mut buf = bytes;
+        while buf.has_remaining() {
+            let remaining = buf.remaining();
+            let result = decode_key(&mut buf).and_then(|(tag, wire_type)| {
+                skip_field(wire_type, tag, &mut buf, DecodeContext::default())
+            });
+            if result.is_err() {
+                break;
+            }
+            prop_assert!(buf.remaining() < remaining);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn skip_nested_groups() {
+        // A group nested deeper than the recursion limit.
+        let mut buf = vec![0x0b; 200];
+        buf.resize(400, 0x0c);
+        let mut slice = &buf[..];
+        let (tag, wire_type) = decode_key(&mut slice).unwrap();
+        #[cfg(not(feature = "no-recursion-limit"))]
+        skip_field(wire_type, tag, &mut slice, DecodeContext::default()).unwrap_err();
+        #[cfg(feature = "no-recursion-limit")]
+        skip_field(wire_type, tag, &mut slice, DecodeContext::default()).unwrap();
+
+        // Mismatched and unterminated groups.
+        for buf in &[
+            &[0x0b, 0x14][..],
+            &[0x0b, 0x13, 0x0c][..],
+            &[0x0b, 0x08][..],
+        ] {
+            let mut slice = *buf;
+            let (tag, wire_type) = decode_key(&mut slice).unwrap();
+            skip_field(wire_type, tag, &mut slice, DecodeContext::default()).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn wire_type_conversions() {
+        for value in 0..=5u8 {
+            let wire_type = WireType::try_from(value).unwrap();
+            assert_eq!(wire_type.to_u8(), value);
+        }
+        WireType::try_from(6u8).unwrap_err();
+        WireType::try_from(7u8).unwrap_err();
+        assert_eq!(WireType::LengthDelimited.to_string(), "length-delimited");
+        assert_eq!(WireType::ThirtyTwoBit.to_string(), "32-bit");
+    }
+
+    #[test]
+    fn reject_oversized_lengths() {
+        // A length of `u32::MAX + 1` is truncated to 0 by an `as u32` cast, so it would be
+        // accepted on a 32-bit target, and `u16::MAX + 1` likewise on a 16-bit target.
+        let len = u64::from(u32::MAX) + 1;
+        assert_eq!(checked_len(len, u32::MAX), None);
+        assert_eq!(checked_len(len, u64::MAX), Some(len));
+        assert_eq!(checked_len(u64::from(u16::MAX) + 1, u16::MAX), None);
+        assert_eq!(checked_len(4, 3usize), None);
+        assert_eq!(checked_len(3, 3usize), Some(3));
+
+        // End to end, followed by a few bytes of data.
+        let mut encoded = Vec::new();
+        encode_varint(len, &mut encoded);
+        encoded.extend_from_slice(&[0x08, 0x01]);
+
+        decode_len(&mut &encoded[..], &DecodeContext::default()).unwrap_err();
+
+        let mut value = Vec::<u8>::new();
+        super::bytes::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &encoded[..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+
+        let mut value = String::new();
+        super::string::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &encoded[..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+
+        let mut value = Vec::<u32>::new();
+        uint32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &encoded[..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+        assert!(value.is_empty());
+
+        skip_field(
+            WireType::LengthDelimited,
+            1,
+            &mut &encoded[..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn merge_repeated_smallvec() {
+        let values = vec![1u32, 2, 300];
+        let mut buf = Vec::new();
+        uint32::encode_packed(1, &values, &mut buf);
+        uint32::encode_repeated(1, &values, &mut buf);
+
+        let mut decoded = smallvec::SmallVec::<[u32; 4]>::new();
+        let mut slice = &buf[..];
+        while slice.has_remaining() {
+            let (_, wire_type) = decode_key(&mut slice).unwrap();
+            uint32::merge_repeated(
+                wire_type,
+                &mut decoded,
+                &mut slice,
+                DecodeContext::default(),
+            )
+            .unwrap();
+        }
+        assert_eq!(&decoded[..], &[1, 2, 300, 1, 2, 300]);
+        assert!(decoded.spilled());
+    }
+
+    #[test]
+    fn lenient_singular_fields() {
+        let mut buf = Vec::new();
+        uint32::encode_packed(1, &[1, 2, 3], &mut buf);
+        let mut value = 0u32;
+        uint32::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+        uint32::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap();
+        assert_eq!(value, 3);
+
+        let mut buf = Vec::new();
+        fixed32::encode_packed(1, &[1, 2], &mut buf);
+        let mut value = 0u32;
+        fixed32::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap();
+        assert_eq!(value, 2);
+
+        // An empty packed run leaves optional fields unset.
+        let mut value = None;
+        uint32::merge_optional(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &[0][..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap();
+        assert_eq!(value, None);
+        let mut value = None;
+        fixed32::merge_optional(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &[0][..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap();
+        assert_eq!(value, None);
+        fixed32::merge_optional(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap();
+        assert_eq!(value, Some(2));
+
+        // Fixed-width types of different widths are still incompatible.
+        let mut value = 0u64;
+        fixed64::merge(
+            WireType::ThirtyTwoBit,
+            &mut value,
+            &mut &[1, 0, 0, 0][..],
+            DecodeContext::default().lenient(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn packed_reserves_capacity() {
+        let mut buf = Vec::new();
+        fixed32::encode_packed(1, &[7; 100], &mut buf);
+        let mut values = Vec::new();
+        fixed32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut values,
+            &mut &buf[1..],
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(values.len(), 100);
+        // The exact capacity is reserved up front, rather than grown by doubling.
+        assert_eq!(values.capacity(), 100);
+
+        // The reservation is capped at the element budget, so a tight budget fails before the
+        // capacity for the whole field is allocated.
+        let mut buf = Vec::new();
+        fixed32::encode_packed(1, &[7; 10_000], &mut buf);
+        let mut values = Vec::new();
+        let error = fixed32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut values,
+            &mut &buf[1..],
+            DecodeContext::default().max_elements(10),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::ElementLimit);
+        assert_eq!(values.capacity(), 10);
+
+        let mut values = Vec::new();
+        fixed32::merge_repeated(
+            WireType::LengthDelimited,
+            &mut values,
+            &mut &buf[1..],
+            DecodeContext::default().max_total_bytes(16),
+        )
+        .unwrap_err();
+        assert_eq!(values.capacity(), 4);
+    }
+
+    #[test]
+    fn overlong_varints() {
+        let strict = DecodeContext::default().reject_overlong_varints();
+
+        // An overlong zero is accepted by default.
+        let mut value = 1u32;
+        uint32::merge(
+            WireType::Varint,
+            &mut value,
+            &mut &[0x80, 0x00][..],
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(value, 0);
+        let error = uint32::merge(
+            WireType::Varint,
+            &mut value,
+            &mut &[0x80, 0x00][..],
+            strict.clone(),
+        )
+        .unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidVarint);
+
+        // Minimal encodings are accepted, including the ten bytes of `u64::MAX`.
+        let mut buf = Vec::new();
+        encode_varint(u64::MAX, &mut buf);
+        let mut value = 0u64;
+        uint64::merge(WireType::Varint, &mut value, &mut &buf[..], strict.clone()).unwrap();
+        assert_eq!(value, u64::MAX);
+
+        // Overlong keys and length delimiters are rejected as well.
+        let mut value = 0u32;
+        value
+            .merge_with_context(&[0x88, 0x00, 0x01][..], DecodeContext::default())
+            .unwrap();
+        assert_eq!(value, 1);
+        value
+            .merge_with_context(&[0x88, 0x00, 0x01][..], strict.clone())
+            .unwrap_err();
+        let mut value = Vec::<u8>::new();
+        bytes::merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &[0x81, 0x00, 0x01][..],
+            strict,
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn valid_tags() {
+        assert!(is_valid_tag(MIN_TAG));
+        assert!(is_valid_tag(18999));
+        assert!(is_valid_tag(20000));
+        assert!(is_valid_tag(MAX_TAG));
+        assert!(!is_valid_tag(0));
+        assert!(!is_valid_tag(19000));
+        assert!(!is_valid_tag(19999));
+        assert!(!is_valid_tag(MAX_TAG + 1));
+    }
+
+    #[test]
+    fn element_budget() {
+        let ctx = DecodeContext::default().max_elements(3).max_total_bytes(8);
+        let shared = ctx.clone();
+        ctx.charge_elements(1, 4).unwrap();
+
+        // Failing on the size budget doesn't use up any of the element budget.
+        assert!(shared.charge_elements(2, 4).is_err());
+        shared.charge_elements(2, 2).unwrap();
+        assert!(ctx.charge_elements(1, 0).is_err());
+    }
+
+    #[test]
+    fn set_eq_counts_duplicates() {
+        assert!(set_eq(&[1, 2, 2, 3], &[2, 3, 2, 1]));
+        assert!(!set_eq(&[1, 2, 2], &[1, 1, 2]));
+        assert!(!set_eq(&[1, 2], &[1, 2, 3]));
+        assert!(set_eq::<f64>(&[], &[]));
+    }
+
+    #[test]
+    fn key_roundtrip() {
+        for &tag in &[MIN_TAG, 15, 16, 2047, 2048, MAX_TAG] {
+            let mut buf = Vec::new();
+            encode_key(tag, WireType::ThirtyTwoBit, &mut buf);
+            assert_eq!(buf.len(), key_len(tag));
+            assert_eq!(
+                decode_key(&mut &buf[..]).unwrap(),
+                (tag, WireType::ThirtyTwoBit)
+            );
+        }
+
+        // Tag 0.
+        decode_key(&mut &[0x00][..]).unwrap_err();
+        decode_key(&mut &[0x02][..]).unwrap_err();
+        // Invalid wire types.
+        decode_key(&mut &[0x0e][..]).unwrap_err();
+        decode_key(&mut &[0x0f][..]).unwrap_err();
+        // Tags greater than `MAX_TAG`.
+        decode_key(&mut &[0x80, 0x80, 0x80, 0x80, 0x10][..]).unwrap_err();
+        // Truncated key.
+        decode_key(&mut &[0x80][..]).unwrap_err();
+    }
+
+    #[test]
+    fn raw_fields() {
+        let buf = [
+            0x08, 0x96, 0x01, // 1: varint
+            0x11, 1, 2, 3, 4, 5, 6, 7, 8, // 2: 64-bit
+            0x1a, 0x02, 0x08, 0x01, // 3: length-delimited (a nested message)
+            0x23, 0x08, 0x01, 0x24, // 4: group
+            0x2d, 1, 2, 3, 4, // 5: 32-bit
+        ];
+        let fields = RawFields::new(&buf)
+            .map(|field| field.map(|field| (field.tag, field.wire_type, field.value)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                (1, WireType::Varint, &[0x96, 0x01][..]),
+                (2, WireType::SixtyFourBit, &[1, 2, 3, 4, 5, 6, 7, 8][..]),
+                (3, WireType::LengthDelimited, &[0x08, 0x01][..]),
+                (4, WireType::StartGroup, &[0x08, 0x01][..]),
+                (5, WireType::ThirtyTwoBit, &[1, 2, 3, 4][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn raw_fields_invalid() {
+        for buf in &[
+            &[0x1a, 0x03, 0x00][..], // truncated length-delimited
+            &[0x23, 0x2c][..],       // mismatched end group
+            &[0x24][..],             // unexpected end group
+            &[0x23, 0x08, 0x01][..], // unterminated group
+            &[0x0e][..],             // invalid wire type
+        ] {
+            let mut fields = RawFields::new(buf);
+            assert!(fields.next().unwrap().is_err());
+            assert!(fields.next().is_none());
+        }
+    }
+
+    #[test]
+    fn varint_swar_lengths() {
+        for len in 1..=10 {
+            let mut bytes = [0xFFu8; 12];
+            bytes[len - 1] = 0x01;
+            let expected = unsafe { wire::decode_varint_slice(&bytes) }.unwrap();
+            assert_eq!(expected.1, len);
+            assert_eq!(decode_varint_swar(&bytes).unwrap(), expected);
+        }
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn index_map_wire_order() {
+        use indexmap::IndexMap;
+
+        let mut values = IndexMap::new();
+        values.insert(3, "three".to_string());
+        values.insert(1, "one".to_string());
+        values.insert(2, "two".to_string());
+
+        let mut buf = Vec::new();
+        index_map::encode(
+            int32::encode,
+            int32::encoded_len,
+            string::encode,
+            string::encoded_len,
+            1,
+            &values,
+            &mut buf,
+        );
+        let encoded_len =
+            index_map::encoded_len(int32::encoded_len, string::encoded_len, 1, &values);
+        assert_eq!(buf.len(), encoded_len);
+
+        let mut decoded = IndexMap::new();
+        let mut buf = &buf[..];
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut buf).unwrap();
+            assert_eq!((tag, wire_type), (1, WireType::LengthDelimited));
+            index_map::merge(
+                int32::merge,
+                string::merge,
+                &mut decoded,
+                &mut buf,
+                DecodeContext::default(),
+            )
+            .unwrap();
+        }
+        assert_eq!(decoded.keys().collect::<Vec<_>>(), [&3, &1, &2]);
+    }
+
     /// This big bowl o' macro soup generates an encoding property test for each combination of map
     /// type, scalar map key, and value type.
     /// TODO: these tests take a long time to compile, can this be improved?
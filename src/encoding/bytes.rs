@@ -14,18 +14,15 @@ pub fn merge<A, B>(
     wire_type: WireType,
     value: &mut A,
     buf: &mut B,
-    _ctx: DecodeContext,
+    ctx: DecodeContext,
 ) -> Result<(), DecodeError>
 where
     A: BytesAdapter,
     B: Buf,
 {
     check_wire_type(WireType::LengthDelimited, wire_type)?;
-    let len = decode_varint(buf)?;
-    if len > buf.remaining() as u64 {
-        return Err(DecodeError::new("buffer underflow"));
-    }
-    let len = len as usize;
+    let len = decode_len(buf, &ctx)?;
+    value.check_capacity(len)?;
 
     // Clear the existing value. This follows from the following rule in the encoding guide[1]:
     //
@@ -35,12 +32,10 @@ where
     // > last value it sees.
     //
     // [1]: https://developers.google.com/protocol-buffers/docs/encoding#optional
-
-    // NOTE: The use of BufExt::take() currently prevents zero-copy decoding
-    // for bytes fields backed by Bytes when docoding from Bytes. This could
-    // be addressed in the future by specialization.
-    // See also: https://github.com/tokio-rs/bytes/issues/374
-    value.replace_with(buf.take(len));
+    //
+    // `Bytes` values are sliced out of the buffer without copying when it is itself a `Bytes`, or
+    // deduplicated by the interner of `ctx` if one is set.
+    value.replace_with_ctx(buf, len, &ctx);
     Ok(())
 }
 
@@ -67,6 +62,21 @@ mod test {
                                                     encode, merge, encoded_len)?;
         }
 
+        #[test]
+        fn check_boxed(value: Vec<u8>, tag in MIN_TAG..=MAX_TAG) {
+            let value = value.into_boxed_slice();
+            super::test::check_type::<Box<[u8]>, Box<[u8]>>(value, tag, WireType::LengthDelimited,
+                                                            encode, merge, encoded_len)?;
+        }
+
+        #[cfg(feature = "heapless")]
+        #[test]
+        fn check_heapless(value in prop::collection::vec(any::<u8>(), 0..=32), tag in MIN_TAG..=MAX_TAG) {
+            let value = heapless::Vec::<u8, 32>::from_slice(&value).unwrap();
+            super::test::check_type::<heapless::Vec<u8, 32>, heapless::Vec<u8, 32>>(
+                value, tag, WireType::LengthDelimited, encode, merge, encoded_len)?;
+        }
+
         #[test]
         fn check_repeated_vec(value: Vec<Vec<u8>>, tag in MIN_TAG..=MAX_TAG) {
             super::test::check_collection_type(value, tag, WireType::LengthDelimited,
@@ -82,4 +92,112 @@ mod test {
                                                encoded_len_repeated)?;
         }
     }
+    #[cfg(feature = "heapless")]
+    #[test]
+    fn heapless_capacity() {
+        use alloc::vec;
+
+        let mut buf = Vec::new();
+        encode(1, &vec![1u8, 2, 3, 4], &mut buf);
+
+        let mut value = heapless::Vec::<u8, 4>::new();
+        merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(value.as_slice(), &[1, 2, 3, 4]);
+
+        let mut value = heapless::Vec::<u8, 3>::new();
+        merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+    }
+
+    #[test]
+    fn zero_copy() {
+        let mut buf = Vec::new();
+        encode(1, &Bytes::from_static(b"foo"), &mut buf);
+        let source = Bytes::from(buf);
+
+        let mut value = Bytes::new();
+        merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut source.slice(1..),
+            DecodeContext::default(),
+        )
+        .unwrap();
+        assert_eq!(value, &b"foo"[..]);
+
+        // The value shares the allocation of the source buffer.
+        let start = source.as_ptr() as usize;
+        let ptr = value.as_ptr() as usize;
+        assert!(start <= ptr && ptr + value.len() <= start + source.len());
+    }
+
+    #[test]
+    fn zero_copy_repeated() {
+        use alloc::vec;
+
+        let values = vec![
+            Bytes::from_static(b"foo"),
+            Bytes::from_static(b"bar"),
+            Bytes::from_static(b"baz"),
+        ];
+        let mut buf = Vec::new();
+        encode_repeated(1, &values, &mut buf);
+        let source = Bytes::from(buf);
+
+        let mut decoded = Vec::new();
+        let mut buf = source.clone();
+        while buf.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut buf).unwrap();
+            assert_eq!(tag, 1);
+            merge_repeated(wire_type, &mut decoded, &mut buf, DecodeContext::default()).unwrap();
+        }
+        assert_eq!(decoded, values);
+
+        // Every element shares the allocation of the source buffer.
+        let start = source.as_ptr() as usize;
+        for value in &decoded {
+            let ptr = value.as_ptr() as usize;
+            assert!(start <= ptr && ptr + value.len() <= start + source.len());
+        }
+    }
+
+    #[test]
+    fn interner() {
+        let interned = Bytes::from_static(b"foo");
+        let pool = interned.clone();
+        let ctx = DecodeContext::default().with_interner(Arc::new(move |value: &[u8]| {
+            if value == &pool[..] {
+                pool.clone()
+            } else {
+                Bytes::copy_from_slice(value)
+            }
+        }));
+
+        for input in &[&b"foo"[..], &b"bar"[..]] {
+            let mut buf = Vec::new();
+            encode(1, &input.to_vec(), &mut buf);
+
+            let mut value = Bytes::new();
+            merge(
+                WireType::LengthDelimited,
+                &mut value,
+                &mut &buf[1..],
+                ctx.clone(),
+            )
+            .unwrap();
+            assert_eq!(&value[..], *input);
+            assert_eq!(value.as_ptr() == interned.as_ptr(), *input == b"foo");
+        }
+    }
 }
@@ -0,0 +1,108 @@
+//! Encoding functions for 128-bit integers.
+//!
+//! 128-bit integers are not part of the Protobuf language. This module encodes them as a
+//! length-delimited field holding exactly 16 bytes in little-endian order, so that a custom
+//! scalar mapped to `u128` (or `i128`, through `as` conversions) can be plugged into hand-written
+//! or generated code. Other Protobuf implementations see such fields as `bytes`.
+
+use super::*;
+
+/// The width of an encoded value, without the key and length delimiter.
+const WIDTH: usize = 16;
+
+pub fn encode<B>(tag: u32, value: &u128, buf: &mut B)
+where
+    B: BufMut,
+{
+    encode_key(tag, WireType::LengthDelimited, buf);
+    encode_varint(WIDTH as u64, buf);
+    buf.put_u128_le(*value);
+}
+
+pub fn merge<B>(
+    wire_type: WireType,
+    value: &mut u128,
+    buf: &mut B,
+    ctx: DecodeContext,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+    let len = decode_len(buf, &ctx)?;
+    if len != WIDTH {
+        return Err(DecodeError::new(format!(
+            "invalid fixed128 length: expected {}, found {}",
+            WIDTH, len
+        )));
+    }
+    *value = buf.get_u128_le();
+    Ok(())
+}
+
+encode_repeated!(u128);
+
+pub fn merge_repeated<C, B>(
+    wire_type: WireType,
+    values: &mut C,
+    buf: &mut B,
+    ctx: DecodeContext,
+) -> Result<(), DecodeError>
+where
+    C: RepeatedContainer<u128>,
+    B: Buf,
+{
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+    ctx.charge_elements(1, mem::size_of::<u128>())?;
+    let mut value = 0;
+    merge(wire_type, &mut value, buf, ctx)?;
+    values.push(value);
+    Ok(())
+}
+
+#[inline]
+pub fn encoded_len(tag: u32, _: &u128) -> usize {
+    key_len(tag) + 1 + WIDTH
+}
+
+#[inline]
+pub fn encoded_len_repeated(tag: u32, values: &[u128]) -> usize {
+    (key_len(tag) + 1 + WIDTH) * values.len()
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use super::super::test::{check_collection_type, check_type};
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn check(value: u128, tag in MIN_TAG..=MAX_TAG) {
+            check_type(value, tag, WireType::LengthDelimited, encode, merge, encoded_len)?;
+        }
+
+        #[test]
+        fn check_repeated(value: Vec<u128>, tag in MIN_TAG..=MAX_TAG) {
+            check_collection_type(value, tag, WireType::LengthDelimited,
+                                  encode_repeated, merge_repeated,
+                                  encoded_len_repeated)?;
+        }
+    }
+
+    #[test]
+    fn invalid_length() {
+        let mut buf = Vec::new();
+        super::super::bytes::encode(1, &alloc::vec![0u8; 8], &mut buf);
+
+        let mut value = 0;
+        merge(
+            WireType::LengthDelimited,
+            &mut value,
+            &mut &buf[1..],
+            DecodeContext::default(),
+        )
+        .unwrap_err();
+    }
+}
@@ -0,0 +1,73 @@
+//! Decoding functions for the borrowed message views generated by `prost-build`.
+//!
+//! A view borrows its string, bytes and message fields from the buffer it is parsed from, so
+//! these functions return subslices of the buffer rather than merging into an owned value.
+//! Scalar fields of a view are decoded with the usual encoding modules.
+
+use super::*;
+
+/// Decodes a length-delimited value, returning the slice of `buf` which holds it.
+pub fn bytes<'a>(
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+    ctx: &DecodeContext,
+) -> Result<&'a [u8], DecodeError> {
+    check_wire_type(WireType::LengthDelimited, wire_type)?;
+    let len = decode_len(buf, ctx)?;
+    let (value, rest) = (*buf).split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+/// Decodes a string value, returning the slice of `buf` which holds it.
+pub fn str<'a>(
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+    ctx: &DecodeContext,
+) -> Result<&'a str, DecodeError> {
+    let value = bytes(wire_type, buf, ctx)?;
+    str::from_utf8(value).map_err(|_| DecodeError::invalid_utf8())
+}
+
+/// Decodes an embedded message value with `parse`, typically the `parse_with_context` function
+/// of the nested view.
+pub fn message<'a, V, F>(
+    wire_type: WireType,
+    buf: &mut &'a [u8],
+    ctx: &DecodeContext,
+    parse: F,
+) -> Result<V, DecodeError>
+where
+    F: FnOnce(&'a [u8], DecodeContext) -> Result<V, DecodeError>,
+{
+    ctx.limit_reached()?;
+    let value = bytes(wire_type, buf, ctx)?;
+    parse(value, ctx.enter_recursion())
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::vec;
+
+    use super::*;
+
+    #[test]
+    fn borrowed_values() {
+        let mut encoded = Vec::new();
+        string::encode(1, &String::from("foo"), &mut encoded);
+        crate::encoding::bytes::encode(2, &vec![0xffu8], &mut encoded);
+
+        let ctx = DecodeContext::default();
+        let mut buf = &encoded[..];
+        let (_, wire_type) = decode_key(&mut buf).unwrap();
+        let value = super::str(wire_type, &mut buf, &ctx).unwrap();
+        assert_eq!(value, "foo");
+        assert_eq!(value.as_ptr(), encoded[2..].as_ptr());
+
+        let (_, wire_type) = decode_key(&mut buf).unwrap();
+        let mut invalid = buf;
+        assert_eq!(super::bytes(wire_type, &mut buf, &ctx).unwrap(), &[0xff]);
+        assert!(buf.is_empty());
+        assert!(super::str(wire_type, &mut invalid, &ctx).is_err());
+    }
+}
@@ -2,10 +2,16 @@
 
 use alloc::borrow::Cow;
 use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 
 use core::fmt;
 
+use crate::encoding::WireType;
+
 /// A Protobuf message decoding error.
 ///
 /// `DecodeError` indicates that the input buffer does not caontain a valid
@@ -16,45 +22,194 @@ pub struct DecodeError {
     inner: Box<Inner>,
 }
 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone)]
 struct Inner {
+    /// The category of the root cause.
+    kind: DecodeErrorKind,
     /// A 'best effort' root cause description.
     description: Cow<'static, str>,
     /// A stack of (message, field) name pairs, which identify the specific
     /// message type and field where decoding failed. The stack contains an
     /// entry per level of nesting.
     stack: Vec<(&'static str, &'static str)>,
+    /// The (field name, tag) pairs of the fields being decoded when decoding
+    /// failed, from the outermost message to the innermost field.
+    tag_path: Vec<(&'static str, u32)>,
+    /// The underlying error, such as the I/O error of a failed reader.
+    #[cfg(feature = "std")]
+    source: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+// The source is not compared, since errors generally aren't comparable.
+impl PartialEq for Inner {
+    fn eq(&self, other: &Inner) -> bool {
+        self.kind == other.kind
+            && self.description == other.description
+            && self.stack == other.stack
+            && self.tag_path == other.tag_path
+    }
+}
+
+impl Eq for Inner {}
+
+/// The category of the root cause of a `DecodeError`.
+///
+/// New kinds may be added in the future, so matches must include a wildcard arm.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeErrorKind {
+    /// The input ended before the end of a value or message.
+    BufferUnderflow,
+    /// A varint is longer than 10 bytes, or overflows a `u64`.
+    InvalidVarint,
+    /// A string field is not valid UTF-8.
+    InvalidUtf8,
+    /// A field has a wire type which is valid, but does not match the type of the field.
+    UnexpectedWireType {
+        /// The wire type of the field type.
+        expected: WireType,
+        /// The wire type which was decoded.
+        found: WireType,
+    },
+    /// A field key has an unknown wire type.
+    InvalidWireType,
+    /// A field key has a tag which is 0 or greater than `MAX_TAG`.
+    InvalidTag,
+    /// An end group key does not match the start group key, or is not preceded by one.
+    UnexpectedEndGroup,
+    /// A length-delimited value is longer, or its contents shorter, than its declared length.
+    InvalidLength,
+    /// Data remains in the input after the end of a length-delimited message which was expected
+    /// to end the input.
+    TrailingData,
+    /// Messages are nested deeper than the recursion limit of the `DecodeContext`.
+    RecursionLimit,
+    /// A limit on the number or size of decoded elements of the `DecodeContext` was exceeded.
+    ElementLimit,
+    /// A value does not fit in the capacity of a fixed-capacity field type.
+    CapacityExceeded,
+    /// Reading the input failed.
+    Io,
+    /// A proto2 `required` field is missing, as checked by `Message::decode_validated`.
+    MissingRequiredField,
+    /// Any other error.
+    Other,
 }
 
 impl DecodeError {
-    /// Creates a new `DecodeError` with a 'best effort' root cause description.
+    /// Creates a new `DecodeError` of kind `Other`, with a 'best effort' root cause description.
     ///
-    /// Meant to be used only by `Message` implementations.
+    /// Meant to be used by `Message` implementations, and by conversions from the errors of
+    /// other decoding steps, such as unpacking a `google.protobuf.Any`.
     #[cold]
-    pub(crate) fn new(description: impl Into<Cow<'static, str>>) -> DecodeError {
+    pub fn new(description: impl Into<Cow<'static, str>>) -> DecodeError {
+        DecodeError::with_kind(DecodeErrorKind::Other, description)
+    }
+
+    /// Creates a new `DecodeError` of the given kind, with a 'best effort' root
+    /// cause description.
+    #[cold]
+    pub(crate) fn with_kind(
+        kind: DecodeErrorKind,
+        description: impl Into<Cow<'static, str>>,
+    ) -> DecodeError {
         DecodeError {
             inner: Box::new(Inner {
+                kind,
                 description: description.into(),
                 stack: Vec::new(),
+                tag_path: Vec::new(),
+                #[cfg(feature = "std")]
+                source: None,
             }),
         }
     }
 
-    /// Pushes a (message, field) name location pair on to the location stack.
+    /// Sets the underlying error, returned by `Error::source`.
+    #[cfg(feature = "std")]
+    pub(crate) fn with_source(
+        mut self,
+        source: impl std::error::Error + Send + Sync + 'static,
+    ) -> DecodeError {
+        self.inner.source = Some(Arc::new(source));
+        self
+    }
+
+    #[cold]
+    pub(crate) fn buffer_underflow() -> DecodeError {
+        DecodeError::with_kind(DecodeErrorKind::BufferUnderflow, "buffer underflow")
+    }
+
+    #[cold]
+    pub(crate) fn invalid_varint() -> DecodeError {
+        DecodeError::with_kind(DecodeErrorKind::InvalidVarint, "invalid varint")
+    }
+
+    #[cold]
+    pub(crate) fn invalid_utf8() -> DecodeError {
+        DecodeError::with_kind(
+            DecodeErrorKind::InvalidUtf8,
+            "invalid string value: data is not UTF-8 encoded",
+        )
+    }
+
+    #[cold]
+    pub(crate) fn unexpected_end_group() -> DecodeError {
+        DecodeError::with_kind(
+            DecodeErrorKind::UnexpectedEndGroup,
+            "unexpected end group tag",
+        )
+    }
+
+    #[cold]
+    pub(crate) fn trailing_data(len: usize) -> DecodeError {
+        DecodeError::with_kind(
+            DecodeErrorKind::TrailingData,
+            format!("{} bytes of trailing data after message", len),
+        )
+    }
+
+    /// Returns the category of the root cause of the error.
+    ///
+    /// Unlike the description, the kind is stable, so it can be matched on to
+    /// handle errors programmatically, for instance to retry a decode after a
+    /// `BufferUnderflow` once more input has been received.
+    pub fn kind(&self) -> DecodeErrorKind {
+        self.inner.kind
+    }
+
+    /// Returns the (field name, tag) pairs of the fields being decoded when
+    /// decoding failed, from the outermost message to the innermost field.
+    ///
+    /// For example, an invalid value in field `b` (tag 2) of a message in field
+    /// `a` (tag 1) of the decoded message has the path `[("a", 1), ("b", 2)]`.
+    pub fn tag_path(&self) -> &[(&'static str, u32)] {
+        &self.inner.tag_path
+    }
+
+    /// Pushes a (message, field) name location pair, along with the tag of the
+    /// field, on to the location stack.
     ///
     /// Meant to be used only by `Message` implementations.
     #[doc(hidden)]
-    pub fn push(&mut self, message: &'static str, field: &'static str) {
+    pub fn push(&mut self, message: &'static str, field: &'static str, tag: u32) {
         self.inner.stack.push((message, field));
+        // Errors propagate from the innermost field outwards.
+        self.inner.tag_path.insert(0, (field, tag));
     }
 }
 
 impl fmt::Debug for DecodeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("DecodeError")
+        let mut builder = f.debug_struct("DecodeError");
+        builder
+            .field("kind", &self.inner.kind)
             .field("description", &self.inner.description)
             .field("stack", &self.inner.stack)
-            .finish()
+            .field("tag_path", &self.inner.tag_path);
+        #[cfg(feature = "std")]
+        builder.field("source", &self.inner.source);
+        builder.finish()
     }
 }
 
@@ -69,7 +224,14 @@ impl fmt::Display for DecodeError {
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for DecodeError {}
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.inner.source {
+            Some(ref source) => Some(&**source),
+            None => None,
+        }
+    }
+}
 
 #[cfg(feature = "std")]
 impl From<DecodeError> for std::io::Error {
@@ -78,26 +240,139 @@ impl From<DecodeError> for std::io::Error {
     }
 }
 
+/// An error converting an `i32` to an enumeration which has no variant with that value.
+///
+/// Holds the original value, so that unknown values of open proto3 enumerations can be preserved.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UnknownEnumValue(pub i32);
+
+impl fmt::Display for UnknownEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown enumeration value: {}", self.0)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for UnknownEnumValue {}
+
+/// An error returned by `Message::validate_encoding` when `required` fields are missing from an
+/// encoded message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MissingFieldsError {
+    fields: Vec<String>,
+}
+
+impl MissingFieldsError {
+    pub(crate) fn new(fields: Vec<String>) -> MissingFieldsError {
+        MissingFieldsError { fields }
+    }
+
+    /// Returns the paths of the missing fields, from the outermost message, such as
+    /// `inner.id` for the `id` field of the `inner` message field.
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+}
+
+impl fmt::Display for MissingFieldsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("missing required fields: ")?;
+        for (i, field) in self.fields.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(field)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MissingFieldsError {}
+
+impl From<MissingFieldsError> for DecodeError {
+    fn from(error: MissingFieldsError) -> DecodeError {
+        let description = format!("{}", error);
+        #[allow(unused_mut)]
+        let mut decode_error =
+            DecodeError::with_kind(DecodeErrorKind::MissingRequiredField, description);
+        #[cfg(feature = "std")]
+        {
+            decode_error = decode_error.with_source(error);
+        }
+        decode_error
+    }
+}
+
 /// A Protobuf message encoding error.
 ///
-/// `EncodeError` always indicates that a message failed to encode because the
-/// provided buffer had insufficient capacity. Message encoding is otherwise
+/// `EncodeError` indicates that a message failed to encode because the
+/// provided buffer had insufficient capacity, or, when encoding to a
+/// `std::io::Write`, because the writer failed. Message encoding is otherwise
 /// infallible.
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug)]
 pub struct EncodeError {
     required: usize,
     remaining: usize,
+    /// The underlying I/O error, and the number of bytes written before it
+    /// occurred.
+    #[cfg(feature = "std")]
+    io: Option<(Arc<std::io::Error>, usize)>,
 }
 
+// The I/O error is compared by kind, since `std::io::Error` isn't comparable.
+impl PartialEq for EncodeError {
+    fn eq(&self, other: &EncodeError) -> bool {
+        #[cfg(feature = "std")]
+        {
+            if self.io_error_kind() != other.io_error_kind()
+                || self.bytes_written() != other.bytes_written()
+            {
+                return false;
+            }
+        }
+        self.required == other.required && self.remaining == other.remaining
+    }
+}
+
+impl Eq for EncodeError {}
+
 impl EncodeError {
     /// Creates a new `EncodeError`.
     pub(crate) fn new(required: usize, remaining: usize) -> EncodeError {
         EncodeError {
             required,
             remaining,
+            #[cfg(feature = "std")]
+            io: None,
         }
     }
 
+    /// Creates a new `EncodeError` for a writer which failed with `error` after
+    /// `written` of the `required` bytes were written.
+    #[cfg(feature = "std")]
+    pub(crate) fn io(error: std::io::Error, required: usize, written: usize) -> EncodeError {
+        EncodeError {
+            required,
+            remaining: 0,
+            io: Some((Arc::new(error), written)),
+        }
+    }
+
+    /// Returns the kind of the I/O error which caused encoding to a writer to fail, or `None` if
+    /// the error is due to insufficient buffer capacity.
+    #[cfg(feature = "std")]
+    pub fn io_error_kind(&self) -> Option<std::io::ErrorKind> {
+        self.io.as_ref().map(|(error, _)| error.kind())
+    }
+
+    /// Returns the number of bytes successfully written before encoding to a writer failed, or
+    /// `None` if the error is due to insufficient buffer capacity.
+    #[cfg(feature = "std")]
+    pub fn bytes_written(&self) -> Option<usize> {
+        self.io.as_ref().map(|&(_, written)| written)
+    }
+
     /// Returns the required buffer capacity to encode the message.
     pub fn required_capacity(&self) -> usize {
         self.required
@@ -111,6 +386,16 @@ impl EncodeError {
 
 impl fmt::Display for EncodeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "std")]
+        {
+            if let Some((ref error, written)) = self.io {
+                return write!(
+                    f,
+                    "failed to encode Protobuf message; I/O error ({:?}) after writing {} of {} bytes",
+                    error.kind(), written, self.required
+                );
+            }
+        }
         write!(
             f,
             "failed to encode Protobuf messsage; insufficient buffer capacity (required: {}, remaining: {})",
@@ -120,11 +405,21 @@ impl fmt::Display for EncodeError {
 }
 
 #[cfg(feature = "std")]
-impl std::error::Error for EncodeError {}
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.io {
+            Some((ref error, _)) => Some(&**error),
+            None => None,
+        }
+    }
+}
 
 #[cfg(feature = "std")]
 impl From<EncodeError> for std::io::Error {
     fn from(error: EncodeError) -> std::io::Error {
-        std::io::Error::new(std::io::ErrorKind::InvalidInput, error)
+        let kind = error
+            .io_error_kind()
+            .unwrap_or(std::io::ErrorKind::InvalidInput);
+        std::io::Error::new(kind, error)
     }
 }
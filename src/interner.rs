@@ -0,0 +1,124 @@
+//! Deduplication of `ByteString` values.
+
+use std::collections::HashSet;
+use std::str;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use bytes::Bytes;
+use bytestring::ByteString;
+
+/// A registry of shared `ByteString` values.
+///
+/// `intern` returns a `ByteString` which shares the storage of the first equal string interned,
+/// so a long-running service which handles a bounded vocabulary of strings keeps a single copy of
+/// each. Cloning the interner is cheap, and the clones share the registry. The interner can also
+/// deduplicate decoded fields, see `decode_interner`.
+///
+/// Interned strings are kept until `clear` is called, so interning an unbounded set of strings
+/// grows the registry without bound.
+///
+/// ```
+/// use prost::interner::ByteStringInterner;
+///
+/// let interner = ByteStringInterner::new();
+/// let a = interner.intern("foo");
+/// let b = interner.intern("foo");
+/// assert_eq!(a.as_bytes().as_ptr(), b.as_bytes().as_ptr());
+/// assert_eq!(interner.len(), 1);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ByteStringInterner {
+    strings: Arc<Mutex<HashSet<ByteString>>>,
+}
+
+impl ByteStringInterner {
+    /// Creates a new, empty interner.
+    pub fn new() -> ByteStringInterner {
+        ByteStringInterner::default()
+    }
+
+    /// Returns the interned `ByteString` equal to `s`, interning a copy of `s` if there is none.
+    pub fn intern(&self, s: &str) -> ByteString {
+        let mut strings = self.lock();
+        if let Some(interned) = strings.get(s) {
+            return interned.clone();
+        }
+        let interned = ByteString::from(s);
+        strings.insert(interned.clone());
+        interned
+    }
+
+    /// Returns the number of interned strings.
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Returns `true` if no strings are interned.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Removes all interned strings.
+    ///
+    /// Values returned by `intern` remain valid, but are no longer shared with later values.
+    pub fn clear(&self) {
+        self.lock().clear()
+    }
+
+    /// Returns an interner for `DecodeContext::with_interner`, which interns decoded values
+    /// through this registry.
+    ///
+    /// Decoded values which are valid UTF-8 are interned, including those of `Bytes` fields.
+    /// Other values are copied, and rejected by the decoder if they belong to a string field.
+    pub fn decode_interner(&self) -> Arc<dyn Fn(&[u8]) -> Bytes + Send + Sync> {
+        let interner = self.clone();
+        Arc::new(move |value: &[u8]| match str::from_utf8(value) {
+            Ok(s) => interner.intern(s).as_bytes().clone(),
+            Err(_) => Bytes::copy_from_slice(value),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashSet<ByteString>> {
+        // The set is never left inconsistent by a panic, so a poisoned lock can be reused.
+        self.strings.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::encoding::{string, DecodeContext, WireType};
+
+    #[test]
+    fn intern() {
+        let interner = ByteStringInterner::new();
+        assert!(interner.is_empty());
+        let foo = interner.intern("foo");
+        let bar = interner.intern("bar");
+        assert_eq!(
+            interner.intern("foo").as_bytes().as_ptr(),
+            foo.as_bytes().as_ptr()
+        );
+        assert_ne!(foo.as_bytes().as_ptr(), bar.as_bytes().as_ptr());
+        assert_eq!(interner.len(), 2);
+
+        interner.clear();
+        assert!(interner.is_empty());
+        assert_eq!(interner.intern("foo"), foo);
+    }
+
+    #[test]
+    fn decode_interner() {
+        let interner = ByteStringInterner::new();
+        let foo = interner.intern("foo");
+
+        let mut buf = Vec::new();
+        string::encode(1, &String::from("foo"), &mut buf);
+        let ctx = DecodeContext::default().with_interner(interner.decode_interner());
+        let mut value = ByteString::new();
+        string::merge(WireType::LengthDelimited, &mut value, &mut &buf[1..], ctx).unwrap();
+        assert_eq!(value.as_bytes().as_ptr(), foo.as_bytes().as_ptr());
+        assert_eq!(interner.len(), 1);
+    }
+}
@@ -0,0 +1,689 @@
+//! Encoding and decoding of length-delimited messages with `std::io` writers and readers.
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+use bytes::buf::UninitSlice;
+use bytes::{Buf, BufMut};
+
+use crate::encoding::{encode_varint, encoded_len_varint};
+use crate::error::DecodeErrorKind;
+use crate::wire;
+use crate::{DecodeError, EncodeError, Message};
+
+/// The initial buffer capacity used when reading a message body.
+///
+/// The declared length of a message is not trusted for the up-front allocation, since a corrupt or
+/// malicious length prefix could otherwise trigger an arbitrarily large allocation. The buffer
+/// grows as data is actually read.
+const INITIAL_CAPACITY: usize = 8 * 1024;
+
+/// The size of the buffer used to batch writes to a writer.
+const WRITE_BUFFER_SIZE: usize = 8 * 1024;
+
+/// The size of the buffer used to read a message incrementally from a reader.
+const READ_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Encodes a message with a length-delimiter directly to a writer.
+///
+/// The message is encoded through a small fixed-size buffer, so encoding a large message does not
+/// require a buffer the size of the whole message. If the writer fails, the returned error
+/// reports how many bytes were written before the failure, and returns the I/O error as its
+/// `source`.
+///
+/// The writer is not flushed.
+pub fn encode_length_delimited<M, W>(msg: &M, writer: W) -> Result<(), EncodeError>
+where
+    M: Message,
+    W: Write,
+{
+    let len = msg.encoded_len();
+    let required = len + encoded_len_varint(len as u64);
+    let mut buf = WriterBuf::new(writer, WRITE_BUFFER_SIZE);
+    encode_varint(len as u64, &mut buf);
+    msg.encode_raw(&mut buf);
+    buf.finish_with_written()
+        .map(drop)
+        .map_err(|(error, written)| EncodeError::io(error, required, written))
+}
+
+/// Encodes a message without a length-delimiter directly to a writer, through a buffer of
+/// `buf_size` bytes.
+///
+/// The encoded fields are written out whenever `buf_size` bytes have been buffered, so a large
+/// message is streamed to the writer as it is encoded, without materializing it. Framing which
+/// needs the length up front, like an HTTP `Content-Length`, can use `encoded_len`. Errors are
+/// reported as by `encode_length_delimited`.
+///
+/// The writer is not flushed.
+pub fn encode_buffered<M, W>(msg: &M, writer: W, buf_size: usize) -> Result<(), EncodeError>
+where
+    M: Message,
+    W: Write,
+{
+    let required = msg.encoded_len();
+    let mut buf = WriterBuf::new(writer, buf_size);
+    msg.encode_raw(&mut buf);
+    buf.finish_with_written()
+        .map(drop)
+        .map_err(|(error, written)| EncodeError::io(error, required, written))
+}
+
+/// A `BufMut` which batches writes to an underlying writer.
+///
+/// Data put into the buffer is written out whenever `buf_size` bytes have been buffered, so a
+/// message encoded with `Message::encode_raw` is streamed to the writer as it is encoded.
+/// `finish` writes out the rest of the data and returns the writer. `BufMut` can't fail, so the
+/// first I/O error is recorded and reported by `finish`; all data put after it is discarded.
+///
+/// ```
+/// use prost::io::WriterBuf;
+/// use prost::Message;
+///
+/// let value = "x".repeat(1000);
+/// let mut buf = WriterBuf::new(Vec::new(), 100);
+/// value.encode_raw(&mut buf);
+/// assert_eq!(buf.finish().unwrap(), value.encode_to_vec());
+/// ```
+#[derive(Debug)]
+pub struct WriterBuf<W> {
+    writer: W,
+    buf: Vec<u8>,
+    /// The buffered length at which the data is written to the writer.
+    buf_size: usize,
+    written: usize,
+    error: Option<io::Error>,
+}
+
+impl<W> WriterBuf<W>
+where
+    W: Write,
+{
+    /// Creates a buffer which writes to `writer` in chunks of `buf_size` bytes.
+    pub fn new(writer: W, buf_size: usize) -> WriterBuf<W> {
+        WriterBuf {
+            writer,
+            // The buffer grows up to `buf_size` as data is put, so that a large `buf_size` doesn't
+            // allocate up front.
+            buf: Vec::with_capacity(buf_size.min(WRITE_BUFFER_SIZE)),
+            buf_size,
+            written: 0,
+            error: None,
+        }
+    }
+
+    /// Writes the buffered data to the writer.
+    fn flush_buf(&mut self) {
+        let mut buf = &self.buf[..];
+        while self.error.is_none() && !buf.is_empty() {
+            match self.writer.write(buf) {
+                Ok(0) => self.error = Some(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    self.written += n;
+                }
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => self.error = Some(error),
+            }
+        }
+        self.buf.clear();
+    }
+
+    /// Returns the number of bytes written to the writer so far.
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
+
+    /// Writes any remaining buffered data, and returns the writer.
+    ///
+    /// Returns the first I/O error which occurred while writing, if any. The writer is not
+    /// flushed.
+    pub fn finish(self) -> io::Result<W> {
+        self.finish_with_written().map_err(|(error, _)| error)
+    }
+
+    /// Like `finish`, but also returns the number of bytes written along with an I/O error.
+    fn finish_with_written(mut self) -> Result<W, (io::Error, usize)> {
+        self.flush_buf();
+        match self.error {
+            Some(error) => Err((error, self.written)),
+            None => Ok(self.writer),
+        }
+    }
+}
+
+unsafe impl<W> BufMut for WriterBuf<W>
+where
+    W: Write,
+{
+    fn remaining_mut(&self) -> usize {
+        self.buf.remaining_mut()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        self.buf.advance_mut(cnt)
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        if self.buf.len() >= self.buf_size {
+            self.flush_buf();
+        }
+        self.buf.chunk_mut()
+    }
+}
+
+/// A reader over the encoding of a message, without a length delimiter.
+///
+/// Returned by `Message::into_reader`. The length of the encoding is computed up front and
+/// available from `encoded_len`, for framing like an HTTP `Content-Length`. The message is encoded
+/// into a buffer of exactly that size on the first read, rather than as the reader is consumed,
+/// since the derived encoders can't be suspended part way through a field. Use
+/// `encode_buffered` to stream a large message to a writer without materializing it.
+#[derive(Debug)]
+pub struct MessageReader<M> {
+    /// The message, until it is encoded by the first read.
+    message: Option<M>,
+    len: usize,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<M> MessageReader<M>
+where
+    M: Message,
+{
+    pub(crate) fn new(message: M) -> MessageReader<M> {
+        MessageReader {
+            len: message.encoded_len(),
+            message: Some(message),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Returns the total length of the encoding, including bytes already read.
+    pub fn encoded_len(&self) -> usize {
+        self.len
+    }
+}
+
+impl<M> Read for MessageReader<M>
+where
+    M: Message,
+{
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if let Some(message) = self.message.take() {
+            self.buf.reserve_exact(self.len);
+            message.encode_raw(&mut self.buf);
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Decodes a single length-delimited message from a reader.
+///
+/// Returns `Ok(None)` if the reader is at EOF before the first byte of the length delimiter. If
+/// the reader reaches EOF part way through the length delimiter or the message, a 'truncated'
+/// error is returned.
+pub fn decode_length_delimited<M, R>(mut reader: R) -> Result<Option<M>, DecodeError>
+where
+    M: Message + Default,
+    R: Read,
+{
+    let len = match read_length_delimiter(&mut reader)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+
+    let mut buf = Vec::with_capacity(len.min(INITIAL_CAPACITY));
+    reader
+        .by_ref()
+        .take(len as u64)
+        .read_to_end(&mut buf)
+        .map_err(io_error)?;
+    if buf.len() != len {
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::BufferUnderflow,
+            format!(
+                "truncated message (expected {} bytes, found {})",
+                len,
+                buf.len()
+            ),
+        ));
+    }
+
+    M::decode(&*buf).map(Some)
+}
+
+/// Decodes a message of `len` bytes from a reader, and merges it into `msg`.
+///
+/// The message is read incrementally through a buffer of at most 8 KiB, so the caller doesn't
+/// need to allocate a buffer for the whole message. If the reader reaches EOF or fails before
+/// `len` bytes are read, a 'truncated' or I/O error is returned rather than the error, if any,
+/// from decoding the incomplete message. If decoding fails before all `len` bytes are read, the
+/// remaining bytes are left unread.
+pub fn merge_from_reader<M, R>(msg: &mut M, reader: R, len: usize) -> Result<(), DecodeError>
+where
+    M: Message,
+    R: Read,
+{
+    let mut buf = ReaderBuf::new(reader, len);
+    let result = msg.merge(&mut buf);
+    match buf.error {
+        Some(error) => Err(error),
+        None => result,
+    }
+}
+
+/// A `Buf` which reads a fixed number of bytes from an underlying reader, through a small buffer.
+///
+/// The first read error, or an early EOF, is recorded in `error`. To keep the `Buf` consistent,
+/// the bytes which could not be read are then left as zeros, and the decode result is discarded.
+struct ReaderBuf<R> {
+    reader: R,
+    buf: Vec<u8>,
+    /// The position of the next unconsumed byte in `buf`.
+    pos: usize,
+    /// The number of bytes which have not yet been consumed, including those in `buf`.
+    remaining: usize,
+    /// The total number of bytes to read.
+    len: usize,
+    error: Option<DecodeError>,
+}
+
+impl<R> ReaderBuf<R>
+where
+    R: Read,
+{
+    fn new(reader: R, len: usize) -> ReaderBuf<R> {
+        let mut buf = ReaderBuf {
+            reader,
+            buf: Vec::with_capacity(len.min(READ_BUFFER_SIZE)),
+            pos: 0,
+            remaining: len,
+            len,
+            error: None,
+        };
+        buf.fill();
+        buf
+    }
+
+    /// Refills the buffer once all of it has been consumed.
+    fn fill(&mut self) {
+        if self.pos < self.buf.len() || self.remaining == 0 {
+            return;
+        }
+        let size = self.remaining.min(READ_BUFFER_SIZE);
+        self.buf.clear();
+        self.buf.resize(size, 0);
+        self.pos = 0;
+
+        let mut filled = 0;
+        while self.error.is_none() && filled < size {
+            match self.reader.read(&mut self.buf[filled..]) {
+                Ok(0) => {
+                    let read = self.len - self.remaining + filled;
+                    self.error = Some(DecodeError::with_kind(
+                        DecodeErrorKind::BufferUnderflow,
+                        format!(
+                            "truncated message (expected {} bytes, found {})",
+                            self.len, read
+                        ),
+                    ));
+                }
+                Ok(n) => filled += n,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => self.error = Some(io_error(error)),
+            }
+        }
+    }
+}
+
+impl<R> Buf for ReaderBuf<R>
+where
+    R: Read,
+{
+    fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    fn chunk(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    fn advance(&mut self, mut cnt: usize) {
+        assert!(
+            cnt <= self.remaining,
+            "cannot advance past the end of the message"
+        );
+        while cnt > 0 {
+            let n = cnt.min(self.buf.len() - self.pos);
+            self.pos += n;
+            self.remaining -= n;
+            cnt -= n;
+            self.fill();
+        }
+    }
+}
+
+/// Returns an iterator which decodes consecutive length-delimited messages from a reader.
+///
+/// This is the format written by repeatedly calling `Message::encode_length_delimited`, or
+/// `writeDelimitedTo` in the Java implementation. The iterator ends when the reader is at EOF at
+/// a message boundary. After an error is returned the iterator yields no further items.
+pub fn decode_length_delimited_stream<M, R>(reader: R) -> DecodeLengthDelimitedStream<M, R>
+where
+    M: Message + Default,
+    R: Read,
+{
+    DecodeLengthDelimitedStream {
+        reader,
+        done: false,
+        _message: PhantomData,
+    }
+}
+
+/// An iterator over length-delimited messages read from a reader.
+///
+/// Returned by `decode_length_delimited_stream`.
+#[derive(Debug)]
+pub struct DecodeLengthDelimitedStream<M, R> {
+    reader: R,
+    done: bool,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M, R> DecodeLengthDelimitedStream<M, R> {
+    /// Consumes the iterator, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<M, R> Iterator for DecodeLengthDelimitedStream<M, R>
+where
+    M: Message + Default,
+    R: Read,
+{
+    type Item = Result<M, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<M, DecodeError>> {
+        if self.done {
+            return None;
+        }
+        let result = decode_length_delimited(&mut self.reader).transpose();
+        if !matches!(result, Some(Ok(_))) {
+            self.done = true;
+        }
+        result
+    }
+}
+
+impl<M, R> core::iter::FusedIterator for DecodeLengthDelimitedStream<M, R>
+where
+    M: Message + Default,
+    R: Read,
+{
+}
+
+/// Reads a length delimiter from a reader one byte at a time, so that no bytes beyond the
+/// delimiter are consumed.
+///
+/// Returns `Ok(None)` if the reader is at EOF before the first byte.
+fn read_length_delimiter<R>(reader: &mut R) -> Result<Option<usize>, DecodeError>
+where
+    R: Read,
+{
+    let mut value = 0u64;
+    for count in 0..10 {
+        let mut byte = [0u8];
+        loop {
+            match reader.read(&mut byte) {
+                Ok(0) if count == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::BufferUnderflow,
+                        "truncated length delimiter",
+                    ))
+                }
+                Ok(_) => break,
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(io_error(error)),
+            }
+        }
+        let byte = byte[0];
+        value |= u64::from(byte & 0x7F) << (count * 7);
+        if byte < 0x80 {
+            // Check for u64::MAX overflow, as `decode_varint` does.
+            if wire::overflows_u64(count, byte) {
+                return Err(DecodeError::invalid_varint());
+            }
+            if value > usize::MAX as u64 {
+                return Err(DecodeError::with_kind(
+                    DecodeErrorKind::InvalidLength,
+                    "length delimiter exceeds maximum usize value",
+                ));
+            }
+            return Ok(Some(value as usize));
+        }
+    }
+    Err(DecodeError::invalid_varint())
+}
+
+fn io_error(error: io::Error) -> DecodeError {
+    DecodeError::with_kind(
+        DecodeErrorKind::Io,
+        format!("failed to read message: {}", error),
+    )
+    .with_source(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(values: &[&str]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for value in values {
+            value.to_string().encode_length_delimited(&mut buf).unwrap();
+        }
+        buf
+    }
+
+    #[test]
+    fn stream() {
+        let buf = encode(&["foo", "", "bar"]);
+        let values = decode_length_delimited_stream::<String, _>(&*buf)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(values, vec!["foo", "", "bar"]);
+    }
+
+    #[test]
+    fn eof() {
+        assert_eq!(decode_length_delimited::<String, _>(&b""[..]), Ok(None));
+        assert_eq!(
+            decode_length_delimited_stream::<String, _>(&b""[..]).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let buf = encode(&["foo"]);
+        let mut stream = decode_length_delimited_stream::<String, _>(&buf[..buf.len() - 1]);
+        assert!(stream
+            .next()
+            .unwrap()
+            .unwrap_err()
+            .to_string()
+            .contains("truncated message"));
+        assert!(stream.next().is_none());
+
+        decode_length_delimited::<String, _>(&[0x80u8][..]).unwrap_err();
+    }
+
+    #[test]
+    fn encode_to_writer() {
+        let value = "x".repeat(3 * WRITE_BUFFER_SIZE);
+        let mut buf = Vec::new();
+        encode_length_delimited(&value, &mut buf).unwrap();
+        let mut expected = Vec::new();
+        value.encode_length_delimited(&mut expected).unwrap();
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn encode_to_failing_writer() {
+        let value = "x".repeat(3 * WRITE_BUFFER_SIZE);
+        let mut storage = [0u8; WRITE_BUFFER_SIZE + 1];
+        let error = encode_length_delimited(&value, &mut storage[..]).unwrap_err();
+        assert_eq!(error.io_error_kind(), Some(io::ErrorKind::WriteZero));
+        assert_eq!(error.bytes_written(), Some(WRITE_BUFFER_SIZE + 1));
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.downcast_ref::<io::Error>().unwrap().kind(),
+            io::ErrorKind::WriteZero
+        );
+        let len = value.encoded_len();
+        assert_eq!(
+            error.required_capacity(),
+            len + encoded_len_varint(len as u64)
+        );
+    }
+
+    #[test]
+    fn encode_buffered_in_chunks() {
+        /// A writer which records the length of each write.
+        struct Recording(Vec<u8>, Vec<usize>);
+
+        impl Write for Recording {
+            fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+                self.0.extend_from_slice(data);
+                self.1.push(data.len());
+                Ok(data.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value = "x".repeat(1000);
+        let mut writer = Recording(Vec::new(), Vec::new());
+        encode_buffered(&value, &mut writer, 100).unwrap();
+        assert_eq!(writer.0, value.encode_to_vec());
+        assert!(writer.1.len() > 1);
+
+        let error = encode_buffered(&value, &mut [0u8; 10][..], 100).unwrap_err();
+        assert_eq!(error.io_error_kind(), Some(io::ErrorKind::WriteZero));
+        assert_eq!(error.required_capacity(), value.encoded_len());
+    }
+
+    #[test]
+    fn writer_buf() {
+        let value = "x".repeat(1000);
+        let mut buf = WriterBuf::new(Vec::new(), usize::MAX);
+        value.encode_raw(&mut buf);
+        assert_eq!(buf.bytes_written(), 0);
+        assert_eq!(buf.finish().unwrap(), value.encode_to_vec());
+
+        let mut storage = [0u8; 10];
+        let mut buf = WriterBuf::new(&mut storage[..], 4);
+        value.encode_raw(&mut buf);
+        assert_eq!(buf.bytes_written(), 10);
+        assert_eq!(buf.finish().unwrap_err().kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn message_reader() {
+        let value = "x".repeat(1000);
+        let mut reader = value.clone().into_reader();
+        assert_eq!(reader.encoded_len(), value.encoded_len());
+
+        let mut chunk = [0u8; 7];
+        let mut buf = Vec::new();
+        loop {
+            match reader.read(&mut chunk).unwrap() {
+                0 => break,
+                n => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        assert_eq!(buf, value.encode_to_vec());
+    }
+
+    /// A reader which returns at most one byte per read.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            let n = out.len().min(self.0.len()).min(1);
+            out[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn merge_from_reader_in_chunks() {
+        let value = "x".repeat(3 * READ_BUFFER_SIZE);
+        let buf = value.encode_to_vec();
+        let input = [&buf[..], &b"trailing"[..]].concat();
+
+        let mut reader = Trickle(&input);
+        let mut decoded = String::new();
+        merge_from_reader(&mut decoded, &mut reader, buf.len()).unwrap();
+        assert_eq!(decoded, value);
+        assert_eq!(reader.0, b"trailing");
+    }
+
+    #[test]
+    fn merge_from_reader_truncated() {
+        let buf = "x".repeat(100).encode_to_vec();
+        let mut decoded = String::new();
+        let error = merge_from_reader(&mut decoded, &buf[..50], buf.len()).unwrap_err();
+        assert!(error.to_string().contains("expected 102 bytes, found 50"));
+
+        // A genuine decode error is reported as such.
+        let error = merge_from_reader(&mut decoded, &[0x0f][..], 1).unwrap_err();
+        assert!(error.to_string().contains("invalid wire type"));
+    }
+
+    #[test]
+    fn read_error_source() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::Other, "boom"))
+            }
+        }
+
+        let error = decode_length_delimited::<String, _>(Failing).unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::Io);
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "boom");
+    }
+
+    #[test]
+    fn large_declared_length() {
+        // A huge declared length must not be allocated up front.
+        let buf = [0xFF, 0xFF, 0xFF, 0xFF, 0x0F, 0x00];
+        decode_length_delimited::<String, _>(&buf[..]).unwrap_err();
+    }
+
+    #[test]
+    fn overflowing_length_delimiter() {
+        let mut buf = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+        let error = decode_length_delimited::<String, _>(&buf[..]).unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidVarint);
+
+        // `u64::MAX` is a valid varint, but no message follows it.
+        buf[9] = 0x01;
+        let error = decode_length_delimited::<String, _>(&buf[..]).unwrap_err();
+        assert_ne!(error.kind(), DecodeErrorKind::InvalidVarint);
+    }
+}
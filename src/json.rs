@@ -0,0 +1,1018 @@
+//! Conversion of messages to and from JSON, following the [proto3 JSON mapping][1].
+//!
+//! [1]: https://developers.google.com/protocol-buffers/docs/proto3#json
+
+pub use serde_json::{Map, Value};
+
+use alloc::borrow::Cow;
+use core::fmt;
+
+use bytes::Bytes;
+
+use crate::Message;
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+/// The range of `google.protobuf.Duration` seconds, approximately +-10,000 years.
+const MAX_DURATION_SECONDS: i64 = 315_576_000_000;
+
+/// The range of `google.protobuf.Timestamp` seconds: 0001-01-01T00:00:00Z to
+/// 9999-12-31T23:59:59Z.
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
+/// A message which can be converted to its proto3 JSON representation.
+///
+/// Implemented by `#[derive(Message)]` for messages marked `#[prost(json)]`.
+pub trait ToJson {
+    /// Returns the JSON representation of the message.
+    fn to_json(&self) -> Value;
+}
+
+/// A message which can be merged from its proto3 JSON representation.
+///
+/// Implemented by `#[derive(Message)]` for messages marked `#[prost(json)]`.
+pub trait FromJson {
+    /// Merges the JSON representation of a message into `self`.
+    ///
+    /// Fields present in the JSON object replace the corresponding fields of `self`; `null`
+    /// values are ignored.
+    fn merge_json(&mut self, value: &Value, options: FromJsonOptions) -> Result<(), JsonError>;
+}
+
+impl<M> ToJson for Box<M>
+where
+    M: ToJson,
+{
+    fn to_json(&self) -> Value {
+        (**self).to_json()
+    }
+}
+
+impl<M> FromJson for Box<M>
+where
+    M: FromJson,
+{
+    fn merge_json(&mut self, value: &Value, options: FromJsonOptions) -> Result<(), JsonError> {
+        (**self).merge_json(value, options)
+    }
+}
+
+/// The wrapper well-known types are represented in JSON by their wrapped value.
+macro_rules! wrapper {
+    ($($ty:ty => $convert:ident,)*) => {
+        $(
+            impl ToJson for $ty {
+                fn to_json(&self) -> Value {
+                    encode::$convert(self)
+                }
+            }
+
+            impl FromJson for $ty {
+                fn merge_json(
+                    &mut self,
+                    value: &Value,
+                    _options: FromJsonOptions,
+                ) -> Result<(), JsonError> {
+                    *self = decode::$convert(value)?;
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+wrapper! {
+    bool => bool,
+    u32 => uint32,
+    u64 => uint64,
+    i32 => int32,
+    i64 => int64,
+    f32 => float,
+    f64 => double,
+    String => string,
+    Vec<u8> => bytes,
+    Bytes => bytes,
+}
+
+/// `google.protobuf.Empty`
+impl ToJson for () {
+    fn to_json(&self) -> Value {
+        Value::Object(Map::new())
+    }
+}
+
+/// `google.protobuf.Empty`
+impl FromJson for () {
+    fn merge_json(&mut self, value: &Value, options: FromJsonOptions) -> Result<(), JsonError> {
+        for name in decode::object(value)?.keys() {
+            decode::unknown_field(name, options)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for converting JSON to messages.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FromJsonOptions {
+    deny_unknown_fields: bool,
+}
+
+impl FromJsonOptions {
+    /// Creates the default options.
+    pub fn new() -> FromJsonOptions {
+        FromJsonOptions::default()
+    }
+
+    /// Sets whether object keys which do not name a field of the message are rejected with an
+    /// error. By default, they are ignored.
+    pub fn deny_unknown_fields(mut self, deny: bool) -> FromJsonOptions {
+        self.deny_unknown_fields = deny;
+        self
+    }
+}
+
+/// An error converting JSON to a message.
+///
+/// The error reports the path to the offending JSON value, e.g. `$.items[2].createTime`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct JsonError {
+    inner: Box<Inner>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct Inner {
+    description: Cow<'static, str>,
+    /// The path to the offending value, innermost segment first.
+    path: Vec<PathSegment>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl JsonError {
+    #[cold]
+    fn new(description: impl Into<Cow<'static, str>>) -> JsonError {
+        JsonError {
+            inner: Box::new(Inner {
+                description: description.into(),
+                path: Vec::new(),
+            }),
+        }
+    }
+
+    fn push(mut self, segment: PathSegment) -> JsonError {
+        self.inner.path.push(segment);
+        self
+    }
+
+    /// Returns the path to the offending value, e.g. `$.items[2].createTime`.
+    pub fn path(&self) -> String {
+        let mut path = String::from("$");
+        for segment in self.inner.path.iter().rev() {
+            match *segment {
+                PathSegment::Field(ref name) => {
+                    path.push('.');
+                    path.push_str(name);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+}
+
+impl fmt::Debug for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonError")
+            .field("description", &self.inner.description)
+            .field("path", &self.path())
+            .finish()
+    }
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to convert JSON to Protobuf message at {}: {}",
+            self.path(),
+            self.inner.description
+        )
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/// Converts a message to its proto3 JSON representation.
+///
+/// The canonical mapping is used:
+///
+///  * fields are keyed by their `lowerCamelCase` JSON name,
+///  * unset optional and message fields are `null`,
+///  * 64-bit integers are strings, and non-finite floats are `"NaN"`, `"Infinity"` or
+///    `"-Infinity"`,
+///  * `bytes` fields are base64 strings,
+///  * enum values are their name, or their number if the value is not a known variant,
+///  * `Duration`, `Timestamp` and `FieldMask` use their special string representations.
+///
+/// All other fields, including those with default values, are emitted.
+pub fn to_json_value<M>(msg: &M) -> Value
+where
+    M: Message + ToJson,
+{
+    msg.to_json()
+}
+
+/// Converts the proto3 JSON representation of a message to a message.
+///
+/// Fields may be keyed by their `lowerCamelCase` JSON name or by their original proto name, and
+/// the parsing is lenient where the mapping allows it: 64-bit integers may be strings or numbers,
+/// integers may be written in exponent notation, bytes may use the standard or URL-safe base64
+/// alphabet, and `null` values leave a field unset. Keys which do not name a field are ignored.
+pub fn from_json_value<M>(value: &Value) -> Result<M, JsonError>
+where
+    M: Message + FromJson + Default,
+{
+    from_json_value_with_options(value, FromJsonOptions::default())
+}
+
+/// Converts the proto3 JSON representation of a message to a message, with the given options.
+///
+/// See `from_json_value` for details.
+pub fn from_json_value_with_options<M>(
+    value: &Value,
+    options: FromJsonOptions,
+) -> Result<M, JsonError>
+where
+    M: Message + FromJson + Default,
+{
+    let mut msg = M::default();
+    msg.merge_json(value, options)?;
+    Ok(msg)
+}
+
+/// Converts a `lower_snake_case` proto name to the `lowerCamelCase` name used in JSON.
+pub(crate) fn json_name(name: &str) -> String {
+    let mut json_name = String::with_capacity(name.len());
+    let mut capitalize_next = false;
+    for c in name.chars() {
+        if c == '_' {
+            capitalize_next = true;
+        } else if capitalize_next {
+            json_name.extend(c.to_uppercase());
+            capitalize_next = false;
+        } else {
+            json_name.push(c);
+        }
+    }
+    json_name
+}
+
+/// Converts a `lowerCamelCase` JSON name to the `lower_snake_case` proto name.
+fn proto_name(json_name: &str) -> String {
+    let mut name = String::with_capacity(json_name.len());
+    for c in json_name.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('_');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    name
+}
+
+/// Formats a fractional second with 0, 3, 6 or 9 digits, as required by the JSON mapping.
+fn push_nanos(s: &mut String, nanos: u32) {
+    if nanos == 0 {
+        return;
+    }
+    let fraction = if nanos % 1_000_000 == 0 {
+        format!(".{:03}", nanos / 1_000_000)
+    } else if nanos % 1_000 == 0 {
+        format!(".{:06}", nanos / 1_000)
+    } else {
+        format!(".{:09}", nanos)
+    };
+    s.push_str(&fraction);
+}
+
+/// Functions used by derived `ToJson` implementations to convert field values.
+#[doc(hidden)]
+pub mod encode {
+    use super::*;
+
+    use crate::encoding::sealed::BytesAdapter as _;
+    use crate::encoding::string::StringAdapter;
+    use crate::encoding::BytesAdapter;
+
+    macro_rules! number {
+        ($($name:ident: $ty:ty),*) => {
+            $(
+                pub fn $name(value: &$ty) -> Value {
+                    Value::from(*value)
+                }
+            )*
+        };
+    }
+
+    macro_rules! string_number {
+        ($($name:ident: $ty:ty),*) => {
+            $(
+                pub fn $name(value: &$ty) -> Value {
+                    Value::String(value.to_string())
+                }
+            )*
+        };
+    }
+
+    number!(int32: i32, uint32: u32, sint32: i32, fixed32: u32, sfixed32: i32);
+    string_number!(int64: i64, uint64: u64, sint64: i64, fixed64: u64, sfixed64: i64);
+
+    pub fn bool(value: &bool) -> Value {
+        Value::Bool(*value)
+    }
+
+    pub fn double(value: &f64) -> Value {
+        if value.is_nan() {
+            Value::String("NaN".into())
+        } else if value.is_infinite() {
+            let s = if *value > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            Value::String(s.into())
+        } else {
+            Value::from(*value)
+        }
+    }
+
+    pub fn float(value: &f32) -> Value {
+        // Widen through the shortest decimal representation, so that e.g. `0.1f32` is emitted as
+        // `0.1` rather than `0.10000000149011612`.
+        let widened = value
+            .to_string()
+            .parse()
+            .unwrap_or_else(|_| f64::from(*value));
+        double(&widened)
+    }
+
+    pub fn string<A>(value: &A) -> Value
+    where
+        A: StringAdapter,
+    {
+        Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned())
+    }
+
+    pub fn bytes<A>(value: &A) -> Value
+    where
+        A: BytesAdapter,
+    {
+        let mut buf = Vec::with_capacity(value.len());
+        value.append_to(&mut buf);
+        Value::String(crate::base64::encode(&buf))
+    }
+
+    /// Converts an enum value, given the name of the variant if the value is known.
+    pub fn enumeration(value: i32, name: Option<&'static str>) -> Value {
+        match name {
+            Some(name) => Value::String(name.into()),
+            None => Value::from(value),
+        }
+    }
+
+    /// Converts an encoded map key to a JSON object key.
+    pub fn map_key(key: Value) -> String {
+        match key {
+            Value::String(key) => key,
+            key => key.to_string(),
+        }
+    }
+
+    /// Converts a `google.protobuf.Duration` to a number of seconds with an `s` suffix, e.g.
+    /// `"-1.500s"`.
+    pub fn duration(seconds: i64, nanos: i32) -> Value {
+        let total = i128::from(seconds) * NANOS_PER_SECOND + i128::from(nanos);
+        let mut s = String::new();
+        if total < 0 {
+            s.push('-');
+        }
+        let total = total.abs();
+        s.push_str(&(total / NANOS_PER_SECOND).to_string());
+        push_nanos(&mut s, (total % NANOS_PER_SECOND) as u32);
+        s.push('s');
+        Value::String(s)
+    }
+
+    /// Converts a `google.protobuf.Timestamp` to an RFC 3339 date-time string in UTC, e.g.
+    /// `"1972-01-01T10:00:20.021Z"`.
+    pub fn timestamp(seconds: i64, nanos: i32) -> Value {
+        let total = i128::from(seconds) * NANOS_PER_SECOND + i128::from(nanos);
+        let seconds = total.div_euclid(NANOS_PER_SECOND);
+        let nanos = total.rem_euclid(NANOS_PER_SECOND) as u32;
+        let days = seconds.div_euclid(86_400);
+        let second_of_day = seconds.rem_euclid(86_400);
+
+        // Convert the day number to a civil date, using the algorithm from
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + i128::from(month <= 2);
+
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            second_of_day / 3600,
+            second_of_day / 60 % 60,
+            second_of_day % 60,
+        );
+        push_nanos(&mut s, nanos);
+        s.push('Z');
+        Value::String(s)
+    }
+
+    /// Converts a `google.protobuf.FieldMask` to a comma-separated list of `lowerCamelCase`
+    /// paths, e.g. `"user.displayName,photo"`.
+    pub fn field_mask(paths: &[String]) -> Value {
+        let paths = paths.iter().map(|path| json_name(path)).collect::<Vec<_>>();
+        Value::String(paths.join(","))
+    }
+}
+
+/// Functions used by derived `FromJson` implementations to convert field values.
+#[doc(hidden)]
+pub mod decode {
+    use super::*;
+
+    use core::convert::TryFrom;
+    use core::iter::FromIterator;
+
+    use crate::encoding::sealed::BytesAdapter as _;
+    use crate::encoding::string::StringAdapter;
+    use crate::encoding::BytesAdapter;
+
+    /// Returns the fields of a JSON object.
+    pub fn object(value: &Value) -> Result<&Map<String, Value>, JsonError> {
+        value
+            .as_object()
+            .ok_or_else(|| JsonError::new("expected an object"))
+    }
+
+    /// Handles an object key which does not name a field of the message.
+    pub fn unknown_field(name: &str, options: FromJsonOptions) -> Result<(), JsonError> {
+        if options.deny_unknown_fields {
+            return Err(JsonError::new("unknown field").push(PathSegment::Field(name.into())));
+        }
+        Ok(())
+    }
+
+    /// Adds the field name to the path of an error converting the field's value.
+    pub fn field<T>(name: &str, result: Result<T, JsonError>) -> Result<T, JsonError> {
+        result.map_err(|error| error.push(PathSegment::Field(name.into())))
+    }
+
+    fn str_value<'a>(value: &'a Value, expected: &'static str) -> Result<&'a str, JsonError> {
+        value.as_str().ok_or_else(|| JsonError::new(expected))
+    }
+
+    fn integer<T>(value: &Value) -> Result<T, JsonError>
+    where
+        T: TryFrom<i128>,
+    {
+        fn integral(value: f64) -> Option<i128> {
+            if value.is_finite() && value.fract() == 0.0 {
+                Some(value as i128)
+            } else {
+                None
+            }
+        }
+
+        let integer = match *value {
+            Value::Number(ref n) => n
+                .as_i64()
+                .map(i128::from)
+                .or_else(|| n.as_u64().map(i128::from))
+                .or_else(|| n.as_f64().and_then(integral)),
+            Value::String(ref s) => s
+                .parse::<i128>()
+                .ok()
+                .or_else(|| s.parse::<f64>().ok().and_then(integral)),
+            _ => return Err(JsonError::new("expected an integer")),
+        };
+        let integer = integer.ok_or_else(|| JsonError::new("invalid integer"))?;
+        T::try_from(integer).map_err(|_| JsonError::new("integer out of range"))
+    }
+
+    macro_rules! integers {
+        ($($name:ident: $ty:ty),*) => {
+            $(
+                pub fn $name(value: &Value) -> Result<$ty, JsonError> {
+                    integer(value)
+                }
+            )*
+        };
+    }
+
+    integers!(int32: i32, uint32: u32, sint32: i32, fixed32: u32, sfixed32: i32);
+    integers!(int64: i64, uint64: u64, sint64: i64, fixed64: u64, sfixed64: i64);
+
+    pub fn bool(value: &Value) -> Result<bool, JsonError> {
+        value
+            .as_bool()
+            .ok_or_else(|| JsonError::new("expected a boolean"))
+    }
+
+    pub fn double(value: &Value) -> Result<f64, JsonError> {
+        match *value {
+            Value::Number(ref n) => n.as_f64().ok_or_else(|| JsonError::new("invalid number")),
+            Value::String(ref s) => match s.as_str() {
+                "NaN" => Ok(f64::NAN),
+                "Infinity" => Ok(f64::INFINITY),
+                "-Infinity" => Ok(f64::NEG_INFINITY),
+                s => s.parse().map_err(|_| JsonError::new("invalid number")),
+            },
+            _ => Err(JsonError::new("expected a number")),
+        }
+    }
+
+    pub fn float(value: &Value) -> Result<f32, JsonError> {
+        let value = double(value)?;
+        if value.is_finite() && value.abs() > f64::from(f32::MAX) {
+            return Err(JsonError::new("float out of range"));
+        }
+        Ok(value as f32)
+    }
+
+    pub fn string<A>(value: &Value) -> Result<A, JsonError>
+    where
+        A: StringAdapter,
+    {
+        let s = str_value(value, "expected a string")?;
+        let mut string = A::default();
+        string
+            .replace_with(s.as_bytes())
+            .map_err(|error| JsonError::new(error.to_string()))?;
+        Ok(string)
+    }
+
+    pub fn bytes<A>(value: &Value) -> Result<A, JsonError>
+    where
+        A: BytesAdapter,
+    {
+        let s = str_value(value, "expected a base64 string")?;
+        let decoded = crate::base64::decode(s).ok_or_else(|| JsonError::new("invalid base64"))?;
+        let mut bytes = A::default();
+        bytes
+            .check_capacity(decoded.len())
+            .map_err(|error| JsonError::new(error.to_string()))?;
+        bytes.replace_with(&*decoded);
+        Ok(bytes)
+    }
+
+    /// Converts an enum value, which may be a variant name or a number.
+    pub fn enumeration<F>(value: &Value, from_str_name: F) -> Result<i32, JsonError>
+    where
+        F: FnOnce(&str) -> Option<i32>,
+    {
+        match *value {
+            Value::String(ref name) => {
+                from_str_name(name).ok_or_else(|| JsonError::new("unknown enum value name"))
+            }
+            _ => int32(value),
+        }
+    }
+
+    /// Converts a JSON object key to a `bool` map key.
+    pub fn bool_key(key: &Value) -> Result<bool, JsonError> {
+        match key.as_str() {
+            Some("true") => Ok(true),
+            Some("false") => Ok(false),
+            _ => Err(JsonError::new("invalid boolean map key")),
+        }
+    }
+
+    pub fn message<M>(value: &Value, options: FromJsonOptions) -> Result<M, JsonError>
+    where
+        M: FromJson + Default,
+    {
+        let mut msg = M::default();
+        msg.merge_json(value, options)?;
+        Ok(msg)
+    }
+
+    /// Converts the elements of a JSON array.
+    pub fn array<T, F>(value: &Value, mut convert: F) -> Result<Vec<T>, JsonError>
+    where
+        F: FnMut(&Value) -> Result<T, JsonError>,
+    {
+        let values = value
+            .as_array()
+            .ok_or_else(|| JsonError::new("expected an array"))?;
+        values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                convert(value).map_err(|error| error.push(PathSegment::Index(index)))
+            })
+            .collect()
+    }
+
+    /// Converts the entries of a JSON object to a map. The keys are passed to `convert_key` as
+    /// JSON strings.
+    pub fn map<K, V, C, KF, VF>(
+        value: &Value,
+        mut convert_key: KF,
+        mut convert_value: VF,
+    ) -> Result<C, JsonError>
+    where
+        C: FromIterator<(K, V)>,
+        KF: FnMut(&Value) -> Result<K, JsonError>,
+        VF: FnMut(&Value) -> Result<V, JsonError>,
+    {
+        object(value)?
+            .iter()
+            .map(|(key, value)| {
+                let entry = convert_key(&Value::String(key.clone()))
+                    .and_then(|key| convert_value(value).map(|value| (key, value)));
+                field(key, entry)
+            })
+            .collect()
+    }
+
+    /// Parses up to 9 fractional second digits to nanoseconds.
+    fn parse_nanos(fraction: &str) -> Option<i32> {
+        if fraction.len() > 9 {
+            return None;
+        }
+        let nanos = parse_digits(fraction)? as i32;
+        Some(nanos * 10i32.pow(9 - fraction.len() as u32))
+    }
+
+    fn parse_digits(digits: &str) -> Option<i64> {
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok()
+    }
+
+    /// Converts a `google.protobuf.Duration` string, e.g. `"-1.500s"`, to seconds and nanos.
+    pub fn duration(value: &Value) -> Result<(i64, i32), JsonError> {
+        let s = str_value(value, "expected a duration string")?;
+        parse_duration(s).ok_or_else(|| JsonError::new("invalid duration"))
+    }
+
+    fn parse_duration(s: &str) -> Option<(i64, i32)> {
+        let s = s.strip_suffix('s')?;
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+        let (seconds, nanos) = match s.find('.') {
+            Some(index) => (parse_digits(&s[..index])?, parse_nanos(&s[index + 1..])?),
+            None => (parse_digits(s)?, 0),
+        };
+        if seconds > MAX_DURATION_SECONDS {
+            return None;
+        }
+        if negative {
+            Some((-seconds, -nanos))
+        } else {
+            Some((seconds, nanos))
+        }
+    }
+
+    /// Converts a `google.protobuf.Timestamp` RFC 3339 date-time string, e.g.
+    /// `"1972-01-01T10:00:20.021Z"`, to seconds and nanos since the Unix epoch.
+    pub fn timestamp(value: &Value) -> Result<(i64, i32), JsonError> {
+        let s = str_value(value, "expected a timestamp string")?;
+        parse_timestamp(s).ok_or_else(|| JsonError::new("invalid timestamp"))
+    }
+
+    fn parse_timestamp(s: &str) -> Option<(i64, i32)> {
+        let b = s.as_bytes();
+        if !s.is_ascii()
+            || b.len() < 20
+            || b[4] != b'-'
+            || b[7] != b'-'
+            || !matches!(b[10], b'T' | b't')
+            || b[13] != b':'
+            || b[16] != b':'
+        {
+            return None;
+        }
+        let year = parse_digits(&s[0..4])?;
+        let month = parse_digits(&s[5..7])?;
+        let day = parse_digits(&s[8..10])?;
+        let hour = parse_digits(&s[11..13])?;
+        let minute = parse_digits(&s[14..16])?;
+        let second = parse_digits(&s[17..19])?;
+
+        let mut rest = &s[19..];
+        let mut nanos = 0;
+        if let Some(fraction) = rest.strip_prefix('.') {
+            let end = fraction
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(fraction.len());
+            nanos = parse_nanos(&fraction[..end])?;
+            rest = &fraction[end..];
+        }
+
+        let offset = match rest {
+            "Z" | "z" => 0,
+            _ => {
+                let b = rest.as_bytes();
+                if b.len() != 6 || b[3] != b':' {
+                    return None;
+                }
+                let offset = parse_digits(&rest[1..3])? * 3600 + parse_digits(&rest[4..6])? * 60;
+                match b[0] {
+                    b'+' => offset,
+                    b'-' => -offset,
+                    _ => return None,
+                }
+            }
+        };
+
+        let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year => 29,
+            2 => 28,
+            _ => return None,
+        };
+        if !(1..=days_in_month).contains(&day) || hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+
+        // Convert the civil date to a day number, using the algorithm from
+        // http://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let year_of_era = y - era * 400;
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146_097 + day_of_era - 719_468;
+
+        let seconds = days * 86_400 + hour * 3600 + minute * 60 + second - offset;
+        if !(MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&seconds) {
+            return None;
+        }
+        Some((seconds, nanos))
+    }
+
+    /// Converts a `google.protobuf.FieldMask` string of comma-separated `lowerCamelCase` paths to
+    /// `lower_snake_case` paths.
+    pub fn field_mask(value: &Value) -> Result<Vec<String>, JsonError> {
+        let s = str_value(value, "expected a field mask string")?;
+        Ok(s.split(',')
+            .filter(|path| !path.is_empty())
+            .map(proto_name)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode::*;
+    use super::*;
+
+    #[test]
+    fn scalars() {
+        assert_eq!(int32(&-1), Value::from(-1));
+        assert_eq!(int64(&-1), Value::from("-1"));
+        assert_eq!(uint64(&u64::MAX), Value::from("18446744073709551615"));
+        assert_eq!(float(&0.1), Value::from(0.1));
+        assert_eq!(double(&f64::NAN), Value::from("NaN"));
+        assert_eq!(float(&f32::INFINITY), Value::from("Infinity"));
+        assert_eq!(double(&f64::NEG_INFINITY), Value::from("-Infinity"));
+        assert_eq!(string(&String::from("foo")), Value::from("foo"));
+        assert_eq!(enumeration(1, Some("ONE")), Value::from("ONE"));
+        assert_eq!(enumeration(7, None), Value::from(7));
+        assert_eq!(map_key(bool(&true)), "true");
+        assert_eq!(map_key(int64(&5)), "5");
+    }
+
+    #[test]
+    fn base64_bytes() {
+        assert_eq!(bytes(&Vec::new()), Value::from(""));
+        assert_eq!(bytes(&b"f".to_vec()), Value::from("Zg=="));
+        assert_eq!(bytes(&b"fo".to_vec()), Value::from("Zm8="));
+        assert_eq!(bytes(&Bytes::from_static(b"foo")), Value::from("Zm9v"));
+        assert_eq!(bytes(&vec![0xFB, 0xFF]), Value::from("+/8="));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(duration(0, 0), Value::from("0s"));
+        assert_eq!(duration(1, 500_000_000), Value::from("1.500s"));
+        assert_eq!(duration(-1, -500_000_000), Value::from("-1.500s"));
+        assert_eq!(duration(0, -1_000), Value::from("-0.000001s"));
+        assert_eq!(duration(3, 1), Value::from("3.000000001s"));
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(timestamp(0, 0), Value::from("1970-01-01T00:00:00Z"));
+        assert_eq!(
+            timestamp(63_108_020, 21_000_000),
+            Value::from("1972-01-01T10:00:20.021Z")
+        );
+        assert_eq!(timestamp(-1, 0), Value::from("1969-12-31T23:59:59Z"));
+        assert_eq!(
+            timestamp(951_782_400, 0),
+            Value::from("2000-02-29T00:00:00Z")
+        );
+        assert_eq!(
+            timestamp(253_402_300_799, 999_999_999),
+            Value::from("9999-12-31T23:59:59.999999999Z")
+        );
+        assert_eq!(
+            timestamp(-62_135_596_800, 0),
+            Value::from("0001-01-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn wrappers() {
+        assert_eq!(5u64.to_json(), Value::from("5"));
+        assert_eq!(String::from("foo").to_json(), Value::from("foo"));
+        assert_eq!(Box::new(true).to_json(), Value::from(true));
+        assert_eq!(().to_json(), Value::Object(Map::new()));
+    }
+
+    #[test]
+    fn field_masks() {
+        let paths = vec!["user.display_name".to_string(), "photo".to_string()];
+        assert_eq!(field_mask(&paths), Value::from("user.displayName,photo"));
+        assert_eq!(json_name("foo_bar_baz"), "fooBarBaz");
+    }
+
+    #[test]
+    fn decode_scalars() {
+        assert_eq!(decode::int32(&Value::from(-1)), Ok(-1));
+        assert_eq!(decode::int32(&Value::from("-1")), Ok(-1));
+        assert_eq!(decode::int32(&Value::from(1e3)), Ok(1000));
+        assert_eq!(
+            decode::int64(&Value::from("-9007199254740993")),
+            Ok(-9_007_199_254_740_993)
+        );
+        assert_eq!(decode::uint64(&Value::from(u64::MAX)), Ok(u64::MAX));
+        assert_eq!(decode::uint64(&Value::from("1e2")), Ok(100));
+        let error = decode::uint32(&Value::from(-1)).unwrap_err();
+        assert!(error.to_string().contains("out of range"));
+        let error = decode::int32(&Value::from(1.5)).unwrap_err();
+        assert!(error.to_string().contains("invalid integer"));
+        let error = decode::int32(&Value::Bool(true)).unwrap_err();
+        assert!(error.to_string().contains("expected an integer"));
+        assert_eq!(decode::bool(&Value::from(true)), Ok(true));
+        decode::bool(&Value::from("true")).unwrap_err();
+        assert_eq!(decode::double(&Value::from("1.5")), Ok(1.5));
+        assert!(decode::double(&Value::from("NaN")).unwrap().is_nan());
+        assert_eq!(
+            decode::float(&Value::from("-Infinity")),
+            Ok(f32::NEG_INFINITY)
+        );
+        decode::float(&Value::from(1e300)).unwrap_err();
+        assert_eq!(
+            decode::string::<String>(&Value::from("foo")),
+            Ok("foo".to_string())
+        );
+        assert_eq!(decode::enumeration(&Value::from(7), |_| None), Ok(7));
+        assert_eq!(decode::enumeration(&Value::from("ONE"), |_| Some(1)), Ok(1));
+        decode::enumeration(&Value::from("TWO"), |_| None).unwrap_err();
+        assert_eq!(decode::bool_key(&Value::from("false")), Ok(false));
+    }
+
+    #[test]
+    fn decode_base64_bytes() {
+        assert_eq!(decode::bytes::<Vec<u8>>(&Value::from("")), Ok(Vec::new()));
+        assert_eq!(
+            decode::bytes::<Vec<u8>>(&Value::from("Zg==")),
+            Ok(b"f".to_vec())
+        );
+        assert_eq!(
+            decode::bytes::<Vec<u8>>(&Value::from("Zm8")),
+            Ok(b"fo".to_vec())
+        );
+        assert_eq!(
+            decode::bytes::<Bytes>(&Value::from("Zm9v")),
+            Ok(Bytes::from_static(b"foo"))
+        );
+        assert_eq!(
+            decode::bytes::<Vec<u8>>(&Value::from("+/8=")),
+            Ok(vec![0xFB, 0xFF])
+        );
+        assert_eq!(
+            decode::bytes::<Vec<u8>>(&Value::from("-_8")),
+            Ok(vec![0xFB, 0xFF])
+        );
+        decode::bytes::<Vec<u8>>(&Value::from("Z")).unwrap_err();
+        decode::bytes::<Vec<u8>>(&Value::from("Zm9v!")).unwrap_err();
+    }
+
+    #[test]
+    fn decode_durations() {
+        assert_eq!(decode::duration(&Value::from("0s")), Ok((0, 0)));
+        assert_eq!(decode::duration(&Value::from("1.5s")), Ok((1, 500_000_000)));
+        assert_eq!(
+            decode::duration(&Value::from("-1.500s")),
+            Ok((-1, -500_000_000))
+        );
+        assert_eq!(
+            decode::duration(&Value::from("-0.000001s")),
+            Ok((0, -1_000))
+        );
+        assert_eq!(decode::duration(&Value::from("3.000000001s")), Ok((3, 1)));
+        for invalid in &["", "1", "s", "1.s", "+1s", "1.0000000001s", "315576000001s"] {
+            decode::duration(&Value::from(*invalid)).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn decode_timestamps() {
+        let timestamp = |s: &str| decode::timestamp(&Value::from(s));
+        assert_eq!(timestamp("1970-01-01T00:00:00Z"), Ok((0, 0)));
+        assert_eq!(
+            timestamp("1972-01-01T10:00:20.021Z"),
+            Ok((63_108_020, 21_000_000))
+        );
+        assert_eq!(
+            timestamp("1972-01-01T11:00:20.021+01:00"),
+            Ok((63_108_020, 21_000_000))
+        );
+        assert_eq!(timestamp("1969-12-31T23:59:59Z"), Ok((-1, 0)));
+        assert_eq!(timestamp("2000-02-29T00:00:00Z"), Ok((951_782_400, 0)));
+        assert_eq!(
+            timestamp("9999-12-31T23:59:59.999999999Z"),
+            Ok((253_402_300_799, 999_999_999))
+        );
+        assert_eq!(timestamp("0001-01-01T00:00:00Z"), Ok((-62_135_596_800, 0)));
+        for invalid in &[
+            "1970-01-01",
+            "1970-01-01T00:00:00",
+            "1970-13-01T00:00:00Z",
+            "1900-02-29T00:00:00Z",
+            "1970-01-01T24:00:00Z",
+            "1970-01-01T00:00:00.Z",
+            "0001-01-01T00:00:00+00:01",
+        ] {
+            timestamp(*invalid).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn decode_wrappers() {
+        let options = FromJsonOptions::new();
+        let mut value = 0u64;
+        value.merge_json(&Value::from("5"), options).unwrap();
+        assert_eq!(value, 5);
+        let mut value = Box::new(false);
+        value.merge_json(&Value::from(true), options).unwrap();
+        assert!(*value);
+
+        let empty = serde_json::json!({ "foo": 1 });
+        ().merge_json(&empty, options).unwrap();
+        let error = ().merge_json(&empty, options.deny_unknown_fields(true)).unwrap_err();
+        assert_eq!(error.path(), "$.foo");
+    }
+
+    #[test]
+    fn decode_field_masks() {
+        assert_eq!(
+            decode::field_mask(&Value::from("user.displayName,photo")),
+            Ok(vec!["user.display_name".to_string(), "photo".to_string()])
+        );
+        assert_eq!(decode::field_mask(&Value::from("")), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn error_paths() {
+        let value = serde_json::json!({ "items": [1, "x"] });
+        let error = decode::object(&value)
+            .and_then(|fields| {
+                let items = decode::array(&fields["items"], decode::int32);
+                decode::field("items", items)
+            })
+            .unwrap_err();
+        assert_eq!(error.path(), "$.items[1]");
+        assert_eq!(
+            error.to_string(),
+            "failed to convert JSON to Protobuf message at $.items[1]: invalid integer"
+        );
+    }
+}
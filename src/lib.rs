@@ -2,27 +2,94 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
 // Re-export the alloc crate for use within derived code.
+#[cfg(feature = "alloc")]
 #[doc(hidden)]
 pub extern crate alloc;
 
 // Re-export the bytes crate for use within derived code.
+#[cfg(feature = "alloc")]
 #[doc(hidden)]
 pub use bytes;
 
+#[cfg(all(feature = "alloc", any(feature = "json", feature = "serde")))]
+mod base64;
+#[cfg(feature = "alloc")]
+pub mod builder;
+#[cfg(all(feature = "alloc", feature = "serde"))]
+pub mod bytes_serde;
+#[cfg(feature = "alloc")]
+pub mod cache;
+#[cfg(feature = "codec")]
+pub mod codec;
+#[cfg(feature = "descriptors")]
+pub mod descriptor;
+#[cfg(feature = "alloc")]
 mod error;
+#[cfg(feature = "std")]
+pub mod interner;
+#[cfg(feature = "std")]
+pub mod io;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "alloc")]
 mod message;
+#[cfg(feature = "alloc")]
+mod name;
+#[cfg(feature = "alloc")]
+mod oneof;
+#[cfg(feature = "reflect")]
+pub mod reflect;
+#[cfg(feature = "alloc")]
+pub mod stream;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+#[cfg(feature = "text-format")]
+pub mod text_format;
+#[cfg(feature = "alloc")]
 mod types;
+#[cfg(feature = "alloc")]
+mod unknown;
+pub mod wire;
 
+#[cfg(feature = "alloc")]
 #[doc(hidden)]
 pub mod encoding;
 
+#[cfg(feature = "arrayvec")]
+pub use arrayvec;
+#[cfg(feature = "alloc")]
 pub use bytestring;
-pub use crate::error::{DecodeError, EncodeError};
+#[cfg(feature = "heapless")]
+pub use heapless;
+#[cfg(feature = "indexmap")]
+pub use indexmap;
+#[cfg(feature = "smallvec")]
+pub use smallvec;
+#[cfg(feature = "smol_str")]
+pub use smol_str;
+#[cfg(feature = "alloc")]
+pub use crate::encoding::{
+    decode_key, encode_key, encoded_len_varint, is_valid_tag, key_len, skip_field, RESERVED_TAGS,
+};
+#[cfg(feature = "alloc")]
+pub use crate::error::{
+    DecodeError, DecodeErrorKind, EncodeError, MissingFieldsError, UnknownEnumValue,
+};
+#[cfg(feature = "alloc")]
 pub use crate::message::Message;
+#[cfg(feature = "alloc")]
+pub use crate::name::Name;
+#[cfg(feature = "alloc")]
+pub use crate::oneof::Oneof;
+#[cfg(feature = "alloc")]
+pub use crate::unknown::UnknownFields;
+pub use crate::wire::{WireType, MAX_TAG, MIN_TAG};
 
+#[cfg(feature = "alloc")]
 use bytes::{Buf, BufMut};
 
-use crate::encoding::{decode_varint, encode_varint, encoded_len_varint};
+#[cfg(feature = "alloc")]
+use crate::encoding::{decode_varint, encode_varint};
 
 // See `encoding::DecodeContext` for more info.
 // 100 is the default recursion limit in the C++ implementation.
@@ -35,6 +102,7 @@ const RECURSION_LIMIT: u32 = 100;
 ///
 /// An error will be returned if the buffer does not have sufficient capacity to encode the
 /// delimiter.
+#[cfg(feature = "alloc")]
 pub fn encode_length_delimiter<B>(length: usize, buf: &mut B) -> Result<(), EncodeError>
 where
     B: BufMut,
@@ -53,6 +121,7 @@ where
 ///
 /// Applications may use this method to ensure sufficient buffer capacity before calling
 /// `encode_length_delimiter`. The returned size will be between 1 and 10, inclusive.
+#[cfg(feature = "alloc")]
 pub fn length_delimiter_len(length: usize) -> usize {
     encoded_len_varint(length as u64)
 }
@@ -68,13 +137,15 @@ pub fn length_delimiter_len(length: usize) -> usize {
 ///    input is required to decode the full delimiter.
 ///  * If the supplied buffer contains more than 10 bytes, then the buffer contains an invalid
 ///    delimiter, and typically the buffer should be considered corrupt.
+#[cfg(feature = "alloc")]
 pub fn decode_length_delimiter<B>(mut buf: B) -> Result<usize, DecodeError>
 where
     B: Buf,
 {
     let length = decode_varint(&mut buf)?;
     if length > usize::max_value() as u64 {
-        return Err(DecodeError::new(
+        return Err(DecodeError::with_kind(
+            DecodeErrorKind::InvalidLength,
             "length delimiter exceeds maximum usize value",
         ));
     }
@@ -1,32 +1,103 @@
 use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::fmt::Debug;
 use core::usize;
 
 use bytes::{Buf, BufMut};
 
 use crate::encoding::{
-    decode_key, encode_varint, encoded_len_varint, message, DecodeContext, WireType,
+    encode_varint, encoded_len_varint, message, DecodeContext, EncodeOptions, WireType,
 };
 use crate::DecodeError;
 use crate::EncodeError;
+use crate::MissingFieldsError;
 
 /// A Protocol Buffers message.
 pub trait Message: Debug + Send + Sync {
-    /// Encodes the message to a buffer.
+    /// Encodes the fields of the message to a buffer, without a length delimiter.
+    ///
+    /// This is the core of encoding: `encode`, `encode_to_vec` and the encoding of the message as a
+    /// field of another message all check or allocate capacity, write a length delimiter if one
+    /// is needed, and then delegate to it. A hand-written implementation of `Message`, for
+    /// instance one which writes a field faster than the generic encoders, can therefore be used
+    /// anywhere a derived one can.
+    ///
+    /// Implementations must write each field's key and value, ordered by tag, and exactly
+    /// `encoded_len` bytes in total. Fields with default values are usually omitted, and fields
+    /// written here must be parsed by `merge_field`.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+        Self: Sized;
+
+    /// Encodes the message to a buffer, emitting map entries in key order.
     ///
     /// This method will panic if the buffer has insufficient capacity.
     ///
+    /// The default implementation delegates to `encode_raw`, which is already deterministic for
+    /// messages without map fields.
+    ///
     /// Meant to be used only by `Message` implementations.
     #[doc(hidden)]
-    fn encode_raw<B>(&self, buf: &mut B)
+    fn encode_raw_deterministic<B>(&self, buf: &mut B)
     where
         B: BufMut,
-        Self: Sized;
+        Self: Sized,
+    {
+        self.encode_raw(buf)
+    }
 
-    /// Decodes a field from a buffer, and merges it into `self`.
+    /// Encodes the message to a buffer following `options`.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    ///
+    /// The default implementation delegates to `encode_raw`, ignoring the options.
     ///
     /// Meant to be used only by `Message` implementations.
     #[doc(hidden)]
+    fn encode_raw_with_options<B>(&self, buf: &mut B, _options: &EncodeOptions)
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        self.encode_raw(buf)
+    }
+
+    /// Returns the encoded length of the message following `options`.
+    ///
+    /// The default implementation delegates to `encoded_len`, ignoring the options.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn encoded_len_with_options(&self, _options: &EncodeOptions) -> usize {
+        self.encoded_len()
+    }
+
+    /// Appends the paths of the `required` fields which are missing from `buf`, an encoding of
+    /// the message, to `missing`. `path` is prepended to each field name.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    fn missing_required_fields(_buf: &[u8], _path: &str, _missing: &mut Vec<String>)
+    where
+        Self: Sized,
+    {
+    }
+
+    /// Decodes a field from a buffer, and merges it into `self`.
+    ///
+    /// This is the core of decoding: `merge_with_context`, and the decoding of the message as a
+    /// field of another message, decode each field key and then delegate to it with the rest of
+    /// the field in `buf`. A hand-written implementation can therefore wrap another message, and
+    /// intercept some tags before delegating the others to its `merge_field`.
+    ///
+    /// Implementations must consume exactly the value of the field from `buf`, and merge it
+    /// following the Protobuf rules: the last value wins for singular scalar fields, and repeated
+    /// fields are appended to. Fields with unknown tags must be skipped with
+    /// `prost::encoding::skip_field`, or stored, rather than rejected.
     fn merge_field<B>(
         &mut self,
         tag: u32,
@@ -41,9 +112,33 @@ pub trait Message: Debug + Send + Sync {
     /// Returns the encoded length of the message without a length delimiter.
     fn encoded_len(&self) -> usize;
 
+    /// Returns `true` if no field of the message would be encoded, i.e. if `encoded_len` is 0.
+    ///
+    /// This is cheaper than checking the encoding: derived messages compare each field with its
+    /// default in tag order, and return `false` at the first field which would be written. A
+    /// message with a `required` field is never default, since required fields are always
+    /// written. The default implementation checks `encoded_len`.
+    fn is_default(&self) -> bool {
+        self.encoded_len() == 0
+    }
+
+    /// Returns the encoded length of the message with a length delimiter, as written by
+    /// `encode_length_delimited`.
+    ///
+    /// This can be used to size a buffer holding several length-delimited messages.
+    fn encoded_len_length_delimited(&self) -> usize {
+        let len = self.encoded_len();
+        encoded_len_varint(len as u64) + len
+    }
+
     /// Encodes the message to a buffer.
     ///
     /// An error will be returned if the buffer does not have sufficient capacity.
+    ///
+    /// Fields are written with many small `BufMut` writes, and `BufMut` has no way to reserve
+    /// capacity, so a growable buffer like `Vec<u8>` or `BytesMut` may reallocate several times for
+    /// a large message. To grow it once, reserve `encoded_len` bytes before encoding, or use
+    /// `encode_to_vec`, which allocates exactly that much.
     fn encode<B>(&self, buf: &mut B) -> Result<(), EncodeError>
     where
         B: BufMut,
@@ -59,6 +154,90 @@ pub trait Message: Debug + Send + Sync {
         Ok(())
     }
 
+    /// Encodes the message to a newly allocated buffer.
+    fn encode_to_vec(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+
+        self.encode_raw(&mut buf);
+        buf
+    }
+
+    /// Encodes the message to a buffer, emitting map entries in key order.
+    ///
+    /// Protobuf maps are unordered, so `encode` writes `HashMap` entries in whatever order the map
+    /// iterates them. This method instead produces the same bytes for equal messages on every run,
+    /// which is useful for hashing, signing, or content-addressed storage.
+    ///
+    /// An error will be returned if the buffer does not have sufficient capacity.
+    fn encode_deterministic<B>(&self, buf: &mut B) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        let required = self.encoded_len();
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+
+        self.encode_raw_deterministic(buf);
+        Ok(())
+    }
+
+    /// Encodes the message to a newly allocated buffer, emitting map entries in key order.
+    ///
+    /// See `encode_deterministic`. Only `HashMap` and `IndexMap` entries are sorted, since
+    /// `BTreeMap` entries are already iterated in key order, and `encode_to_vec` remains the
+    /// faster choice when the output doesn't need to be canonical.
+    fn encode_deterministic_to_vec(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+
+        self.encode_raw_deterministic(&mut buf);
+        buf
+    }
+
+    /// Encodes the message to a buffer following `options`.
+    ///
+    /// With the default options this is equivalent to `encode`. See `EncodeOptions` for the
+    /// available options.
+    ///
+    /// An error will be returned if the buffer does not have sufficient capacity.
+    fn encode_with_options<B>(
+        &self,
+        buf: &mut B,
+        options: &EncodeOptions,
+    ) -> Result<(), EncodeError>
+    where
+        B: BufMut,
+        Self: Sized,
+    {
+        let required = self.encoded_len_with_options(options);
+        let remaining = buf.remaining_mut();
+        if required > remaining {
+            return Err(EncodeError::new(required, remaining));
+        }
+
+        self.encode_raw_with_options(buf, options);
+        Ok(())
+    }
+
+    /// Encodes the message to a newly allocated buffer following `options`.
+    fn encode_to_vec_with_options(&self, options: &EncodeOptions) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let mut buf = Vec::with_capacity(self.encoded_len_with_options(options));
+
+        self.encode_raw_with_options(&mut buf, options);
+        buf
+    }
+
     /// Encodes the message with a length-delimiter to a buffer.
     ///
     /// An error will be returned if the buffer does not have sufficient capacity.
@@ -78,6 +257,56 @@ pub trait Message: Debug + Send + Sync {
         Ok(())
     }
 
+    /// Encodes the message with a length-delimiter to a newly allocated buffer.
+    fn encode_length_delimited_to_vec(&self) -> Vec<u8>
+    where
+        Self: Sized,
+    {
+        let len = self.encoded_len();
+        let mut buf = Vec::with_capacity(len + encoded_len_varint(len as u64));
+
+        encode_varint(len as u64, &mut buf);
+        self.encode_raw(&mut buf);
+        buf
+    }
+
+    /// Encodes the message with a length-delimiter directly to a writer.
+    ///
+    /// See `prost::io::encode_length_delimited` for details.
+    #[cfg(feature = "std")]
+    fn encode_length_delimited_to_writer<W>(&self, writer: W) -> Result<(), EncodeError>
+    where
+        W: std::io::Write,
+        Self: Sized,
+    {
+        crate::io::encode_length_delimited(self, writer)
+    }
+
+    /// Encodes the message without a length-delimiter to a writer, streaming it out in chunks of
+    /// `buf_size` bytes as it is encoded.
+    ///
+    /// See `prost::io::encode_buffered` for details.
+    #[cfg(feature = "std")]
+    fn encode_to_writer_buffered<W>(&self, writer: W, buf_size: usize) -> Result<(), EncodeError>
+    where
+        W: std::io::Write,
+        Self: Sized,
+    {
+        crate::io::encode_buffered(self, writer, buf_size)
+    }
+
+    /// Converts the message into a reader over its encoding, without a length-delimiter.
+    ///
+    /// The reader's `encoded_len` is available before anything is read. See
+    /// `prost::io::MessageReader` for details.
+    #[cfg(feature = "std")]
+    fn into_reader(self) -> crate::io::MessageReader<Self>
+    where
+        Self: Sized,
+    {
+        crate::io::MessageReader::new(self)
+    }
+
     /// Decodes an instance of the message from a buffer.
     ///
     /// The entire buffer will be consumed.
@@ -90,6 +319,80 @@ pub trait Message: Debug + Send + Sync {
         Self::merge(&mut message, &mut buf).map(|_| message)
     }
 
+    /// Decodes an instance of the message from a byte slice.
+    ///
+    /// The entire slice is decoded as the message. Protobuf messages are not terminated, so any
+    /// bytes following the intended message are decoded as further fields of it: data which isn't
+    /// a valid field results in an error, but valid fields are merged into the message. Use a
+    /// length delimiter, as with `decode_length_delimited`, to separate a message from the data
+    /// following it.
+    ///
+    /// Unlike passing a `&mut &[u8]` cursor to `decode`, this doesn't advance the caller's slice.
+    fn decode_slice(data: &[u8]) -> Result<Self, DecodeError>
+    where
+        Self: Default,
+    {
+        Self::decode(data)
+    }
+
+    /// Decodes an instance of the message from a buffer, and checks that its proto2 `required`
+    /// fields, and those of the messages nested in it, are present.
+    ///
+    /// Missing required fields are decoded as their default values by `decode`, since presence
+    /// isn't tracked for them, so the encoding is checked with `validate_encoding`. The entire
+    /// buffer will be consumed. It is checked in place if it is contiguous, like a `&[u8]` or a
+    /// `Bytes`, and copied otherwise. Missing fields result in an error of kind
+    /// `MissingRequiredField`, whose source is a `MissingFieldsError`.
+    fn decode_validated<B>(mut buf: B) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        if buf.chunk().len() == buf.remaining() {
+            let validated = Self::validate_encoding(buf.chunk());
+            let message = Self::decode(buf)?;
+            validated?;
+            return Ok(message);
+        }
+        let buf = buf.copy_to_bytes(buf.remaining());
+        let message = Self::decode(buf.clone())?;
+        Self::validate_encoding(&buf)?;
+        Ok(message)
+    }
+
+    /// Checks that the proto2 `required` fields of the message encoded in `buf`, and those of
+    /// the messages nested in it, are present.
+    ///
+    /// Presence is checked on the encoding because required fields are stored without an
+    /// `Option`, so in a decoded message a missing field can't be told apart from one set to its
+    /// default value. The occurrences of each field are followed the way decoding merges them, so
+    /// every element of a repeated field is checked against its own encoding.
+    fn validate_encoding(buf: &[u8]) -> Result<(), MissingFieldsError>
+    where
+        Self: Sized,
+    {
+        let mut missing = Vec::new();
+        Self::missing_required_fields(buf, "", &mut missing);
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MissingFieldsError::new(missing))
+        }
+    }
+
+    /// Decodes an instance of the message from a buffer, using `ctx` to limit recursion and the
+    /// size of decoded repeated fields and maps.
+    ///
+    /// The entire buffer will be consumed.
+    fn decode_with_context<B>(mut buf: B, ctx: DecodeContext) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let mut message = Self::default();
+        Self::merge_with_context(&mut message, &mut buf, ctx).map(|_| message)
+    }
+
     /// Decodes a length-delimited instance of the message from the buffer.
     fn decode_length_delimited<B>(buf: B) -> Result<Self, DecodeError>
     where
@@ -101,6 +404,37 @@ pub trait Message: Debug + Send + Sync {
         Ok(message)
     }
 
+    /// Decodes a length-delimited instance of the message from the buffer, and checks that no
+    /// data follows it.
+    ///
+    /// `decode_length_delimited` leaves any data after the message in the buffer, which hides a
+    /// length delimiter that is shorter than the message it was written for, since the message is
+    /// then silently truncated. This returns an error of kind `TrailingData` instead.
+    fn decode_length_delimited_exact<B>(mut buf: B) -> Result<Self, DecodeError>
+    where
+        B: Buf,
+        Self: Default,
+    {
+        let message = Self::decode_length_delimited(&mut buf)?;
+        if buf.has_remaining() {
+            return Err(DecodeError::trailing_data(buf.remaining()));
+        }
+        Ok(message)
+    }
+
+    /// Decodes an instance of the message of `len` bytes from a reader, and merges it into
+    /// `self`.
+    ///
+    /// See `prost::io::merge_from_reader` for details.
+    #[cfg(feature = "std")]
+    fn merge_from_reader<R>(&mut self, reader: R, len: usize) -> Result<(), DecodeError>
+    where
+        R: std::io::Read,
+        Self: Sized,
+    {
+        crate::io::merge_from_reader(self, reader, len)
+    }
+
     /// Decodes an instance of the message from a buffer, and merges it into `self`.
     ///
     /// The entire buffer will be consumed.
@@ -109,9 +443,20 @@ pub trait Message: Debug + Send + Sync {
         B: Buf,
         Self: Sized,
     {
-        let ctx = DecodeContext::default();
+        self.merge_with_context(&mut buf, DecodeContext::default())
+    }
+
+    /// Decodes an instance of the message from a buffer, and merges it into `self`, using `ctx`
+    /// to limit recursion.
+    ///
+    /// The entire buffer will be consumed.
+    fn merge_with_context<B>(&mut self, mut buf: B, ctx: DecodeContext) -> Result<(), DecodeError>
+    where
+        B: Buf,
+        Self: Sized,
+    {
         while buf.has_remaining() {
-            let (tag, wire_type) = decode_key(&mut buf)?;
+            let (tag, wire_type) = ctx.decode_key(&mut buf)?;
             self.merge_field(tag, wire_type, &mut buf, ctx.clone())?;
         }
         Ok(())
@@ -133,6 +478,25 @@ pub trait Message: Debug + Send + Sync {
     }
 
     /// Clears the message, resetting all fields to their default.
+    ///
+    /// Unlike `*self = Default::default()`, this retains the capacity allocated by `String`,
+    /// `Vec`, map and unknown field storage, so that a message can be reused to decode many
+    /// inputs without re-allocating:
+    ///
+    /// ```
+    /// use prost::Message;
+    ///
+    /// let mut value = String::new();
+    /// value.merge(&b"\n\x03foo"[..]).unwrap();
+    /// let capacity = value.capacity();
+    ///
+    /// Message::clear(&mut value);
+    /// assert!(value.is_empty());
+    /// assert_eq!(value.capacity(), capacity);
+    ///
+    /// value.merge(&b"\n\x03bar"[..]).unwrap();
+    /// assert_eq!(value, "bar");
+    /// ```
     fn clear(&mut self);
 }
 
@@ -146,6 +510,24 @@ where
     {
         (**self).encode_raw(buf)
     }
+    fn encode_raw_deterministic<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        (**self).encode_raw_deterministic(buf)
+    }
+    fn encode_raw_with_options<B>(&self, buf: &mut B, options: &EncodeOptions)
+    where
+        B: BufMut,
+    {
+        (**self).encode_raw_with_options(buf, options)
+    }
+    fn encoded_len_with_options(&self, options: &EncodeOptions) -> usize {
+        (**self).encoded_len_with_options(options)
+    }
+    fn missing_required_fields(buf: &[u8], path: &str, missing: &mut Vec<String>) {
+        M::missing_required_fields(buf, path, missing)
+    }
     fn merge_field<B>(
         &mut self,
         tag: u32,
@@ -161,6 +543,9 @@ where
     fn encoded_len(&self) -> usize {
         (**self).encoded_len()
     }
+    fn is_default(&self) -> bool {
+        (**self).is_default()
+    }
     fn clear(&mut self) {
         (**self).clear()
     }
@@ -171,4 +556,56 @@ mod tests {
     use super::*;
 
     const _MESSAGE_IS_OBJECT_SAFE: Option<&dyn Message> = None;
+
+    #[test]
+    fn encode_to_vec() {
+        let msg = alloc::string::String::from("foo");
+
+        let mut buf = Vec::new();
+        msg.encode(&mut buf).unwrap();
+        assert_eq!(msg.encode_to_vec(), buf);
+
+        let mut buf = Vec::new();
+        msg.encode_length_delimited(&mut buf).unwrap();
+        assert_eq!(msg.encode_length_delimited_to_vec(), buf);
+        assert_eq!(msg.encoded_len_length_delimited(), buf.len());
+    }
+
+    #[test]
+    fn decode_slice() {
+        let data = b"\n\x03foo";
+        assert_eq!(alloc::string::String::decode_slice(data).unwrap(), "foo");
+
+        // Trailing garbage is decoded as part of the message.
+        let error = alloc::string::String::decode_slice(b"\n\x03foo\xff").unwrap_err();
+        assert_eq!(error.kind(), crate::DecodeErrorKind::InvalidVarint);
+    }
+
+    #[test]
+    fn decode_length_delimited_exact() {
+        let buf = b"\x05\n\x03foo";
+        assert_eq!(
+            alloc::string::String::decode_length_delimited_exact(&buf[..]).unwrap(),
+            "foo"
+        );
+
+        // A length delimiter which is too short truncates the message.
+        let buf = b"\x02\n\x00foo";
+        assert_eq!(
+            alloc::string::String::decode_length_delimited(&buf[..]).unwrap(),
+            ""
+        );
+        let error = alloc::string::String::decode_length_delimited_exact(&buf[..]).unwrap_err();
+        assert_eq!(error.kind(), crate::DecodeErrorKind::TrailingData);
+    }
+
+    #[test]
+    fn encoded_len_length_delimited() {
+        // 127 bytes of string data and the key make a 129 byte message, which needs a 2 byte
+        // length delimiter.
+        let msg = "x".repeat(127);
+        assert_eq!(msg.encoded_len(), 129);
+        assert_eq!(msg.encoded_len_length_delimited(), 131);
+        assert_eq!(msg.encode_length_delimited_to_vec().len(), 131);
+    }
 }
@@ -0,0 +1,37 @@
+//! The fully-qualified names of Protobuf message types.
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::Message;
+
+/// A message type with a known fully-qualified Protobuf name.
+///
+/// The name identifies the type of a message packed in a `google.protobuf.Any`. Generated messages
+/// implement this trait when built with `prost_build::Config::enable_type_names`.
+pub trait Name: Message {
+    /// The name of the message type, without the package. The names of nested types include the
+    /// names of the enclosing messages, separated by `.`, for instance `Outer.Inner`.
+    const NAME: &'static str;
+
+    /// The Protobuf package of the message type, for instance `google.protobuf`.
+    const PACKAGE: &'static str;
+
+    /// Returns the fully-qualified name of the message type, for instance
+    /// `google.protobuf.Duration`.
+    fn full_name() -> String {
+        if Self::PACKAGE.is_empty() {
+            Self::NAME.into()
+        } else {
+            format!("{}.{}", Self::PACKAGE, Self::NAME)
+        }
+    }
+
+    /// Returns the type URL used to identify the message type in a `google.protobuf.Any`.
+    ///
+    /// The default implementation uses the `type.googleapis.com/` prefix, like the other Protobuf
+    /// implementations.
+    fn type_url() -> String {
+        format!("type.googleapis.com/{}", Self::full_name())
+    }
+}
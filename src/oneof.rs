@@ -0,0 +1,45 @@
+use core::fmt::Debug;
+
+use bytes::{Buf, BufMut};
+
+use crate::encoding::{DecodeContext, WireType};
+use crate::DecodeError;
+
+/// A Protocol Buffers oneof field, a set of fields of which at most one is set.
+///
+/// Implemented by `#[derive(Oneof)]` enums, with a variant per field. Messages hold the oneof as
+/// an `Option`, which is `None` if no field is set. The trait allows generic code to encode and
+/// decode any oneof without depending on the shape of the generated enum.
+pub trait Oneof: Debug + Sized {
+    /// Encodes the set field, including its key, to a buffer.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    fn encode<B>(&self, buf: &mut B)
+    where
+        B: BufMut;
+
+    /// Encodes the set field, including its key, to a buffer, emitting map entries in key order.
+    ///
+    /// This method will panic if the buffer has insufficient capacity.
+    fn encode_deterministic<B>(&self, buf: &mut B)
+    where
+        B: BufMut;
+
+    /// Decodes the value of the field with the given tag from a buffer, and merges it into
+    /// `field`.
+    ///
+    /// If `field` holds a different variant, it is replaced. This method will panic if `tag` is
+    /// not the tag of one of the oneof's fields.
+    fn merge<B>(
+        field: &mut Option<Self>,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf;
+
+    /// Returns the encoded length of the set field, including its key.
+    fn encoded_len(&self) -> usize;
+}
@@ -0,0 +1,443 @@
+//! Dynamic access to the fields of messages by tag.
+//!
+//! Derived messages marked `#[prost(reflect)]` implement `Reflect`, which requires their nested
+//! messages and oneofs to be marked as well. Fields are read and written as `Value`s, and the
+//! `FieldDescriptor`s of a message describe which fields it has, which allows generic code like
+//! configuration loaders to fill in messages of any type.
+//!
+//! ```
+//! use prost::reflect::{Reflect, Value};
+//!
+//! #[derive(Clone, PartialEq, prost::Message)]
+//! #[prost(reflect)]
+//! struct Config {
+//!     #[prost(string, tag = "1")]
+//!     name: String,
+//!     #[prost(uint32, repeated, tag = "2")]
+//!     ports: Vec<u32>,
+//! }
+//!
+//! let mut config = Config::default();
+//! config.set_field(1, Value::String("server".into())).unwrap();
+//! config
+//!     .set_field(2, Value::List(vec![Value::U32(80), Value::U32(443)]))
+//!     .unwrap();
+//! assert_eq!(config.ports, [80, 443]);
+//! assert!(matches!(config.get_field(1), Some(Value::String(name)) if name == "server"));
+//! assert!(config.set_field(1, Value::U32(1)).is_err());
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+use crate::descriptor::FieldDescriptor;
+use crate::encoding::sealed::RepeatedContainer as _;
+use crate::encoding::string::StringAdapter;
+use crate::encoding::{BytesAdapter, DecodeContext, RepeatedContainer};
+use crate::Message;
+
+/// A message whose fields can be read and written by tag.
+///
+/// Unlike `Message`, this trait is object safe, so nested messages are represented as
+/// `Box<dyn Reflect>`.
+pub trait Reflect: fmt::Debug + Send + Sync {
+    /// Returns the descriptors of the fields of the message.
+    fn field_descriptors(&self) -> &'static [FieldDescriptor];
+
+    /// Returns the value of the field with the given tag.
+    ///
+    /// Returns `None` if the message has no such field, or if it is an optional field or a oneof
+    /// variant which is not set. Repeated fields are returned as a `Value::List`, and maps as a
+    /// `Value::Map`, even if they are empty.
+    fn get_field(&self, tag: u32) -> Option<Value>;
+
+    /// Sets the field with the given tag to `value`.
+    ///
+    /// The value must have the type returned by `get_field` for the field, otherwise an error
+    /// is returned and the field is left unchanged. Setting a oneof variant replaces the
+    /// variant which was set before.
+    fn set_field(&mut self, tag: u32, value: Value) -> Result<(), ReflectError>;
+
+    /// Returns the Rust type name of the message.
+    fn type_name(&self) -> &'static str;
+
+    /// Converts the boxed message into `Any`, so that it can be downcast to its concrete type.
+    #[doc(hidden)]
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+
+    /// Replaces the message with `value` if it has the same type, or returns `value` otherwise.
+    #[doc(hidden)]
+    fn assign_any(&mut self, value: Box<dyn Any>) -> Result<(), Box<dyn Any>>;
+}
+
+/// Boxed messages, which generated code uses for recursive message fields, are reflected as the
+/// inner message.
+impl<M> Reflect for Box<M>
+where
+    M: Reflect,
+{
+    fn field_descriptors(&self) -> &'static [FieldDescriptor] {
+        (**self).field_descriptors()
+    }
+
+    fn get_field(&self, tag: u32) -> Option<Value> {
+        (**self).get_field(tag)
+    }
+
+    fn set_field(&mut self, tag: u32, value: Value) -> Result<(), ReflectError> {
+        (**self).set_field(tag, value)
+    }
+
+    fn type_name(&self) -> &'static str {
+        (**self).type_name()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        (*self).into_any()
+    }
+
+    fn assign_any(&mut self, value: Box<dyn Any>) -> Result<(), Box<dyn Any>> {
+        (**self).assign_any(value)
+    }
+}
+
+/// The value of a field.
+///
+/// Scalar values use the Rust type of the field: `sint32` and `sfixed32` fields are `I32`,
+/// `fixed64` fields are `U64`, and so on. Enumerations are represented by their numeric value.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Value {
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    U32(u32),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    Enumeration(i32),
+    Message(Box<dyn Reflect>),
+    /// The elements of a repeated field.
+    List(Vec<Value>),
+    /// The entries of a map field, in the iteration order of the map.
+    Map(Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// Returns the name of the type of the value, as used in `ReflectError`.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Bool(..) => "bool",
+            Value::I32(..) => "i32",
+            Value::I64(..) => "i64",
+            Value::U32(..) => "u32",
+            Value::U64(..) => "u64",
+            Value::F32(..) => "f32",
+            Value::F64(..) => "f64",
+            Value::String(..) => "string",
+            Value::Bytes(..) => "bytes",
+            Value::Enumeration(..) => "enumeration",
+            Value::Message(ref message) => message.type_name(),
+            Value::List(..) => "list",
+            Value::Map(..) => "map",
+        }
+    }
+}
+
+/// An error setting a field with `Reflect::set_field`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReflectError {
+    /// The message has no field with the tag.
+    UnknownField(u32),
+    /// The value has a different type than the field.
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The value has the right type, but can't be stored in the field, for instance because it
+    /// exceeds the capacity of a fixed-capacity string.
+    InvalidValue(String),
+}
+
+impl ReflectError {
+    fn mismatch(expected: &'static str, found: &Value) -> ReflectError {
+        ReflectError::TypeMismatch {
+            expected,
+            found: found.type_name(),
+        }
+    }
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ReflectError::UnknownField(tag) => write!(f, "unknown field tag: {}", tag),
+            ReflectError::TypeMismatch { expected, found } => {
+                write!(f, "type mismatch: expected {}, found {}", expected, found)
+            }
+            ReflectError::InvalidValue(ref description) => {
+                write!(f, "invalid value: {}", description)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ReflectError {}
+
+// Conversions between field values and `Value`, used by derived `Reflect` implementations. Each
+// module has a `get` function converting a reference to a field value, and a `set` function
+// assigning a `Value` to one.
+
+macro_rules! scalar {
+    ($module:ident, $ty:ty, $variant:ident, $name:literal) => {
+        #[doc(hidden)]
+        pub mod $module {
+            use super::*;
+
+            pub fn get(value: &$ty) -> Value {
+                Value::$variant(*value)
+            }
+
+            pub fn set(field: &mut $ty, value: Value) -> Result<(), ReflectError> {
+                match value {
+                    Value::$variant(value) => {
+                        *field = value;
+                        Ok(())
+                    }
+                    other => Err(ReflectError::mismatch($name, &other)),
+                }
+            }
+        }
+    };
+}
+
+scalar!(bool, bool, Bool, "bool");
+scalar!(int32, i32, I32, "i32");
+scalar!(int64, i64, I64, "i64");
+scalar!(uint32, u32, U32, "u32");
+scalar!(uint64, u64, U64, "u64");
+scalar!(sint32, i32, I32, "i32");
+scalar!(sint64, i64, I64, "i64");
+scalar!(fixed32, u32, U32, "u32");
+scalar!(fixed64, u64, U64, "u64");
+scalar!(sfixed32, i32, I32, "i32");
+scalar!(sfixed64, i64, I64, "i64");
+scalar!(float, f32, F32, "f32");
+scalar!(double, f64, F64, "f64");
+scalar!(enumeration, i32, Enumeration, "enumeration");
+
+#[doc(hidden)]
+pub mod string {
+    use super::*;
+
+    pub fn get<S>(value: &S) -> Value
+    where
+        S: StringAdapter,
+    {
+        Value::String(String::from_utf8_lossy(value.as_bytes()).into_owned())
+    }
+
+    pub fn set<S>(field: &mut S, value: Value) -> Result<(), ReflectError>
+    where
+        S: StringAdapter,
+    {
+        match value {
+            Value::String(value) => {
+                let mut string = S::default();
+                string
+                    .replace_with(value.as_bytes())
+                    .map_err(|error| ReflectError::InvalidValue(error.to_string()))?;
+                *field = string;
+                Ok(())
+            }
+            other => Err(ReflectError::mismatch("string", &other)),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod bytes {
+    use super::*;
+
+    use crate::encoding::sealed::BytesAdapter as _;
+
+    pub fn get<A>(value: &A) -> Value
+    where
+        A: BytesAdapter,
+    {
+        let mut buf = Vec::with_capacity(value.len());
+        value.append_to(&mut buf);
+        Value::Bytes(buf)
+    }
+
+    pub fn set<A>(field: &mut A, value: Value) -> Result<(), ReflectError>
+    where
+        A: BytesAdapter,
+    {
+        match value {
+            Value::Bytes(value) => {
+                field
+                    .check_capacity(value.len())
+                    .map_err(|error| ReflectError::InvalidValue(error.to_string()))?;
+                field.replace_with(&*value);
+                Ok(())
+            }
+            other => Err(ReflectError::mismatch("bytes", &other)),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod message {
+    use super::*;
+
+    /// Copies the message into a `Value`.
+    ///
+    /// Messages are not required to implement `Clone`, so the copy is made by re-encoding the
+    /// message, which can't fail.
+    pub fn get<M>(value: &M) -> Value
+    where
+        M: Message + Reflect + Default + 'static,
+    {
+        let ctx = DecodeContext::with_recursion_limit(u32::MAX);
+        let copy = M::decode_with_context(&*value.encode_to_vec(), ctx)
+            .expect("failed to decode re-encoded message");
+        Value::Message(Box::new(copy))
+    }
+
+    pub fn set<M>(field: &mut M, value: Value) -> Result<(), ReflectError>
+    where
+        M: Reflect,
+    {
+        match value {
+            Value::Message(message) => {
+                let found = message.type_name();
+                field
+                    .assign_any(message.into_any())
+                    .map_err(|_| ReflectError::TypeMismatch {
+                        expected: field.type_name(),
+                        found,
+                    })
+            }
+            other => Err(ReflectError::mismatch(field.type_name(), &other)),
+        }
+    }
+}
+
+/// A map type which map fields can be stored in.
+#[doc(hidden)]
+pub trait MapField: Default {
+    type Key: Default;
+    type Value: Default;
+
+    fn insert(&mut self, key: Self::Key, value: Self::Value);
+}
+
+#[cfg(feature = "std")]
+impl<K, V, S> MapField for std::collections::HashMap<K, V, S>
+where
+    K: Default + Eq + core::hash::Hash,
+    V: Default,
+    S: Default + core::hash::BuildHasher,
+{
+    type Key = K;
+    type Value = V;
+
+    fn insert(&mut self, key: K, value: V) {
+        std::collections::HashMap::insert(self, key, value);
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl<K, V, S> MapField for indexmap::IndexMap<K, V, S>
+where
+    K: Default + Eq + core::hash::Hash,
+    V: Default,
+    S: Default + core::hash::BuildHasher,
+{
+    type Key = K;
+    type Value = V;
+
+    fn insert(&mut self, key: K, value: V) {
+        indexmap::IndexMap::insert(self, key, value);
+    }
+}
+
+impl<K, V> MapField for alloc::collections::BTreeMap<K, V>
+where
+    K: Default + Ord,
+    V: Default,
+{
+    type Key = K;
+    type Value = V;
+
+    fn insert(&mut self, key: K, value: V) {
+        alloc::collections::BTreeMap::insert(self, key, value);
+    }
+}
+
+/// Replaces the elements of a repeated field with the elements of a `Value::List`.
+#[doc(hidden)]
+pub fn set_list<C, T, F>(field: &mut C, value: Value, set: F) -> Result<(), ReflectError>
+where
+    C: Default + RepeatedContainer<T>,
+    T: Default,
+    F: Fn(&mut T, Value) -> Result<(), ReflectError>,
+{
+    match value {
+        Value::List(values) => {
+            let mut list = C::default();
+            for value in values {
+                list.push(set_owned(value, &set)?);
+            }
+            *field = list;
+            Ok(())
+        }
+        other => Err(ReflectError::mismatch("list", &other)),
+    }
+}
+
+/// Replaces the entries of a map field with the entries of a `Value::Map`.
+#[doc(hidden)]
+pub fn set_map<M, FK, FV>(
+    field: &mut M,
+    value: Value,
+    set_key: FK,
+    set_value: FV,
+) -> Result<(), ReflectError>
+where
+    M: MapField,
+    FK: Fn(&mut M::Key, Value) -> Result<(), ReflectError>,
+    FV: Fn(&mut M::Value, Value) -> Result<(), ReflectError>,
+{
+    match value {
+        Value::Map(entries) => {
+            let mut map = M::default();
+            for (key, value) in entries {
+                map.insert(set_owned(key, &set_key)?, set_owned(value, &set_value)?);
+            }
+            *field = map;
+            Ok(())
+        }
+        other => Err(ReflectError::mismatch("map", &other)),
+    }
+}
+
+/// Converts `value` with `set` into a new default value, for optional fields and oneof variants.
+#[doc(hidden)]
+pub fn set_owned<T, F>(value: Value, set: F) -> Result<T, ReflectError>
+where
+    T: Default,
+    F: Fn(&mut T, Value) -> Result<(), ReflectError>,
+{
+    let mut owned = T::default();
+    set(&mut owned, value)?;
+    Ok(owned)
+}
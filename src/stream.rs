@@ -0,0 +1,303 @@
+//! Incremental decoding of length-delimited messages from a sequence of buffers.
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use core::marker::PhantomData;
+
+use bytes::{Buf, Bytes, BytesMut};
+
+use crate::error::DecodeErrorKind;
+use crate::wire;
+use crate::{DecodeError, Message};
+
+/// The default maximum length of a message, 8 MiB.
+pub(crate) const DEFAULT_MAX_FRAME_LEN: usize = 8 * 1024 * 1024;
+
+/// A decoder for a stream of length-delimited messages which arrives in chunks.
+///
+/// This is the format written by repeatedly calling `Message::encode_length_delimited`. Chunks are
+/// added with `push`, and may split messages at arbitrary points. The decoder implements
+/// `Iterator`, yielding each message as soon as all of its bytes have been pushed; `next` returns
+/// `None` while the next message is incomplete, and may return more messages after further
+/// chunks are pushed.
+///
+/// Chunks are stored without copying. A message which lies within a single chunk is decoded from
+/// a slice of it, so its `Bytes` fields share the chunk's allocation; only messages which span
+/// several chunks are copied into a contiguous buffer first.
+///
+/// A message which fails to decode is returned as an error and skipped, and decoding continues
+/// with the next message. An invalid length delimiter is returned as an error too, but since the
+/// boundary of the next message is then unknown, the buffered data is discarded and the decoder
+/// yields no further items. The same holds for a message longer than the maximum frame length,
+/// 8 MiB by default, so that a corrupt or malicious length prefix can't make the caller buffer an
+/// arbitrary amount of data while waiting for the message to complete.
+///
+/// ```
+/// use bytes::Bytes;
+/// use prost::stream::StreamDecoder;
+/// use prost::Message;
+///
+/// let mut buf = Vec::new();
+/// String::from("foo").encode_length_delimited(&mut buf).unwrap();
+/// String::from("bar").encode_length_delimited(&mut buf).unwrap();
+/// let buf = Bytes::from(buf);
+///
+/// let mut decoder = StreamDecoder::<String>::new();
+/// decoder.push(buf.slice(..6));
+/// assert_eq!(decoder.next(), Some(Ok(String::from("foo"))));
+/// assert_eq!(decoder.next(), None);
+///
+/// decoder.push(buf.slice(6..));
+/// assert_eq!(decoder.next(), Some(Ok(String::from("bar"))));
+/// assert_eq!(decoder.next(), None);
+/// decoder.finish().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct StreamDecoder<M> {
+    chunks: VecDeque<Bytes>,
+    buffered: usize,
+    max_frame_len: usize,
+    failed: bool,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M> StreamDecoder<M> {
+    /// Creates a new decoder with no buffered data and the default maximum frame length.
+    pub fn new() -> StreamDecoder<M> {
+        StreamDecoder {
+            chunks: VecDeque::new(),
+            buffered: 0,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            failed: false,
+            _message: PhantomData,
+        }
+    }
+
+    /// Limits the length of the decoded messages to `len` bytes, excluding the length delimiter.
+    pub fn max_frame_len(mut self, len: usize) -> StreamDecoder<M> {
+        self.max_frame_len = len;
+        self
+    }
+
+    /// Appends a chunk of the stream.
+    pub fn push(&mut self, chunk: Bytes) {
+        if self.failed || chunk.is_empty() {
+            return;
+        }
+        self.buffered += chunk.len();
+        self.chunks.push_back(chunk);
+    }
+
+    /// Returns the number of bytes which have been pushed but not yet decoded.
+    pub fn buffered_len(&self) -> usize {
+        self.buffered
+    }
+
+    /// Checks that the stream ended at a message boundary.
+    ///
+    /// Returns a 'truncated' error if any bytes are still buffered, which is the case if the last
+    /// message is incomplete, or if complete messages have not been taken with `next`.
+    pub fn finish(self) -> Result<(), DecodeError> {
+        if self.buffered == 0 {
+            Ok(())
+        } else {
+            Err(DecodeError::with_kind(
+                DecodeErrorKind::BufferUnderflow,
+                format!("truncated message stream: {} bytes left", self.buffered),
+            ))
+        }
+    }
+
+    /// Returns the length and the encoded length of the length delimiter at the front of the
+    /// buffered data, or `None` if it is incomplete.
+    fn peek_length_delimiter(&self) -> Result<Option<(usize, usize)>, DecodeError> {
+        let bytes = self.chunks.iter().flat_map(|chunk| chunk.iter());
+        let mut value = 0u64;
+        for (count, &byte) in bytes.take(10).enumerate() {
+            value |= u64::from(byte & 0x7F) << (count * 7);
+            if byte < 0x80 {
+                // Check for u64::MAX overflow, as `decode_varint` does.
+                if wire::overflows_u64(count, byte) {
+                    return Err(DecodeError::invalid_varint());
+                }
+                if value > self.max_frame_len as u64 {
+                    return Err(DecodeError::with_kind(
+                        DecodeErrorKind::InvalidLength,
+                        format!(
+                            "frame of {} bytes exceeds the maximum frame length of {} bytes",
+                            value, self.max_frame_len
+                        ),
+                    ));
+                }
+                return Ok(Some((value as usize, count + 1)));
+            }
+        }
+        if self.buffered < 10 {
+            Ok(None)
+        } else {
+            Err(DecodeError::invalid_varint())
+        }
+    }
+
+    /// Removes the first `len` buffered bytes, which must all have been pushed.
+    fn split_to(&mut self, len: usize) -> Bytes {
+        debug_assert!(len <= self.buffered);
+        self.buffered -= len;
+        if len == 0 {
+            return Bytes::new();
+        }
+        let front_len = self.chunks[0].len();
+        if len < front_len {
+            return self.chunks[0].split_to(len);
+        }
+        if len == front_len {
+            return self.chunks.pop_front().unwrap();
+        }
+
+        // The bytes span several chunks, so they have to be copied.
+        let mut bytes = BytesMut::with_capacity(len);
+        while bytes.len() < len {
+            let front = &mut self.chunks[0];
+            let n = front.len().min(len - bytes.len());
+            bytes.extend_from_slice(&front[..n]);
+            front.advance(n);
+            if front.is_empty() {
+                self.chunks.pop_front();
+            }
+        }
+        bytes.freeze()
+    }
+}
+
+impl<M> Default for StreamDecoder<M> {
+    fn default() -> StreamDecoder<M> {
+        StreamDecoder::new()
+    }
+}
+
+impl<M> Iterator for StreamDecoder<M>
+where
+    M: Message + Default,
+{
+    type Item = Result<M, DecodeError>;
+
+    fn next(&mut self) -> Option<Result<M, DecodeError>> {
+        if self.failed {
+            return None;
+        }
+        let (len, delimiter_len) = match self.peek_length_delimiter() {
+            Ok(Some(delimiter)) => delimiter,
+            Ok(None) => return None,
+            Err(error) => {
+                self.failed = true;
+                self.chunks.clear();
+                self.buffered = 0;
+                return Some(Err(error));
+            }
+        };
+        if self.buffered - delimiter_len < len {
+            return None;
+        }
+        self.split_to(delimiter_len);
+        Some(M::decode(self.split_to(len)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    fn encode(values: &[&str]) -> Bytes {
+        let mut buf = Vec::new();
+        for value in values {
+            value.to_string().encode_length_delimited(&mut buf).unwrap();
+        }
+        Bytes::from(buf)
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let buf = encode(&["foo", "", "x"]);
+        for split in 0..=buf.len() {
+            for chunk_len in 1..=buf.len() {
+                let mut decoder = StreamDecoder::<String>::new();
+                let mut values = Vec::new();
+                decoder.push(buf.slice(..split));
+                values.extend(&mut decoder);
+                for start in (split..buf.len()).step_by(chunk_len) {
+                    decoder.push(buf.slice(start..buf.len().min(start + chunk_len)));
+                    values.extend(&mut decoder);
+                }
+                let values = values.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+                assert_eq!(values, ["foo", "", "x"]);
+                decoder.finish().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn zero_copy() {
+        let mut buf = Vec::new();
+        Bytes::from_static(b"foo")
+            .encode_length_delimited(&mut buf)
+            .unwrap();
+        let buf = Bytes::from(buf);
+
+        let mut decoder = StreamDecoder::<Bytes>::new();
+        decoder.push(buf.clone());
+        let value = decoder.next().unwrap().unwrap();
+        assert_eq!(value, &b"foo"[..]);
+        assert_eq!(value.as_ptr(), buf[3..].as_ptr());
+    }
+
+    #[test]
+    fn errors() {
+        // An invalid message is skipped.
+        let mut decoder = StreamDecoder::<String>::new();
+        decoder.push(Bytes::from_static(&[0x02, 0x0f, 0x00]));
+        decoder.push(encode(&["foo"]));
+        assert!(decoder.next().unwrap().is_err());
+        assert_eq!(decoder.next(), Some(Ok("foo".to_string())));
+
+        // A partial message is reported by `finish`.
+        decoder.push(encode(&["bar"]).slice(..3));
+        assert_eq!(decoder.next(), None);
+        assert_eq!(decoder.buffered_len(), 3);
+        let error = decoder.finish().unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::BufferUnderflow);
+
+        // An invalid length delimiter ends the stream.
+        let mut decoder = StreamDecoder::<String>::new();
+        decoder.push(Bytes::from_static(&[0xFF; 11]));
+        let error = decoder.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidVarint);
+        decoder.push(encode(&["foo"]));
+        assert_eq!(decoder.next(), None);
+        decoder.finish().unwrap();
+
+        // As is a length delimiter which overflows a `u64`.
+        let mut decoder = StreamDecoder::<String>::new();
+        decoder.push(Bytes::from_static(&[
+            0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02,
+        ]));
+        let error = decoder.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidVarint);
+    }
+
+    #[test]
+    fn max_frame_len() {
+        let mut decoder = StreamDecoder::<String>::new().max_frame_len(5);
+        decoder.push(encode(&["foo"]));
+        assert_eq!(decoder.next(), Some(Ok("foo".to_string())));
+
+        // The length is checked before the message is buffered.
+        decoder.push(Bytes::from_static(b"\x08"));
+        let error = decoder.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidLength);
+        assert_eq!(decoder.buffered_len(), 0);
+        assert_eq!(decoder.next(), None);
+    }
+}
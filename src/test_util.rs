@@ -0,0 +1,223 @@
+//! Test helpers for encoding functions and messages.
+//!
+//! These are the property test helpers `prost` checks its own field types with, exposed behind
+//! the `test-util` feature so that crates providing custom `BytesAdapter` or `StringAdapter`
+//! implementations, or hand-written messages, can be held to the same checks.
+
+use alloc::format;
+use alloc::string::ToString;
+use core::borrow::Borrow;
+use core::fmt::Debug;
+
+use bytes::{Buf, Bytes, BytesMut};
+use proptest::prelude::*;
+use proptest::test_runner::{TestCaseError, TestCaseResult};
+
+use crate::encoding::{decode_key, DecodeContext, WireType, MAX_TAG, MIN_TAG};
+use crate::{DecodeError, Message};
+
+/// Checks that `value` roundtrips through the encoding functions of a field type.
+///
+/// `encode` is called with `tag`, and must write a single field of `wire_type`, whose length is
+/// `encoded_len`. `merge` must decode it back into a value equal to `value`, consuming the field.
+pub fn check_type<T, B>(
+    value: T,
+    tag: u32,
+    wire_type: WireType,
+    encode: fn(u32, &B, &mut BytesMut),
+    merge: fn(WireType, &mut T, &mut Bytes, DecodeContext) -> Result<(), DecodeError>,
+    encoded_len: fn(u32, &B) -> usize,
+) -> TestCaseResult
+where
+    T: Debug + Default + PartialEq + Borrow<B>,
+    B: ?Sized,
+{
+    prop_assume!(MIN_TAG <= tag && tag <= MAX_TAG);
+
+    let expected_len = encoded_len(tag, value.borrow());
+
+    let mut buf = BytesMut::with_capacity(expected_len);
+    encode(tag, value.borrow(), &mut buf);
+
+    let mut buf = buf.freeze();
+
+    prop_assert_eq!(
+        buf.remaining(),
+        expected_len,
+        "encoded_len wrong; expected: {}, actual: {}",
+        expected_len,
+        buf.remaining()
+    );
+
+    if !buf.has_remaining() {
+        // Short circuit for empty packed values.
+        return Ok(());
+    }
+
+    let (decoded_tag, decoded_wire_type) =
+        decode_key(&mut buf).map_err(|error| TestCaseError::fail(error.to_string()))?;
+    prop_assert_eq!(
+        tag,
+        decoded_tag,
+        "decoded tag does not match; expected: {}, actual: {}",
+        tag,
+        decoded_tag
+    );
+
+    prop_assert_eq!(
+        wire_type,
+        decoded_wire_type,
+        "decoded wire type does not match; expected: {:?}, actual: {:?}",
+        wire_type,
+        decoded_wire_type,
+    );
+
+    match wire_type {
+        WireType::SixtyFourBit if buf.remaining() != 8 => Err(TestCaseError::fail(format!(
+            "64bit wire type illegal remaining: {}, tag: {}",
+            buf.remaining(),
+            tag
+        ))),
+        WireType::ThirtyTwoBit if buf.remaining() != 4 => Err(TestCaseError::fail(format!(
+            "32bit wire type illegal remaining: {}, tag: {}",
+            buf.remaining(),
+            tag
+        ))),
+        _ => Ok(()),
+    }?;
+
+    let mut roundtrip_value = T::default();
+    merge(
+        wire_type,
+        &mut roundtrip_value,
+        &mut buf,
+        DecodeContext::default(),
+    )
+    .map_err(|error| TestCaseError::fail(error.to_string()))?;
+
+    prop_assert!(
+        !buf.has_remaining(),
+        "expected buffer to be empty, remaining: {}",
+        buf.remaining()
+    );
+
+    prop_assert_eq!(value, roundtrip_value);
+
+    Ok(())
+}
+
+/// Checks that the collection `value` roundtrips through the encoding functions of a repeated or
+/// map field type.
+///
+/// Like `check_type`, except that `encode` may write any number of fields with `tag` and
+/// `wire_type`, which are merged one by one.
+pub fn check_collection_type<T, B, E, M, L>(
+    value: T,
+    tag: u32,
+    wire_type: WireType,
+    encode: E,
+    mut merge: M,
+    encoded_len: L,
+) -> TestCaseResult
+where
+    T: Debug + Default + PartialEq + Borrow<B>,
+    B: ?Sized,
+    E: FnOnce(u32, &B, &mut BytesMut),
+    M: FnMut(WireType, &mut T, &mut Bytes, DecodeContext) -> Result<(), DecodeError>,
+    L: FnOnce(u32, &B) -> usize,
+{
+    prop_assume!(MIN_TAG <= tag && tag <= MAX_TAG);
+
+    let expected_len = encoded_len(tag, value.borrow());
+
+    let mut buf = BytesMut::with_capacity(expected_len);
+    encode(tag, value.borrow(), &mut buf);
+
+    let mut buf = buf.freeze();
+
+    prop_assert_eq!(
+        buf.remaining(),
+        expected_len,
+        "encoded_len wrong; expected: {}, actual: {}",
+        expected_len,
+        buf.remaining()
+    );
+
+    let mut roundtrip_value = Default::default();
+    while buf.has_remaining() {
+        let (decoded_tag, decoded_wire_type) =
+            decode_key(&mut buf).map_err(|error| TestCaseError::fail(error.to_string()))?;
+
+        prop_assert_eq!(
+            tag,
+            decoded_tag,
+            "decoded tag does not match; expected: {}, actual: {}",
+            tag,
+            decoded_tag
+        );
+
+        prop_assert_eq!(
+            wire_type,
+            decoded_wire_type,
+            "decoded wire type does not match; expected: {:?}, actual: {:?}",
+            wire_type,
+            decoded_wire_type
+        );
+
+        merge(
+            wire_type,
+            &mut roundtrip_value,
+            &mut buf,
+            DecodeContext::default(),
+        )
+        .map_err(|error| TestCaseError::fail(error.to_string()))?;
+    }
+
+    prop_assert_eq!(value, roundtrip_value);
+
+    Ok(())
+}
+
+/// Checks that `message` roundtrips through encoding and decoding, and that its `encoded_len` and
+/// `encoded_len_length_delimited` are the lengths of the encodings.
+///
+/// # Panics
+///
+/// Panics if a check fails, with a message describing it.
+pub fn roundtrip<M>(message: &M)
+where
+    M: Message + Default + PartialEq,
+{
+    let buf = message.encode_to_vec();
+    assert_eq!(buf.len(), message.encoded_len(), "encoded_len is wrong");
+    let decoded = M::decode(&*buf).expect("failed to decode the encoded message");
+    assert_eq!(&decoded, message, "decoded message differs");
+
+    let buf = message.encode_length_delimited_to_vec();
+    assert_eq!(
+        buf.len(),
+        message.encoded_len_length_delimited(),
+        "encoded_len_length_delimited is wrong"
+    );
+    let decoded = M::decode_length_delimited_exact(&*buf)
+        .expect("failed to decode the length-delimited message");
+    assert_eq!(
+        &decoded, message,
+        "decoded length-delimited message differs"
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn roundtrip_wrappers() {
+        roundtrip(&String::from("foo"));
+        roundtrip(&Vec::<u8>::new());
+        roundtrip(&42u64);
+    }
+}
@@ -0,0 +1,1118 @@
+//! Conversion of messages to and from the Protobuf [text format][1], e.g. for debugging output
+//! and human-editable test fixtures.
+//!
+//! [1]: https://developers.google.com/protocol-buffers/docs/text-format-spec
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter;
+use core::str::CharIndices;
+
+use bytes::Bytes;
+
+use crate::Message;
+
+/// A message which can be written in the text format.
+///
+/// Implemented by `#[derive(Message)]` for messages marked `#[prost(text_format)]`.
+pub trait ToText {
+    /// Writes the fields of the message to `printer`.
+    fn write_text(&self, printer: &mut Printer);
+}
+
+/// A message which can be parsed from the text format.
+///
+/// Implemented by `#[derive(Message)]` for messages marked `#[prost(text_format)]`.
+pub trait FromText {
+    /// Parses the value of the field `name` from `parser` and merges it into `self`.
+    ///
+    /// Returns `false`, without consuming any input, if `name` does not name a field.
+    fn merge_text_field(&mut self, name: &str, parser: &mut Parser) -> Result<bool, ParseError>;
+}
+
+impl<M> ToText for Box<M>
+where
+    M: ToText,
+{
+    fn write_text(&self, printer: &mut Printer) {
+        (**self).write_text(printer)
+    }
+}
+
+impl<M> FromText for Box<M>
+where
+    M: FromText,
+{
+    fn merge_text_field(&mut self, name: &str, parser: &mut Parser) -> Result<bool, ParseError> {
+        (**self).merge_text_field(name, parser)
+    }
+}
+
+/// The wrapper well-known types are messages with a single `value` field.
+macro_rules! wrapper {
+    ($($ty:ty => $convert:ident,)*) => {
+        $(
+            impl ToText for $ty {
+                fn write_text(&self, printer: &mut Printer) {
+                    if *self != <$ty>::default() {
+                        printer.scalar("value", &encode::$convert(self));
+                    }
+                }
+            }
+
+            impl FromText for $ty {
+                fn merge_text_field(
+                    &mut self,
+                    name: &str,
+                    parser: &mut Parser,
+                ) -> Result<bool, ParseError> {
+                    if name != "value" {
+                        return Ok(false);
+                    }
+                    *self = parser.value(decode::$convert)?;
+                    Ok(true)
+                }
+            }
+        )*
+    };
+}
+
+wrapper! {
+    bool => bool,
+    u32 => uint32,
+    u64 => uint64,
+    i32 => int32,
+    i64 => int64,
+    f32 => float,
+    f64 => double,
+    String => string,
+    Vec<u8> => bytes,
+    Bytes => bytes,
+}
+
+/// `google.protobuf.Empty`
+impl ToText for () {
+    fn write_text(&self, _printer: &mut Printer) {}
+}
+
+/// `google.protobuf.Empty`
+impl FromText for () {
+    fn merge_text_field(&mut self, _name: &str, _parser: &mut Parser) -> Result<bool, ParseError> {
+        Ok(false)
+    }
+}
+
+/// Options for writing messages in the text format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextFormatOptions {
+    pretty: bool,
+    indent: usize,
+}
+
+impl TextFormatOptions {
+    /// Creates the default options: pretty output, indented by two spaces.
+    pub fn new() -> TextFormatOptions {
+        TextFormatOptions::default()
+    }
+
+    /// Sets whether each field is written on its own line, indented by its nesting depth. If
+    /// `false`, the message is written on a single line, e.g. `id: 1 child { name: "x" }`.
+    pub fn pretty(mut self, pretty: bool) -> TextFormatOptions {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Sets the number of spaces per nesting level used for pretty output.
+    pub fn indent(mut self, indent: usize) -> TextFormatOptions {
+        self.indent = indent;
+        self
+    }
+}
+
+impl Default for TextFormatOptions {
+    fn default() -> TextFormatOptions {
+        TextFormatOptions {
+            pretty: true,
+            indent: 2,
+        }
+    }
+}
+
+/// Writes a message in the text format, with the default options.
+///
+/// Set fields are written in field number order as `name: value`, with nested messages in
+/// braces, one entry per element of repeated fields, and map fields as repeated `key`/`value`
+/// entries sorted by key. Proto3 fields with default values are omitted.
+pub fn to_string<M>(msg: &M) -> String
+where
+    M: Message + ToText,
+{
+    to_string_with_options(msg, TextFormatOptions::default())
+}
+
+/// Writes a message in the text format, with the given options.
+///
+/// See `to_string` for details.
+pub fn to_string_with_options<M>(msg: &M, options: TextFormatOptions) -> String
+where
+    M: Message + ToText,
+{
+    let mut printer = Printer {
+        buf: String::new(),
+        options,
+        depth: 0,
+    };
+    msg.write_text(&mut printer);
+    printer.buf
+}
+
+/// Options for parsing messages from the text format.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    allow_unknown: bool,
+}
+
+impl ParseOptions {
+    /// Creates the default options.
+    pub fn new() -> ParseOptions {
+        ParseOptions::default()
+    }
+
+    /// Sets whether fields with unknown names are skipped. By default, they are rejected with an
+    /// error.
+    pub fn allow_unknown(mut self, allow: bool) -> ParseOptions {
+        self.allow_unknown = allow;
+        self
+    }
+}
+
+/// An error parsing a message from the text format.
+///
+/// The error reports the line and column, counted from 1, at which the error was detected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    column: usize,
+    description: Cow<'static, str>,
+}
+
+impl ParseError {
+    /// Returns the line of the input at which the error was detected, counted from 1.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column, in characters and counted from 1, at which the error was detected.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to parse text format message at line {}, column {}: {}",
+            self.line, self.column, self.description
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+/// Parses a message from the text format, with the default options.
+///
+/// Fields are written as `name: value`, with message fields as `name { ... }` (or `name < ... >`)
+/// and repeated fields as one entry per element or as a list, e.g. `ids: [1, 2]`. Strings and
+/// bytes are quoted and may contain C-style escapes, adjacent quoted strings are concatenated,
+/// enum values may be written by name or number, and `#` starts a comment. Fields with unknown
+/// names are rejected.
+pub fn parse<M>(input: &str) -> Result<M, ParseError>
+where
+    M: Message + FromText + Default,
+{
+    parse_with_options(input, ParseOptions::default())
+}
+
+/// Parses a message from the text format, with the given options.
+///
+/// See `parse` for details.
+pub fn parse_with_options<M>(input: &str, options: ParseOptions) -> Result<M, ParseError>
+where
+    M: Message + FromText + Default,
+{
+    let mut parser = Parser {
+        input,
+        pos: 0,
+        depth: 0,
+        options,
+    };
+    let mut msg = M::default();
+    parser.merge_fields(&mut msg, None)?;
+    Ok(msg)
+}
+
+/// Parses the text format representation of a message.
+///
+/// Meant to be used only by `FromText` implementations.
+#[doc(hidden)]
+pub struct Parser<'a> {
+    input: &'a str,
+    /// The byte offset of the next unparsed character.
+    pos: usize,
+    /// The number of enclosing messages being parsed.
+    depth: u32,
+    options: ParseOptions,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses the value of a singular field, following its name.
+    ///
+    /// The `:` separating the name and value is optional before message values.
+    pub fn value<T, F>(&mut self, parse: F) -> Result<T, ParseError>
+    where
+        F: FnOnce(&mut Parser<'a>) -> Result<T, ParseError>,
+    {
+        self.separator()?;
+        parse(self)
+    }
+
+    /// Parses the value of a repeated field, following its name, and appends it to `values`. The
+    /// value may be a single element or a list of elements, e.g. `[1, 2]`.
+    pub fn repeated<T, F>(&mut self, values: &mut Vec<T>, mut parse: F) -> Result<(), ParseError>
+    where
+        F: FnMut(&mut Parser<'a>) -> Result<T, ParseError>,
+    {
+        if self.separator()? && self.consume('[') {
+            self.list(|parser| {
+                values.push(parse(parser)?);
+                Ok(())
+            })
+        } else {
+            values.push(parse(self)?);
+            Ok(())
+        }
+    }
+
+    /// Consumes the `:` following a field name. Returns whether it was present, since it may be
+    /// omitted before message values.
+    fn separator(&mut self) -> Result<bool, ParseError> {
+        if self.consume(':') {
+            return Ok(true);
+        }
+        match self.peek() {
+            Some('{') | Some('<') => Ok(false),
+            _ => Err(self.error("expected ':'")),
+        }
+    }
+
+    /// Parses the elements of a list, after the opening `[`.
+    fn list<F>(&mut self, mut element: F) -> Result<(), ParseError>
+    where
+        F: FnMut(&mut Parser<'a>) -> Result<(), ParseError>,
+    {
+        if self.consume(']') {
+            return Ok(());
+        }
+        loop {
+            element(self)?;
+            if self.consume(']') {
+                return Ok(());
+            }
+            self.expect(',')?;
+        }
+    }
+
+    /// Parses the fields of a message until `close`, or until the end of the input if `close` is
+    /// `None`.
+    fn merge_fields<M>(&mut self, msg: &mut M, close: Option<char>) -> Result<(), ParseError>
+    where
+        M: FromText,
+    {
+        loop {
+            match (self.peek(), close) {
+                (None, None) => return Ok(()),
+                (None, Some(close)) => return Err(self.error(format!("expected '{}'", close))),
+                (Some(c), Some(close)) if c == close => {
+                    self.pos += c.len_utf8();
+                    return Ok(());
+                }
+                _ => (),
+            }
+
+            let start = self.pos;
+            let name = self.field_name()?;
+            if !msg.merge_text_field(name, self)? {
+                if !self.options.allow_unknown {
+                    return Err(self.error_at(start, format!("unknown field `{}`", name)));
+                }
+                self.skip_field()?;
+            }
+
+            // Fields may be followed by a `,` or `;`.
+            if !self.consume(',') {
+                self.consume(';');
+            }
+        }
+    }
+
+    /// Parses a message value, enclosed in `{}` or `<>`.
+    fn message_value<M>(&mut self, msg: &mut M) -> Result<(), ParseError>
+    where
+        M: FromText,
+    {
+        let close = if self.consume('{') {
+            '}'
+        } else if self.consume('<') {
+            '>'
+        } else {
+            return Err(self.error("expected '{'"));
+        };
+
+        #[cfg(not(feature = "no-recursion-limit"))]
+        {
+            if self.depth >= crate::RECURSION_LIMIT {
+                return Err(self.error("recursion limit reached"));
+            }
+        }
+        self.depth += 1;
+        let result = self.merge_fields(msg, Some(close));
+        self.depth -= 1;
+        result
+    }
+
+    /// Skips the value of an unknown field, following its name.
+    fn skip_field(&mut self) -> Result<(), ParseError> {
+        if self.separator()? && self.consume('[') {
+            self.list(Parser::skip_value)
+        } else {
+            self.skip_value()
+        }
+    }
+
+    fn skip_value(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            // `()` has no fields, so every field of the message is skipped.
+            Some('{') | Some('<') => self.message_value(&mut ()),
+            Some('"') | Some('\'') => self.string().map(drop),
+            _ => self.token().map(drop),
+        }
+    }
+
+    /// Skips whitespace and comments.
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = &self.input[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if !trimmed.starts_with('#') {
+                return;
+            }
+            self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+        }
+    }
+
+    /// Returns the next character after any whitespace, without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input[self.pos..].chars().next()
+    }
+
+    /// Consumes the next character after any whitespace if it is `c`.
+    fn consume(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), ParseError> {
+        if self.consume(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    fn field_name(&mut self) -> Result<&'a str, ParseError> {
+        self.skip_whitespace();
+        let input = self.input;
+        let rest = &input[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        if len == 0 || rest.as_bytes()[0].is_ascii_digit() {
+            return Err(self.error("expected a field name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    /// Consumes an identifier or number, e.g. `FOO`, `-1.5e+3f` or `0x1F`.
+    fn token(&mut self) -> Result<&'a str, ParseError> {
+        self.skip_whitespace();
+        let input = self.input;
+        let bytes = input.as_bytes();
+        let start = self.pos;
+        let mut end = start;
+        if bytes.get(end) == Some(&b'-') {
+            end += 1;
+        }
+        let body = end;
+        while let Some(&b) = bytes.get(end) {
+            let is_exponent_sign = (b == b'+' || b == b'-')
+                && end > body
+                && matches!(bytes[end - 1], b'e' | b'E')
+                && !bytes[body..].starts_with(b"0x")
+                && !bytes[body..].starts_with(b"0X");
+            if b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || is_exponent_sign {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        if end == body {
+            return Err(self.error("expected a value"));
+        }
+        self.pos = end;
+        Ok(&input[start..end])
+    }
+
+    /// Parses one or more adjacent quoted strings, returning their concatenated contents.
+    fn string(&mut self) -> Result<Vec<u8>, ParseError> {
+        let mut bytes = Vec::new();
+        self.quoted_string(&mut bytes)?;
+        while let Some('"') | Some('\'') = self.peek() {
+            self.quoted_string(&mut bytes)?;
+        }
+        Ok(bytes)
+    }
+
+    fn quoted_string(&mut self, out: &mut Vec<u8>) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        let input = self.input;
+        let start = self.pos;
+        let delimiter = match input[start..].chars().next() {
+            Some(c @ '"') | Some(c @ '\'') => c,
+            _ => return Err(self.error("expected a string")),
+        };
+        let body = start + 1;
+        let mut chars = input[body..].char_indices();
+        loop {
+            let (i, c) = match chars.next() {
+                Some((_, '\n')) | None => return Err(self.error_at(start, "unterminated string")),
+                Some(next) => next,
+            };
+            if c == delimiter {
+                self.pos = body + i + 1;
+                return Ok(());
+            }
+            if c != '\\' {
+                let mut buf = [0; 4];
+                out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                continue;
+            }
+
+            let escape = body + i;
+            let invalid = "invalid escape sequence";
+            let c = match chars.next() {
+                Some((_, c)) => c,
+                None => return Err(self.error_at(start, "unterminated string")),
+            };
+            match c {
+                'n' => out.push(b'\n'),
+                'r' => out.push(b'\r'),
+                't' => out.push(b'\t'),
+                'a' => out.push(0x07),
+                'b' => out.push(0x08),
+                'f' => out.push(0x0C),
+                'v' => out.push(0x0B),
+                '\\' | '\'' | '"' | '?' => out.push(c as u8),
+                '0'..='7' => {
+                    let (rest, count) = take_digits(&mut chars, 8, 2);
+                    let value = c.to_digit(8).unwrap() * 8u32.pow(count) + rest;
+                    if value > 0xFF {
+                        return Err(self.error_at(escape, invalid));
+                    }
+                    out.push(value as u8);
+                }
+                'x' => match take_digits(&mut chars, 16, 2) {
+                    (_, 0) => return Err(self.error_at(escape, invalid)),
+                    (value, _) => out.push(value as u8),
+                },
+                'u' | 'U' => {
+                    let len = if c == 'u' { 4 } else { 8 };
+                    let (value, count) = take_digits(&mut chars, 16, len);
+                    let c = match char::from_u32(value) {
+                        Some(c) if count == len => c,
+                        _ => return Err(self.error_at(escape, invalid)),
+                    };
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => return Err(self.error_at(escape, invalid)),
+            }
+        }
+    }
+
+    /// Returns an error at the next unparsed character.
+    fn error(&self, description: impl Into<Cow<'static, str>>) -> ParseError {
+        self.error_at(self.pos, description)
+    }
+
+    #[cold]
+    fn error_at(&self, pos: usize, description: impl Into<Cow<'static, str>>) -> ParseError {
+        let before = &self.input[..pos];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        ParseError {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            description: description.into(),
+        }
+    }
+}
+
+/// Consumes up to `max` digits in `radix` from `chars`, returning their value and count.
+fn take_digits(chars: &mut CharIndices<'_>, radix: u32, max: u32) -> (u32, u32) {
+    let mut value = 0;
+    let mut count = 0;
+    while count < max {
+        match chars.clone().next().and_then(|(_, c)| c.to_digit(radix)) {
+            Some(digit) => {
+                value = value * radix + digit;
+                count += 1;
+                chars.next();
+            }
+            None => break,
+        }
+    }
+    (value, count)
+}
+
+/// Accumulates the text format output of a message.
+///
+/// Meant to be used only by `ToText` implementations.
+#[doc(hidden)]
+pub struct Printer {
+    buf: String,
+    options: TextFormatOptions,
+    depth: usize,
+}
+
+impl Printer {
+    /// Writes the separator or indentation preceding a field, followed by `name`.
+    fn start_field(&mut self, name: &str) {
+        if self.options.pretty {
+            let indent = self.depth * self.options.indent;
+            self.buf.extend(iter::repeat(' ').take(indent));
+        } else if !self.buf.is_empty() {
+            self.buf.push(' ');
+        }
+        self.buf.push_str(name);
+    }
+
+    fn end_line(&mut self) {
+        if self.options.pretty {
+            self.buf.push('\n');
+        }
+    }
+
+    /// Writes a scalar field, given its formatted value.
+    pub fn scalar(&mut self, name: &str, value: &str) {
+        self.start_field(name);
+        self.buf.push_str(": ");
+        self.buf.push_str(value);
+        self.end_line();
+    }
+
+    /// Opens a message field. The fields written until the matching `end_message` are nested in
+    /// the message.
+    pub fn begin_message(&mut self, name: &str) {
+        self.start_field(name);
+        self.buf.push_str(" {");
+        self.end_line();
+        self.depth += 1;
+    }
+
+    /// Closes the innermost open message field.
+    pub fn end_message(&mut self) {
+        self.depth -= 1;
+        self.start_field("}");
+        self.end_line();
+    }
+
+    /// Writes a message field.
+    pub fn message<M>(&mut self, name: &str, msg: &M)
+    where
+        M: ToText,
+    {
+        self.begin_message(name);
+        msg.write_text(self);
+        self.end_message();
+    }
+}
+
+/// Quotes a string or bytes value, escaping quotes, backslashes and non-printable bytes.
+///
+/// Bytes outside of printable ASCII, including the bytes of non-ASCII UTF-8 characters, are
+/// written as octal escapes.
+fn quote(bytes: &[u8]) -> String {
+    let mut quoted = String::with_capacity(bytes.len() + 2);
+    quoted.push('"');
+    for &byte in bytes {
+        match byte {
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            b'"' => quoted.push_str("\\\""),
+            b'\'' => quoted.push_str("\\'"),
+            b'\\' => quoted.push_str("\\\\"),
+            0x20..=0x7E => quoted.push(char::from(byte)),
+            _ => quoted.push_str(&format!("\\{:03o}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Functions used by derived `ToText` implementations to format field values.
+#[doc(hidden)]
+pub mod encode {
+    use super::*;
+
+    use crate::encoding::sealed::BytesAdapter as _;
+    use crate::encoding::string::StringAdapter;
+    use crate::encoding::BytesAdapter;
+
+    macro_rules! number {
+        ($($name:ident: $ty:ty),*) => {
+            $(
+                pub fn $name(value: &$ty) -> String {
+                    value.to_string()
+                }
+            )*
+        };
+    }
+
+    number!(int32: i32, uint32: u32, sint32: i32, fixed32: u32, sfixed32: i32);
+    number!(int64: i64, uint64: u64, sint64: i64, fixed64: u64, sfixed64: i64);
+
+    pub fn bool(value: &bool) -> String {
+        value.to_string()
+    }
+
+    pub fn double(value: &f64) -> String {
+        if value.is_nan() {
+            "nan".to_string()
+        } else if value.is_infinite() {
+            let s = if *value > 0.0 { "inf" } else { "-inf" };
+            s.to_string()
+        } else {
+            // `Debug` uses exponent notation for very large and very small values.
+            format!("{:?}", value)
+        }
+    }
+
+    pub fn float(value: &f32) -> String {
+        if value.is_finite() {
+            format!("{:?}", value)
+        } else {
+            double(&f64::from(*value))
+        }
+    }
+
+    pub fn string<A>(value: &A) -> String
+    where
+        A: StringAdapter,
+    {
+        quote(value.as_bytes())
+    }
+
+    pub fn bytes<A>(value: &A) -> String
+    where
+        A: BytesAdapter,
+    {
+        let mut buf = Vec::with_capacity(value.len());
+        value.append_to(&mut buf);
+        quote(&buf)
+    }
+
+    /// Formats an enum value, given the name of the variant if the value is known.
+    pub fn enumeration(value: i32, name: Option<&'static str>) -> String {
+        match name {
+            Some(name) => name.to_string(),
+            None => value.to_string(),
+        }
+    }
+}
+
+/// Functions used by derived `FromText` implementations to parse field values.
+#[doc(hidden)]
+pub mod decode {
+    use super::*;
+
+    use core::convert::TryFrom;
+
+    use crate::encoding::sealed::BytesAdapter as _;
+    use crate::encoding::string::StringAdapter;
+    use crate::encoding::BytesAdapter;
+
+    /// Parses a decimal, hexadecimal (`0x`) or octal (`0`) integer.
+    fn integer<T>(parser: &mut Parser) -> Result<T, ParseError>
+    where
+        T: TryFrom<i128>,
+    {
+        parser.skip_whitespace();
+        let start = parser.pos;
+        let token = parser.token()?;
+        let (negative, digits) = match token.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, token),
+        };
+        let hex = digits
+            .strip_prefix("0x")
+            .or_else(|| digits.strip_prefix("0X"));
+        let magnitude = match hex {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None if digits.len() > 1 && digits.starts_with('0') => {
+                u64::from_str_radix(&digits[1..], 8)
+            }
+            None => digits.parse(),
+        };
+        let magnitude = magnitude.map_err(|_| parser.error_at(start, "invalid integer"))?;
+        let value = if negative {
+            -i128::from(magnitude)
+        } else {
+            i128::from(magnitude)
+        };
+        T::try_from(value).map_err(|_| parser.error_at(start, "integer out of range"))
+    }
+
+    macro_rules! integers {
+        ($($name:ident: $ty:ty),*) => {
+            $(
+                pub fn $name(parser: &mut Parser) -> Result<$ty, ParseError> {
+                    integer(parser)
+                }
+            )*
+        };
+    }
+
+    integers!(int32: i32, uint32: u32, sint32: i32, fixed32: u32, sfixed32: i32);
+    integers!(int64: i64, uint64: u64, sint64: i64, fixed64: u64, sfixed64: i64);
+
+    pub fn bool(parser: &mut Parser) -> Result<bool, ParseError> {
+        parser.skip_whitespace();
+        let start = parser.pos;
+        match parser.token()? {
+            "true" | "True" | "t" | "1" => Ok(true),
+            "false" | "False" | "f" | "0" => Ok(false),
+            _ => Err(parser.error_at(start, "invalid boolean")),
+        }
+    }
+
+    pub fn double(parser: &mut Parser) -> Result<f64, ParseError> {
+        parser.skip_whitespace();
+        let start = parser.pos;
+        let token = parser.token()?;
+        let (negative, body) = match token.strip_prefix('-') {
+            Some(body) => (true, body),
+            None => (false, token),
+        };
+        let value = match body.to_ascii_lowercase().as_str() {
+            "inf" | "infinity" => f64::INFINITY,
+            "nan" => f64::NAN,
+            body => {
+                let body = body.strip_suffix('f').unwrap_or(body);
+                // Only digits, `.` and exponents are accepted; `str::parse` would also accept
+                // e.g. `infinity`.
+                if !body.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+                    return Err(parser.error_at(start, "invalid number"));
+                }
+                body.parse()
+                    .map_err(|_| parser.error_at(start, "invalid number"))?
+            }
+        };
+        Ok(if negative { -value } else { value })
+    }
+
+    pub fn float(parser: &mut Parser) -> Result<f32, ParseError> {
+        double(parser).map(|value| value as f32)
+    }
+
+    pub fn string<A>(parser: &mut Parser) -> Result<A, ParseError>
+    where
+        A: StringAdapter,
+    {
+        parser.skip_whitespace();
+        let start = parser.pos;
+        let bytes = parser.string()?;
+        let mut string = A::default();
+        string
+            .replace_with(&*bytes)
+            .map_err(|_| parser.error_at(start, "invalid UTF-8 in string"))?;
+        Ok(string)
+    }
+
+    pub fn bytes<A>(parser: &mut Parser) -> Result<A, ParseError>
+    where
+        A: BytesAdapter,
+    {
+        parser.skip_whitespace();
+        let start = parser.pos;
+        let bytes = parser.string()?;
+        let mut value = A::default();
+        value
+            .check_capacity(bytes.len())
+            .map_err(|error| parser.error_at(start, error.to_string()))?;
+        value.replace_with(&*bytes);
+        Ok(value)
+    }
+
+    /// Parses an enum value, which may be a variant name or a number.
+    pub fn enumeration<F>(parser: &mut Parser, from_str_name: F) -> Result<i32, ParseError>
+    where
+        F: FnOnce(&str) -> Option<i32>,
+    {
+        match parser.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let start = parser.pos;
+                let name = parser.token()?;
+                from_str_name(name)
+                    .ok_or_else(|| parser.error_at(start, format!("unknown enum value `{}`", name)))
+            }
+            _ => int32(parser),
+        }
+    }
+
+    /// Parses a message value, enclosed in `{}` or `<>`.
+    pub fn message<M>(parser: &mut Parser) -> Result<M, ParseError>
+    where
+        M: FromText + Default,
+    {
+        let mut msg = M::default();
+        parser.message_value(&mut msg)?;
+        Ok(msg)
+    }
+
+    /// Parses a map entry, a message value with `key` and `value` fields.
+    pub fn map_entry<K, V, KF, VF>(
+        parser: &mut Parser,
+        key: KF,
+        value: VF,
+    ) -> Result<(K, V), ParseError>
+    where
+        K: Default,
+        V: Default,
+        KF: FnMut(&mut Parser) -> Result<K, ParseError>,
+        VF: FnMut(&mut Parser) -> Result<V, ParseError>,
+    {
+        struct Entry<K, V, KF, VF> {
+            key: K,
+            value: V,
+            parse_key: KF,
+            parse_value: VF,
+        }
+
+        impl<K, V, KF, VF> FromText for Entry<K, V, KF, VF>
+        where
+            KF: FnMut(&mut Parser) -> Result<K, ParseError>,
+            VF: FnMut(&mut Parser) -> Result<V, ParseError>,
+        {
+            fn merge_text_field(
+                &mut self,
+                name: &str,
+                parser: &mut Parser,
+            ) -> Result<bool, ParseError> {
+                match name {
+                    "key" => self.key = parser.value(&mut self.parse_key)?,
+                    "value" => self.value = parser.value(&mut self.parse_value)?,
+                    _ => return Ok(false),
+                }
+                Ok(true)
+            }
+        }
+
+        let mut entry = Entry {
+            key: K::default(),
+            value: V::default(),
+            parse_key: key,
+            parse_value: value,
+        };
+        parser.message_value(&mut entry)?;
+        Ok((entry.key, entry.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::encode::*;
+    use super::*;
+
+    use alloc::vec;
+
+    #[test]
+    fn scalars() {
+        assert_eq!(int32(&-1), "-1");
+        assert_eq!(uint64(&u64::MAX), "18446744073709551615");
+        assert_eq!(bool(&true), "true");
+        assert_eq!(float(&0.1), "0.1");
+        assert_eq!(double(&1.0), "1.0");
+        assert_eq!(double(&1e300), "1e300");
+        assert_eq!(double(&f64::NAN), "nan");
+        assert_eq!(float(&f32::INFINITY), "inf");
+        assert_eq!(double(&f64::NEG_INFINITY), "-inf");
+        assert_eq!(enumeration(1, Some("ONE")), "ONE");
+        assert_eq!(enumeration(7, None), "7");
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(string(&String::from("foo")), r#""foo""#);
+        assert_eq!(string(&String::from("a\"b'c\\")), r#""a\"b\'c\\""#);
+        assert_eq!(string(&String::from("\n\t\u{e9}")), r#""\n\t\303\251""#);
+        assert_eq!(bytes(&vec![0u8, 0x7F, b'x']), r#""\000\177x""#);
+        assert_eq!(bytes(&Bytes::new()), r#""""#);
+    }
+
+    #[test]
+    fn printer() {
+        struct Inner;
+
+        impl ToText for Inner {
+            fn write_text(&self, printer: &mut Printer) {
+                printer.scalar("name", "\"x\"");
+                printer.message("empty", &());
+            }
+        }
+
+        let mut printer = Printer {
+            buf: String::new(),
+            options: TextFormatOptions::new().indent(4),
+            depth: 0,
+        };
+        printer.scalar("id", "1");
+        printer.message("child", &Box::new(Inner));
+        assert_eq!(
+            printer.buf,
+            "id: 1\nchild {\n    name: \"x\"\n    empty {\n    }\n}\n"
+        );
+
+        let mut printer = Printer {
+            buf: String::new(),
+            options: TextFormatOptions::new().pretty(false),
+            depth: 0,
+        };
+        printer.scalar("id", "1");
+        printer.message("child", &Inner);
+        assert_eq!(printer.buf, "id: 1 child { name: \"x\" empty { } }");
+    }
+
+    #[test]
+    fn wrappers() {
+        let mut printer = Printer {
+            buf: String::new(),
+            options: TextFormatOptions::new().pretty(false),
+            depth: 0,
+        };
+        printer.message("a", &5u64);
+        printer.message("b", &String::new());
+        assert_eq!(printer.buf, "a { value: 5 } b { }");
+    }
+
+    fn parser(input: &str) -> Parser {
+        Parser {
+            input,
+            pos: 0,
+            depth: 0,
+            options: ParseOptions::new(),
+        }
+    }
+
+    #[test]
+    fn parse_scalars() {
+        assert_eq!(decode::int32(&mut parser("0x1F")), Ok(31));
+        assert_eq!(decode::int32(&mut parser("-010")), Ok(-8));
+        assert_eq!(
+            decode::uint64(&mut parser("18446744073709551615")),
+            Ok(u64::MAX)
+        );
+        assert_eq!(decode::int64(&mut parser("-0")), Ok(0));
+        decode::int32(&mut parser("2147483648")).unwrap_err();
+        decode::uint32(&mut parser("-1")).unwrap_err();
+        decode::int32(&mut parser("1.5")).unwrap_err();
+        assert_eq!(decode::bool(&mut parser("True")), Ok(true));
+        assert_eq!(decode::bool(&mut parser("0")), Ok(false));
+        assert_eq!(decode::double(&mut parser("-inf")), Ok(f64::NEG_INFINITY));
+        assert_eq!(decode::double(&mut parser("1.5e+3f")), Ok(1500.0));
+        assert_eq!(decode::float(&mut parser(".25")), Ok(0.25));
+        assert!(decode::double(&mut parser("nan")).unwrap().is_nan());
+        decode::double(&mut parser("infinite")).unwrap_err();
+        assert_eq!(
+            decode::enumeration(&mut parser("ONE"), |name| Some(name.len() as i32)),
+            Ok(3)
+        );
+        assert_eq!(decode::enumeration(&mut parser("-2"), |_| None), Ok(-2));
+        decode::enumeration(&mut parser("TWO"), |_| None).unwrap_err();
+    }
+
+    #[test]
+    fn parse_strings() {
+        assert_eq!(
+            decode::string::<String>(&mut parser(r#""a\n\x41\101é" 'b\'\"'"#)),
+            Ok("a\nAA\u{e9}b'\"".to_string())
+        );
+        assert_eq!(
+            decode::bytes::<Vec<u8>>(&mut parser(r#""\0\377\xff""#)),
+            Ok(vec![0, 0xFF, 0xFF])
+        );
+        decode::string::<String>(&mut parser(r#""\377""#)).unwrap_err();
+        decode::string::<String>(&mut parser(r#""\q""#)).unwrap_err();
+        decode::string::<String>(&mut parser(r#""\u12""#)).unwrap_err();
+        decode::string::<String>(&mut parser("\"a\nb\"")).unwrap_err();
+        decode::string::<String>(&mut parser("\"a")).unwrap_err();
+    }
+
+    #[test]
+    fn parse_messages() {
+        assert_eq!(parse::<u64>("value: 5"), Ok(5));
+        assert_eq!(parse::<u64>("# comment\nvalue: 0x10;"), Ok(16));
+        assert_eq!(parse::<String>("value: 'a' \"b\","), Ok("ab".to_string()));
+        assert_eq!(parse::<()>(""), Ok(()));
+
+        let options = ParseOptions::new().allow_unknown(true);
+        let input = "other { a: [1, 2] b: \"x\" c <> } list: [{}, 'y'] value: 3";
+        assert_eq!(parse_with_options::<u64>(input, options), Ok(3));
+        parse::<u64>(input).unwrap_err();
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = parse::<u64>("\n  other: 1").unwrap_err();
+        assert_eq!((error.line(), error.column()), (2, 3));
+        assert_eq!(
+            error.to_string(),
+            "failed to parse text format message at line 2, column 3: unknown field `other`"
+        );
+
+        let error = parse::<u64>("value 5").unwrap_err();
+        assert_eq!((error.line(), error.column()), (1, 7));
+        let error = parse::<u64>("value: x").unwrap_err();
+        assert_eq!((error.line(), error.column()), (1, 8));
+        let error = parse::<String>("value: '\u{e9}\\z'").unwrap_err();
+        assert_eq!((error.line(), error.column()), (1, 10));
+
+        let options = ParseOptions::new().allow_unknown(true);
+        let error = parse_with_options::<()>("a { b: 1", options).unwrap_err();
+        assert_eq!((error.line(), error.column()), (1, 9));
+        let nested = "a {".repeat(200);
+        parse_with_options::<()>(&nested, options).unwrap_err();
+    }
+}
@@ -0,0 +1,219 @@
+//! Storage for fields which are not recognized while decoding a message.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use bytes::{Buf, BufMut};
+
+use crate::encoding::{encode_key, encode_varint, DecodeContext, WireType};
+use crate::DecodeError;
+
+/// A set of Protobuf fields which were not recognized while decoding a message.
+///
+/// Messages which embed an `UnknownFields` container append every unrecognized field to it instead
+/// of skipping it, and re-emit the stored fields after the known fields when encoding. This allows
+/// a message to be decoded and re-encoded without losing fields added by newer versions of the
+/// schema.
+///
+/// Generated messages embed the container when built with
+/// `prost_build::Config::preserve_unknown_fields`. Derived messages can embed it in a field
+/// annotated with `#[prost(unknown_fields)]`.
+///
+/// The fields are kept in their encoded form, in the order they were decoded.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct UnknownFields {
+    buf: Vec<u8>,
+}
+
+impl UnknownFields {
+    /// Creates a new, empty set of unknown fields.
+    pub fn new() -> UnknownFields {
+        UnknownFields::default()
+    }
+
+    /// Returns `true` if no unknown fields have been stored.
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    /// Returns the stored fields, encoded as they would appear in a message.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Removes all stored fields.
+    pub fn clear(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Returns the encoded length of the stored fields.
+    pub fn encoded_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Encodes the stored fields to a buffer.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        buf.put_slice(&self.buf);
+    }
+
+    /// Decodes a field with the given key from a buffer, and appends it to the stored fields.
+    ///
+    /// Meant to be used only by `Message` implementations.
+    #[doc(hidden)]
+    pub fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        let len = self.buf.len();
+        encode_key(tag, wire_type, &mut self.buf);
+        copy_value(tag, wire_type, buf, &mut self.buf, ctx).map_err(|error| {
+            self.buf.truncate(len);
+            error
+        })
+    }
+}
+
+impl fmt::Debug for UnknownFields {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("UnknownFields").field(&self.buf).finish()
+    }
+}
+
+/// Copies the encoded value of a field from `buf` into `out`, validating it as `skip_field` does.
+fn copy_value<B>(
+    tag: u32,
+    wire_type: WireType,
+    buf: &mut B,
+    out: &mut Vec<u8>,
+    ctx: DecodeContext,
+) -> Result<(), DecodeError>
+where
+    B: Buf,
+{
+    ctx.limit_reached()?;
+    let len = match wire_type {
+        WireType::Varint => {
+            // Copy the bytes as-is rather than re-encoding the value, so that the output is
+            // identical to the input even if the varint is not minimally encoded, unless `ctx`
+            // rejects overlong varints.
+            let start = out.len();
+            for _ in 0..10 {
+                if !buf.has_remaining() {
+                    break;
+                }
+                let byte = buf.get_u8();
+                out.push(byte);
+                if byte < 0x80 {
+                    ctx.decode_varint(&mut &out[start..])?;
+                    return Ok(());
+                }
+            }
+            return Err(DecodeError::invalid_varint());
+        }
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        WireType::LengthDelimited => {
+            let len = ctx.decode_varint(buf)?;
+            encode_varint(len, out);
+            len
+        }
+        WireType::StartGroup => loop {
+            let (inner_tag, inner_wire_type) = ctx.decode_key(buf)?;
+            encode_key(inner_tag, inner_wire_type, out);
+            match inner_wire_type {
+                WireType::EndGroup => {
+                    if inner_tag != tag {
+                        return Err(DecodeError::unexpected_end_group());
+                    }
+                    break 0;
+                }
+                _ => copy_value(inner_tag, inner_wire_type, buf, out, ctx.enter_recursion())?,
+            }
+        },
+        WireType::EndGroup => return Err(DecodeError::unexpected_end_group()),
+    };
+
+    if len > buf.remaining() as u64 {
+        return Err(DecodeError::buffer_underflow());
+    }
+
+    out.put(buf.take(len as usize));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec;
+
+    use crate::encoding::decode_key;
+
+    fn roundtrip(mut input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut fields = UnknownFields::new();
+        while input.has_remaining() {
+            let (tag, wire_type) = decode_key(&mut input)?;
+            fields.merge_field(tag, wire_type, &mut input, DecodeContext::default())?;
+        }
+        assert_eq!(fields.encoded_len(), fields.as_bytes().len());
+        let mut buf = Vec::new();
+        fields.encode_raw(&mut buf);
+        Ok(buf)
+    }
+
+    #[test]
+    fn preserves_fields() {
+        let input = vec![
+            0x08, 0x96, 0x01, // varint
+            0x11, 1, 2, 3, 4, 5, 6, 7, 8, // 64-bit
+            0x1a, 0x03, b'a', b'b', b'c', // length-delimited
+            0x23, 0x08, 0x80, 0x00, 0x24, // group containing a non-minimal varint
+            0x2d, 1, 2, 3, 4, // 32-bit
+        ];
+        assert_eq!(roundtrip(&input).unwrap(), input);
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        // Truncated length-delimited value.
+        roundtrip(&[0x1a, 0x03, b'a']).unwrap_err();
+        // Mismatched end group tag.
+        roundtrip(&[0x23, 0x2c]).unwrap_err();
+        // Unterminated varint.
+        roundtrip(&[0x08, 0x80]).unwrap_err();
+    }
+
+    #[test]
+    fn rejects_overlong_varints() {
+        let inputs: [&[u8]; 3] = [
+            &[0x08, 0x80, 0x00],                   // varint
+            &[0x1a, 0x83, 0x00, b'a', b'b', b'c'], // length-delimited with an overlong length
+            &[0x23, 0x88, 0x00, 0x01, 0x24],       // group containing an overlong key
+        ];
+        for &input in &inputs {
+            let mut buf = input;
+            let (tag, wire_type) = decode_key(&mut buf).unwrap();
+            let mut fields = UnknownFields::new();
+            fields
+                .merge_field(tag, wire_type, &mut buf.clone(), DecodeContext::default())
+                .unwrap();
+            let strict = DecodeContext::default().reject_overlong_varints();
+            let error = UnknownFields::new()
+                .merge_field(tag, wire_type, &mut buf, strict)
+                .unwrap_err();
+            assert_eq!(error.kind(), crate::DecodeErrorKind::InvalidVarint);
+        }
+    }
+}
@@ -0,0 +1,395 @@
+//! Allocation-free parsing of the Protobuf wire format.
+//!
+//! The functions in this module read field keys, varints and whole fields from a byte slice
+//! cursor, which is advanced past the decoded data. Unlike the rest of the crate, they use only
+//! `core`, and report errors as a `WireError`, which unlike `DecodeError` does not allocate. The
+//! module is available without the `alloc` feature, which allows the structure of a message to be
+//! inspected, for instance to count its fields, in contexts where no allocator is available.
+//!
+//! The varint and key decoding of `prost::encoding` is built on the functions of this module, so
+//! both accept and reject the same keys and varints.
+//!
+//! ```
+//! use prost::wire::{decode_key, skip_field};
+//!
+//! // Field 1 is a varint, field 2 a string and field 3 a group containing field 4.
+//! let mut buf = &[0x08, 0x96, 0x01, 0x12, 0x01, b'a', 0x1b, 0x20, 0x01, 0x1c][..];
+//! let mut count = 0;
+//! while !buf.is_empty() {
+//!     let (tag, wire_type) = decode_key(&mut buf).unwrap();
+//!     skip_field(wire_type, tag, &mut buf).unwrap();
+//!     count += 1;
+//! }
+//! assert_eq!(count, 3);
+//! ```
+
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use crate::error::DecodeErrorKind;
+#[cfg(feature = "alloc")]
+use crate::DecodeError;
+
+/// The wire type of an encoded field, stored in the low three bits of the field key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum WireType {
+    Varint = 0,
+    SixtyFourBit = 1,
+    LengthDelimited = 2,
+    StartGroup = 3,
+    EndGroup = 4,
+    ThirtyTwoBit = 5,
+}
+
+/// The smallest valid field tag.
+pub const MIN_TAG: u32 = 1;
+/// The largest valid field tag, which fits in the 29 bits of a field key left by the wire type.
+pub const MAX_TAG: u32 = (1 << 29) - 1;
+impl WireType {
+    /// Returns the numeric value of the wire type, as stored in the field key.
+    #[inline]
+    pub const fn to_u8(self) -> u8 {
+        self as u8
+    }
+}
+
+impl fmt::Display for WireType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match *self {
+            WireType::Varint => "varint",
+            WireType::SixtyFourBit => "64-bit",
+            WireType::LengthDelimited => "length-delimited",
+            WireType::StartGroup => "start-group",
+            WireType::EndGroup => "end-group",
+            WireType::ThirtyTwoBit => "32-bit",
+        })
+    }
+}
+
+/// An error parsing the wire format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WireError {
+    /// The input ended before the end of a value.
+    BufferUnderflow,
+    /// A varint is longer than 10 bytes.
+    InvalidVarint,
+    /// A field key has a tag which is 0 or greater than `MAX_TAG`.
+    InvalidTag,
+    /// A field key has an unknown wire type.
+    InvalidWireType,
+    /// An end group key does not match the start group key, or is not preceded by one.
+    UnexpectedEndGroup,
+    /// Groups are nested more deeply than the default recursion limit of `DecodeContext`.
+    RecursionLimit,
+}
+
+impl WireError {
+    /// Returns the equivalent `DecodeErrorKind`.
+    #[cfg(feature = "alloc")]
+    pub fn kind(self) -> DecodeErrorKind {
+        match self {
+            WireError::BufferUnderflow => DecodeErrorKind::BufferUnderflow,
+            WireError::InvalidVarint => DecodeErrorKind::InvalidVarint,
+            WireError::InvalidTag => DecodeErrorKind::InvalidTag,
+            WireError::InvalidWireType => DecodeErrorKind::InvalidWireType,
+            WireError::UnexpectedEndGroup => DecodeErrorKind::UnexpectedEndGroup,
+            WireError::RecursionLimit => DecodeErrorKind::RecursionLimit,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            WireError::BufferUnderflow => "buffer underflow",
+            WireError::InvalidVarint => "invalid varint",
+            WireError::InvalidTag => "invalid tag value",
+            WireError::InvalidWireType => "invalid wire type value",
+            WireError::UnexpectedEndGroup => "unexpected end group tag",
+            WireError::RecursionLimit => "recursion limit reached",
+        }
+    }
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for WireError {}
+
+#[cfg(feature = "alloc")]
+impl From<WireError> for DecodeError {
+    fn from(error: WireError) -> DecodeError {
+        DecodeError::with_kind(error.kind(), error.description())
+    }
+}
+
+/// Decodes a LEB128-encoded variable length integer, advancing `buf` past it.
+#[inline]
+pub fn decode_varint(buf: &mut &[u8]) -> Result<u64, WireError> {
+    let len = buf.len();
+    if len >= 10 || (len > 0 && buf[len - 1] < 0x80) {
+        // SAFETY: `buf` is non-empty, and either holds ten bytes or ends with a terminating byte.
+        let (value, advance) = unsafe { decode_varint_slice(buf) }?;
+        *buf = &buf[advance..];
+        return Ok(value);
+    }
+
+    // The varint may still terminate before the end of the buffer.
+    let mut value = 0;
+    for (count, &byte) in buf.iter().enumerate() {
+        value |= u64::from(byte & 0x7F) << (count * 7);
+        if byte < 0x80 {
+            *buf = &buf[count + 1..];
+            return Ok(value);
+        }
+    }
+    Err(WireError::BufferUnderflow)
+}
+
+/// Decodes a LEB128-encoded variable length integer from the slice, returning the value and the
+/// number of bytes read.
+///
+/// Based loosely on [`ReadVarint64FromArray`][1].
+///
+/// ## Safety
+///
+/// The caller must ensure that `bytes` is non-empty and either `bytes.len() >= 10` or the last
+/// element in bytes is < `0x80`.
+///
+/// [1]: https://github.com/google/protobuf/blob/3.3.x/src/google/protobuf/io/coded_stream.cc#L365-L406
+#[inline]
+pub(crate) unsafe fn decode_varint_slice(bytes: &[u8]) -> Result<(u64, usize), WireError> {
+    // Fully unrolled varint decoding loop. Splitting into 32-bit pieces gives better performance.
+
+    let mut b: u8;
+    let mut part0: u32;
+    b = *bytes.get_unchecked(0);
+    part0 = u32::from(b);
+    if b < 0x80 {
+        return Ok((u64::from(part0), 1));
+    };
+    part0 -= 0x80;
+    b = *bytes.get_unchecked(1);
+    part0 += u32::from(b) << 7;
+    if b < 0x80 {
+        return Ok((u64::from(part0), 2));
+    };
+    part0 -= 0x80 << 7;
+    b = *bytes.get_unchecked(2);
+    part0 += u32::from(b) << 14;
+    if b < 0x80 {
+        return Ok((u64::from(part0), 3));
+    };
+    part0 -= 0x80 << 14;
+    b = *bytes.get_unchecked(3);
+    part0 += u32::from(b) << 21;
+    if b < 0x80 {
+        return Ok((u64::from(part0), 4));
+    };
+    part0 -= 0x80 << 21;
+    let value = u64::from(part0);
+
+    let mut part1: u32;
+    b = *bytes.get_unchecked(4);
+    part1 = u32::from(b);
+    if b < 0x80 {
+        return Ok((value + (u64::from(part1) << 28), 5));
+    };
+    part1 -= 0x80;
+    b = *bytes.get_unchecked(5);
+    part1 += u32::from(b) << 7;
+    if b < 0x80 {
+        return Ok((value + (u64::from(part1) << 28), 6));
+    };
+    part1 -= 0x80 << 7;
+    b = *bytes.get_unchecked(6);
+    part1 += u32::from(b) << 14;
+    if b < 0x80 {
+        return Ok((value + (u64::from(part1) << 28), 7));
+    };
+    part1 -= 0x80 << 14;
+    b = *bytes.get_unchecked(7);
+    part1 += u32::from(b) << 21;
+    if b < 0x80 {
+        return Ok((value + (u64::from(part1) << 28), 8));
+    };
+    part1 -= 0x80 << 21;
+    let value = value + ((u64::from(part1)) << 28);
+
+    let mut part2: u32;
+    b = *bytes.get_unchecked(8);
+    part2 = u32::from(b);
+    if b < 0x80 {
+        return Ok((value + (u64::from(part2) << 56), 9));
+    };
+    part2 -= 0x80;
+    b = *bytes.get_unchecked(9);
+    part2 += u32::from(b) << 7;
+    if b < 0x80 && !overflows_u64(9, b) {
+        return Ok((value + (u64::from(part2) << 56), 10));
+    };
+
+    // We have overrun the maximum size of a varint (10 bytes), or the value doesn't fit in a
+    // `u64`. Assume the data is corrupt.
+    Err(WireError::InvalidVarint)
+}
+
+/// Returns `true` if `byte`, the terminating byte of a varint at index `count`, overflows a `u64`.
+///
+/// A `u64` holds 64 bits, so the tenth byte of a varint may only contribute the single remaining
+/// bit. All of the varint decoders share this check, so that they agree on which inputs overflow.
+#[inline]
+pub(crate) fn overflows_u64(count: usize, byte: u8) -> bool {
+    count == 9 && byte > 0x01
+}
+
+/// Decodes a field key, advancing `buf` past it.
+///
+/// Like `prost::decode_key`, tags outside of `MIN_TAG..=MAX_TAG` and unknown wire types are
+/// rejected.
+pub fn decode_key(buf: &mut &[u8]) -> Result<(u32, WireType), WireError> {
+    split_key(decode_varint(buf)?)
+}
+
+/// Splits a decoded field key into its tag and wire type.
+#[inline]
+pub(crate) fn split_key(key: u64) -> Result<(u32, WireType), WireError> {
+    let wire_type = match key & 0x07 {
+        0 => WireType::Varint,
+        1 => WireType::SixtyFourBit,
+        2 => WireType::LengthDelimited,
+        3 => WireType::StartGroup,
+        4 => WireType::EndGroup,
+        5 => WireType::ThirtyTwoBit,
+        _ => return Err(WireError::InvalidWireType),
+    };
+    let tag = key >> 3;
+    if tag < u64::from(MIN_TAG) || tag > u64::from(MAX_TAG) {
+        return Err(WireError::InvalidTag);
+    }
+    Ok((tag as u32, wire_type))
+}
+
+/// Skips the value of a field with the given key, advancing `buf` past it.
+///
+/// Groups are skipped along with all of their nested fields, up to the default recursion limit of
+/// `DecodeContext`, unless the `no-recursion-limit` feature is enabled.
+pub fn skip_field(wire_type: WireType, tag: u32, buf: &mut &[u8]) -> Result<(), WireError> {
+    skip_value(wire_type, tag, buf, 0)
+}
+
+fn skip_value(wire_type: WireType, tag: u32, buf: &mut &[u8], depth: u32) -> Result<(), WireError> {
+    let len = match wire_type {
+        WireType::Varint => return decode_varint(buf).map(|_| ()),
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        WireType::LengthDelimited => decode_varint(buf)?,
+        WireType::StartGroup => {
+            check_depth(depth)?;
+            loop {
+                let (inner_tag, inner_wire_type) = decode_key(buf)?;
+                match inner_wire_type {
+                    WireType::EndGroup if inner_tag == tag => return Ok(()),
+                    WireType::EndGroup => return Err(WireError::UnexpectedEndGroup),
+                    _ => skip_value(inner_wire_type, inner_tag, buf, depth + 1)?,
+                }
+            }
+        }
+        WireType::EndGroup => return Err(WireError::UnexpectedEndGroup),
+    };
+    if len > buf.len() as u64 {
+        return Err(WireError::BufferUnderflow);
+    }
+    *buf = &buf[len as usize..];
+    Ok(())
+}
+
+/// Checks that a group at `depth` levels of nesting may be skipped.
+#[cfg(not(feature = "no-recursion-limit"))]
+#[inline]
+fn check_depth(depth: u32) -> Result<(), WireError> {
+    if depth >= crate::RECURSION_LIMIT {
+        return Err(WireError::RecursionLimit);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "no-recursion-limit")]
+#[inline]
+fn check_depth(_depth: u32) -> Result<(), WireError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn varints() {
+        let mut buf = &[0x96, 0x01, 0x00][..];
+        assert_eq!(decode_varint(&mut buf), Ok(150));
+        assert_eq!(buf, &[0x00]);
+
+        assert_eq!(
+            decode_varint(&mut &[0x80, 0x80][..]),
+            Err(WireError::BufferUnderflow)
+        );
+        assert_eq!(
+            decode_varint(&mut &[0xFF; 11][..]),
+            Err(WireError::InvalidVarint)
+        );
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(
+            decode_key(&mut &[0x0a][..]),
+            Ok((1, WireType::LengthDelimited))
+        );
+        assert_eq!(decode_key(&mut &[0x02][..]), Err(WireError::InvalidTag));
+        assert_eq!(
+            decode_key(&mut &[0x0e][..]),
+            Err(WireError::InvalidWireType)
+        );
+        assert_eq!(
+            decode_key(&mut &[0x80, 0x80, 0x80, 0x80, 0x10][..]),
+            Err(WireError::InvalidTag)
+        );
+    }
+
+    #[test]
+    fn skip_fields() {
+        let mut buf = &[0x0d, 1, 2, 3, 4, 0x0a, 0x02, 1, 2, 0xff][..];
+        let (tag, wire_type) = decode_key(&mut buf).unwrap();
+        skip_field(wire_type, tag, &mut buf).unwrap();
+        let (tag, wire_type) = decode_key(&mut buf).unwrap();
+        skip_field(wire_type, tag, &mut buf).unwrap();
+        assert_eq!(buf, &[0xff]);
+
+        // Truncated length-delimited value.
+        assert_eq!(
+            skip_field(WireType::LengthDelimited, 1, &mut &[0x03, 1][..]),
+            Err(WireError::BufferUnderflow)
+        );
+        // Mismatched end group tag.
+        assert_eq!(
+            skip_field(WireType::StartGroup, 1, &mut &[0x14][..]),
+            Err(WireError::UnexpectedEndGroup)
+        );
+        // Groups nested too deeply.
+        #[cfg(not(feature = "no-recursion-limit"))]
+        assert_eq!(
+            skip_field(WireType::StartGroup, 1, &mut &[0x0b; 200][..]),
+            Err(WireError::RecursionLimit)
+        );
+        #[cfg(feature = "alloc")]
+        assert_eq!(
+            DecodeError::from(WireError::RecursionLimit).kind(),
+            DecodeErrorKind::RecursionLimit
+        );
+    }
+}
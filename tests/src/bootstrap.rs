@@ -23,6 +23,10 @@ fn bootstrap() {
     prost_build::Config::new()
         .compile_well_known_types()
         .btree_map(&["."])
+        .json(&["."])
+        .text_format(&["."])
+        .reflect(&["."])
+        .feature_gated_derives(true)
         .out_dir(tempdir.path())
         .compile_protos(
             &[
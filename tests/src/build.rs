@@ -26,6 +26,11 @@ fn main() {
     // values.
     let mut config = prost_build::Config::new();
     config.btree_map(&["."]);
+    config.enable_type_names();
+    // The JSON and text format tests use the well-known types messages.
+    config.json(&[".well_known_types"]);
+    config.text_format(&[".well_known_types"]);
+    config.feature_gated_derives(true);
     // Tests for custom attributes
     config.type_attribute("Foo.Bar_Baz.Foo_barBaz", "#[derive(Eq, PartialOrd, Ord)]");
     config.type_attribute(
@@ -91,6 +96,22 @@ fn main() {
         .compile_protos(&[src.join("proto3_presence.proto")], includes)
         .unwrap();
 
+    prost_build::Config::new()
+        .preserve_unknown_fields(&[".unknown_fields.Old"])
+        .compile_protos(&[src.join("unknown_fields.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .set_semantics(&[".set_semantics.Tagged.tags"])
+        .compile_protos(&[src.join("set_semantics.proto")], includes)
+        .unwrap();
+
+    prost_build::Config::new()
+        .btree_map(&["."])
+        .borrowed_views(&[".borrowed_views.Document", ".borrowed_views.Author"])
+        .compile_protos(&[src.join("borrowed_views.proto")], includes)
+        .unwrap();
+
     {
         let mut config = prost_build::Config::new();
         config.disable_comments(&["."]);
@@ -0,0 +1,225 @@
+use alloc::collections::BTreeMap;
+use prost::alloc::{string::String, vec, vec::Vec};
+use prost::json::{
+    from_json_value, from_json_value_with_options, to_json_value, FromJsonOptions, Value,
+};
+use prost::{Enumeration, Message, Oneof};
+
+use crate::well_known_types::Foo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Color {
+    #[prost(name = "COLOR_RED")]
+    Red = 0,
+    Green = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(json)]
+pub struct Nested {
+    #[prost(int32, tag = "1")]
+    pub value: i32,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+#[prost(json)]
+pub enum Choice {
+    #[prost(string, tag = "8")]
+    TextValue(String),
+    #[prost(message, tag = "9")]
+    NestedValue(Nested),
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(json)]
+pub struct Sample {
+    #[prost(int64, tag = "1")]
+    pub big_number: i64,
+    #[prost(double, tag = "2")]
+    pub ratio: f64,
+    #[prost(bytes = "vec", tag = "3")]
+    pub payload: Vec<u8>,
+    #[prost(enumeration = "Color", repeated, tag = "4")]
+    pub colors: Vec<i32>,
+    #[prost(message, optional, tag = "5")]
+    pub nested: Option<Nested>,
+    #[prost(string, optional, tag = "6")]
+    pub label: Option<String>,
+    #[prost(btree_map = "uint64, message", tag = "7")]
+    pub lookup: BTreeMap<u64, Nested>,
+    #[prost(oneof = "Choice", tags = "8, 9")]
+    pub choice: Option<Choice>,
+    #[prost(message, optional, tag = "10")]
+    pub timeout: Option<::prost_types::Duration>,
+    #[prost(message, optional, tag = "11")]
+    pub created: Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "12")]
+    pub mask: Option<::prost_types::FieldMask>,
+}
+
+fn json(s: &str) -> Value {
+    s.parse().unwrap()
+}
+
+#[test]
+fn test_to_json_value() {
+    let mut lookup = BTreeMap::new();
+    lookup.insert(3, Nested { value: 1 });
+    let msg = Sample {
+        big_number: -5,
+        ratio: f64::NAN,
+        payload: vec![1, 2],
+        colors: vec![Color::Red as i32, Color::Green as i32, 7],
+        nested: None,
+        label: Some("x".into()),
+        lookup,
+        choice: Some(Choice::NestedValue(Nested { value: 2 })),
+        timeout: Some(::prost_types::Duration {
+            seconds: 1,
+            nanos: 500_000_000,
+        }),
+        created: Some(::prost_types::Timestamp {
+            seconds: 1,
+            nanos: 0,
+        }),
+        mask: Some(::prost_types::FieldMask {
+            paths: vec!["foo_bar".into(), "baz".into()],
+        }),
+    };
+    let expected = json(
+        r#"{
+            "bigNumber": "-5",
+            "ratio": "NaN",
+            "payload": "AQI=",
+            "colors": ["COLOR_RED", "Green", 7],
+            "nested": null,
+            "label": "x",
+            "lookup": {"3": {"value": 1}},
+            "nestedValue": {"value": 2},
+            "timeout": "1.500s",
+            "created": "1970-01-01T00:00:01Z",
+            "mask": "fooBar,baz"
+        }"#,
+    );
+    assert_eq!(to_json_value(&msg), expected);
+
+    let msg = Sample {
+        choice: Some(Choice::TextValue("y".into())),
+        ..Sample::default()
+    };
+    let value = to_json_value(&msg);
+    assert_eq!(value["textValue"], json(r#""y""#));
+    assert_eq!(value["bigNumber"], json(r#""0""#));
+    assert_eq!(value["timeout"], Value::Null);
+    assert!(value.get("nestedValue").is_none());
+}
+
+#[test]
+fn test_generated_to_json_value() {
+    let msg = Foo {
+        null: ::prost_types::NullValue::NullValue.into(),
+        int64: Some(42),
+        bytes: Some(b"value".to_vec()),
+        ..Foo::default()
+    };
+    let value = to_json_value(&msg);
+    assert_eq!(value["null"], json(r#""NULL_VALUE""#));
+    assert_eq!(value["int64"], json(r#""42""#));
+    assert_eq!(value["bytes"], json(r#""dmFsdWU=""#));
+    assert_eq!(value["string"], Value::Null);
+}
+
+#[test]
+fn test_from_json_value() {
+    let value = json(
+        r#"{
+            "bigNumber": -5,
+            "ratio": "Infinity",
+            "payload": "AQI",
+            "colors": ["COLOR_RED", 1, 7],
+            "nested": null,
+            "label": "x",
+            "lookup": {"3": {"value": "1"}},
+            "nested_value": {"value": 2e0},
+            "timeout": "1.5s",
+            "created": "1970-01-01T01:00:01+01:00",
+            "mask": "fooBar,baz",
+            "unknown": true
+        }"#,
+    );
+    let mut lookup = BTreeMap::new();
+    lookup.insert(3, Nested { value: 1 });
+    let expected = Sample {
+        big_number: -5,
+        ratio: f64::INFINITY,
+        payload: vec![1, 2],
+        colors: vec![Color::Red as i32, Color::Green as i32, 7],
+        nested: None,
+        label: Some("x".into()),
+        lookup,
+        choice: Some(Choice::NestedValue(Nested { value: 2 })),
+        timeout: Some(::prost_types::Duration {
+            seconds: 1,
+            nanos: 500_000_000,
+        }),
+        created: Some(::prost_types::Timestamp {
+            seconds: 1,
+            nanos: 0,
+        }),
+        mask: Some(::prost_types::FieldMask {
+            paths: vec!["foo_bar".into(), "baz".into()],
+        }),
+    };
+    assert_eq!(from_json_value::<Sample>(&value), Ok(expected.clone()));
+    assert_eq!(
+        from_json_value::<Sample>(&to_json_value(&expected)),
+        Ok(expected)
+    );
+
+    let msg = from_json_value::<Sample>(&json(r#"{"text_value": "y"}"#)).unwrap();
+    assert_eq!(msg.choice, Some(Choice::TextValue("y".into())));
+}
+
+#[test]
+fn test_from_json_value_errors() {
+    let value = json(r#"{"lookup": {"3": {"value": 1.5}}}"#);
+    let error = from_json_value::<Sample>(&value).unwrap_err();
+    assert_eq!(error.path(), "$.lookup.3.value");
+
+    let value = json(r#"{"colors": ["COLOR_RED", "BLUE"]}"#);
+    let error = from_json_value::<Sample>(&value).unwrap_err();
+    assert_eq!(error.path(), "$.colors[1]");
+
+    let value = json(r#"{"created": "1970-01-01"}"#);
+    let error = from_json_value::<Sample>(&value).unwrap_err();
+    assert_eq!(error.path(), "$.created");
+
+    let options = FromJsonOptions::new().deny_unknown_fields(true);
+    let value = json(r#"{"nested": {"other": 1}}"#);
+    from_json_value::<Sample>(&value).unwrap();
+    let error = from_json_value_with_options::<Sample>(&value, options).unwrap_err();
+    assert_eq!(error.path(), "$.nested.other");
+    let value = json(r#"{"textValue": "y", "big_number": "1"}"#);
+    from_json_value_with_options::<Sample>(&value, options).unwrap();
+}
+
+#[test]
+fn test_generated_from_json_value() {
+    let value = json(r#"{"null": "NULL_VALUE", "int64": 42, "bytes": "dmFsdWU="}"#);
+    let msg = from_json_value::<Foo>(&value).unwrap();
+    assert_eq!(msg.null, ::prost_types::NullValue::NullValue as i32);
+    assert_eq!(msg.int64, Some(42));
+    assert_eq!(msg.bytes, Some(b"value".to_vec()));
+    assert_eq!(msg.string, None);
+}
+
+#[test]
+fn test_enumeration_names() {
+    use prost_types::field_descriptor_proto::Type;
+
+    assert_eq!(Type::Double.as_str_name(), "TYPE_DOUBLE");
+    assert_eq!(Type::from_str_name("TYPE_SINT64"), Some(Type::Sint64));
+    assert_eq!(Type::from_str_name("Double"), None);
+    assert_eq!(Color::Green.as_str_name(), "Green");
+    assert_eq!(Color::from_str_name("COLOR_RED"), Some(Color::Red));
+}
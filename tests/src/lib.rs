@@ -37,10 +37,14 @@ mod debug;
 mod deprecated_field;
 #[cfg(test)]
 mod generic_derive;
+#[cfg(all(test, feature = "json"))]
+mod json;
 #[cfg(test)]
 mod message_encoding;
 #[cfg(test)]
 mod no_unused_results;
+#[cfg(all(test, feature = "text-format"))]
+mod text_format;
 #[cfg(test)]
 mod well_known_types;
 
@@ -95,6 +99,18 @@ pub mod proto3 {
     }
 }
 
+pub mod unknown_fields {
+    include!(concat!(env!("OUT_DIR"), "/unknown_fields.rs"));
+}
+
+pub mod set_semantics {
+    include!(concat!(env!("OUT_DIR"), "/set_semantics.rs"));
+}
+
+pub mod borrowed_views {
+    include!(concat!(env!("OUT_DIR"), "/borrowed_views.rs"));
+}
+
 pub mod invalid {
     pub mod doctest {
         include!(concat!(env!("OUT_DIR"), "/invalid.doctest.rs"));
@@ -360,6 +376,24 @@ mod tests {
         set2.insert(msg2.field);
     }
 
+    #[test]
+    fn test_type_names() {
+        use prost::Name;
+
+        assert_eq!(nesting::A::NAME, "A");
+        assert_eq!(nesting::A::PACKAGE, "nesting");
+        assert_eq!(nesting::A::full_name(), "nesting.A");
+        assert_eq!(nesting::A::type_url(), "type.googleapis.com/nesting.A");
+
+        // Nested types include the enclosing message in their name.
+        type FuzzBuster = foo::bar_baz::foo_bar_baz::FuzzBuster;
+        assert_eq!(FuzzBuster::NAME, "Foo_barBaz.fuzz_buster");
+        assert_eq!(
+            FuzzBuster::full_name(),
+            "Foo.Bar_Baz.Foo_barBaz.fuzz_buster"
+        );
+    }
+
     #[test]
     fn test_nesting() {
         use crate::nesting::{A, B};
@@ -373,6 +407,57 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_decode_error_tag_path() {
+        use crate::nesting::{A, B};
+
+        let a = A {
+            b: Some(Box::new(B {
+                a: Some(Box::new(A {
+                    repeated_a: vec![A::default()],
+                    ..A::default()
+                })),
+            })),
+            ..A::default()
+        };
+        let mut buf = Vec::new();
+        a.encode(&mut buf).unwrap();
+        // Corrupt the length of the empty, innermost message, which is the last field.
+        let len = buf.len();
+        buf[len - 1] = 1;
+
+        let error = A::decode(&*buf).unwrap_err();
+        assert_eq!(error.tag_path(), &[("b", 4), ("a", 1), ("repeated_a", 2)]);
+    }
+
+    #[test]
+    fn test_decode_error_kind() {
+        use prost::encoding::WireType;
+        use prost::DecodeErrorKind;
+
+        fn kind(buf: &[u8]) -> DecodeErrorKind {
+            crate::nesting::A::decode(buf).unwrap_err().kind()
+        }
+
+        // Field 4 is a message, with a length which exceeds the input.
+        assert_eq!(kind(&[0x22, 0x05]), DecodeErrorKind::BufferUnderflow);
+        assert_eq!(kind(&[0x22, 0x80]), DecodeErrorKind::InvalidVarint);
+        assert_eq!(
+            kind(&[0x20, 0x00]),
+            DecodeErrorKind::UnexpectedWireType {
+                expected: WireType::LengthDelimited,
+                found: WireType::Varint,
+            }
+        );
+        assert_eq!(kind(&[0x00]), DecodeErrorKind::InvalidTag);
+        assert_eq!(kind(&[0x0f]), DecodeErrorKind::InvalidWireType);
+        // An end group key for unknown field 7.
+        assert_eq!(kind(&[0x3c]), DecodeErrorKind::UnexpectedEndGroup);
+
+        let error = alloc::string::String::decode(&[0x0a, 0x01, 0xff][..]).unwrap_err();
+        assert_eq!(error.kind(), DecodeErrorKind::InvalidUtf8);
+    }
+
     #[test]
     fn test_deep_nesting() {
         fn build_and_roundtrip(depth: usize) -> Result<(), prost::DecodeError> {
@@ -394,6 +479,77 @@ mod tests {
         assert!(build_and_roundtrip(101).is_err());
     }
 
+    #[test]
+    fn test_custom_recursion_limit() {
+        use crate::nesting::A;
+        use prost::encoding::DecodeContext;
+
+        let mut a = Box::new(A::default());
+        for _ in 0..10 {
+            let mut next = Box::new(A::default());
+            next.a = Some(a);
+            a = next;
+        }
+
+        let mut buf = Vec::new();
+        a.encode(&mut buf).unwrap();
+        assert!(A::decode_with_context(&*buf, DecodeContext::with_recursion_limit(10)).is_ok());
+        let error = A::decode_with_context(&*buf, DecodeContext::with_recursion_limit(9))
+            .err()
+            .unwrap();
+        assert!(error
+            .to_string()
+            .contains("recursion limit reached (limit: 9)"));
+
+        // Each decode with a clone of a context starts from its recursion depth.
+        let ctx = DecodeContext::with_recursion_limit(10);
+        for _ in 0..3 {
+            assert!(A::decode_with_context(&*buf, ctx.clone()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_decode_element_limits() {
+        use crate::nesting::A;
+        use core::mem::size_of;
+        use prost::encoding::DecodeContext;
+
+        let mut a = A {
+            repeated_a: vec![
+                A::default(),
+                A {
+                    repeated_a: vec![A::default()],
+                    ..A::default()
+                },
+            ],
+            ..A::default()
+        };
+        a.map_a.insert(1, A::default());
+        let mut buf = Vec::new();
+        a.encode(&mut buf).unwrap();
+
+        let decode = |ctx| A::decode_with_context(&*buf, ctx);
+        assert_eq!(
+            decode(DecodeContext::default().max_elements(4)),
+            Ok(a.clone())
+        );
+        let error = decode(DecodeContext::default().max_elements(3)).unwrap_err();
+        assert!(error.to_string().contains("element limit exceeded"));
+
+        let total = 4 * size_of::<A>() + size_of::<i32>();
+        assert_eq!(
+            decode(DecodeContext::default().max_total_bytes(total)),
+            Ok(a)
+        );
+        let error = decode(DecodeContext::default().max_total_bytes(total - 1)).unwrap_err();
+        assert!(error.to_string().contains("element size limit exceeded"));
+
+        // The budget is shared by all clones of a context.
+        let ctx = DecodeContext::new().max_elements(4);
+        assert!(decode(ctx.clone()).is_ok());
+        assert!(decode(ctx).is_err());
+    }
+
     #[test]
     fn test_deep_nesting_oneof() {
         fn build_and_roundtrip(depth: usize) -> Result<(), prost::DecodeError> {
@@ -597,9 +753,119 @@ mod tests {
 
     #[test]
     fn test_proto3_presence() {
-        let msg = proto3::presence::A { b: Some(42) };
+        let msg = proto3::presence::A {
+            b: Some(42),
+            c: Some("foo".to_string()),
+        };
 
         check_message(&msg);
+
+        // Fields set to the default value are still present on the wire, and are decoded as set.
+        let msg = proto3::presence::A {
+            b: Some(0),
+            c: Some(alloc::string::String::new()),
+        };
+        let buf = msg.encode_to_vec();
+        assert_eq!(buf, [0x08, 0x00, 0x12, 0x00]);
+        assert_eq!(proto3::presence::A::decode(&*buf), Ok(msg));
+
+        // Unset fields are not encoded.
+        let msg = proto3::presence::A::default();
+        assert_eq!(msg.encoded_len(), 0);
+        assert_eq!(msg.b, None);
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        use crate::unknown_fields::{new, New, Old};
+
+        let msg = New {
+            a: 1,
+            b: "foo".to_string(),
+            c: vec![1, -2, 3],
+            d: Some(new::Nested {
+                s: "bar".to_string(),
+            }),
+            e: 42,
+            f: 1.5,
+            kind: Some(new::Kind::Y("baz".to_string())),
+        };
+        let mut buf = Vec::new();
+        msg.encode(&mut buf).unwrap();
+
+        // The unknown oneof variant is preserved along with the other unknown fields.
+        let mut old = Old::decode(&*buf).unwrap();
+        assert_eq!(old.a, 1);
+        assert_eq!(old.kind, None);
+        assert!(!old.unknown_fields.is_empty());
+        assert_eq!(old.encoded_len(), buf.len());
+
+        let mut roundtrip = Vec::new();
+        old.encode(&mut roundtrip).unwrap();
+        assert_eq!(buf, roundtrip);
+        assert_eq!(New::decode(&*roundtrip).unwrap(), msg);
+
+        old.clear();
+        assert!(old.unknown_fields.is_empty());
+    }
+
+    #[test]
+    fn test_set_semantics() {
+        use crate::set_semantics::Tagged;
+
+        let a = Tagged {
+            name: "foo".to_string(),
+            tags: vec!["x".to_string(), "y".to_string()],
+            values: vec![1, 2],
+        };
+        let mut b = a.clone();
+        b.tags.reverse();
+        assert_ne!(a, b);
+        assert!(a.semantic_eq(&b));
+
+        // Fields without set semantics are still compared in order.
+        b.values.reverse();
+        assert!(!a.semantic_eq(&b));
+    }
+
+    #[test]
+    fn test_borrowed_views() {
+        use crate::borrowed_views::{Author, Document, DocumentRef, Metadata};
+
+        let mut document = Document {
+            title: "foo".to_string(),
+            body: b"bar".to_vec(),
+            tags: vec!["x".to_string(), "y".to_string()],
+            sizes: vec![1, 2, 3],
+            author: Some(Author {
+                name: "baz".to_string(),
+                id: 42,
+            }),
+            metadata: Some(Metadata {
+                source: "qux".to_string(),
+            }),
+            labels: Default::default(),
+        };
+        document.labels.insert("k".to_string(), "v".to_string());
+        let buf = document.encode_to_vec();
+
+        let view = DocumentRef::parse(&buf).unwrap();
+        assert_eq!(view.title, "foo");
+        assert_eq!(view.body, b"bar");
+        assert_eq!(view.tags, vec!["x", "y"]);
+        assert_eq!(view.sizes, vec![1, 2, 3]);
+        let author = view.author.as_ref().unwrap();
+        assert_eq!((author.name, author.id), ("baz", 42));
+        assert_eq!(
+            Metadata::decode(view.metadata.unwrap()).unwrap(),
+            document.metadata.unwrap()
+        );
+
+        // Strings are borrowed from the encoded message.
+        let title = view.title.as_ptr();
+        assert!(title >= buf.as_ptr() && title < buf.as_ptr().wrapping_add(buf.len()));
+
+        assert!(DocumentRef::parse(&buf[..buf.len() - 1]).is_err());
     }
 
     #[test]
@@ -1,6 +1,7 @@
-use bytes::Bytes;
-use prost::alloc::{borrow::ToOwned, string::String, vec, vec::Vec};
-use prost::{Enumeration, Message, Oneof};
+use bytes::{Buf, BufMut, Bytes};
+use prost::alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
+use prost::encoding::{DecodeContext, EncodeOptions, WireType};
+use prost::{DecodeError, Enumeration, Message, Oneof};
 
 use crate::check_message;
 use crate::check_serialize_equivalent;
@@ -30,6 +31,85 @@ fn check_scalar_types() {
     check_message(&ScalarTypes::default());
 }
 
+#[test]
+fn check_clear_retains_capacity() {
+    let mut msg = ScalarTypes {
+        string: "foo".to_owned(),
+        bytes_vec: vec![1, 2, 3],
+        repeated_int32: vec![1, 2, 3],
+        optional_int32: Some(4),
+        ..ScalarTypes::default()
+    };
+    msg.clear();
+    assert_eq!(msg, ScalarTypes::default());
+    assert!(msg.string.capacity() >= 3);
+    assert!(msg.bytes_vec.capacity() >= 3);
+    assert!(msg.repeated_int32.capacity() >= 3);
+}
+
+/// Repeated scalar fields declared as packed, with the same tags as `UnpackedScalars`.
+#[derive(Clone, PartialEq, Message)]
+pub struct PackedScalars {
+    #[prost(int32, repeated, packed = "true", tag = "1")]
+    pub int32: Vec<i32>,
+    #[prost(sint64, repeated, packed = "true", tag = "2")]
+    pub sint64: Vec<i64>,
+    #[prost(fixed32, repeated, packed = "true", tag = "3")]
+    pub fixed32: Vec<u32>,
+    #[prost(double, repeated, packed = "true", tag = "4")]
+    pub double: Vec<f64>,
+    #[prost(bool, repeated, packed = "true", tag = "5")]
+    pub boolean: Vec<bool>,
+}
+
+/// Repeated scalar fields declared as unpacked, with the same tags as `PackedScalars`.
+#[derive(Clone, PartialEq, Message)]
+pub struct UnpackedScalars {
+    #[prost(int32, repeated, packed = "false", tag = "1")]
+    pub int32: Vec<i32>,
+    #[prost(sint64, repeated, packed = "false", tag = "2")]
+    pub sint64: Vec<i64>,
+    #[prost(fixed32, repeated, packed = "false", tag = "3")]
+    pub fixed32: Vec<u32>,
+    #[prost(double, repeated, packed = "false", tag = "4")]
+    pub double: Vec<f64>,
+    #[prost(bool, repeated, packed = "false", tag = "5")]
+    pub boolean: Vec<bool>,
+}
+
+#[test]
+fn check_packed_unpacked_leniency() {
+    // Decoders must accept both the packed and unpacked encoding of repeated scalar fields,
+    // regardless of how the field is declared.
+    let packed = PackedScalars {
+        int32: vec![1, -2, 300],
+        sint64: vec![-1, 2],
+        fixed32: vec![3, 4],
+        double: vec![0.5, -1.5],
+        boolean: vec![true, false],
+    };
+    let unpacked = UnpackedScalars {
+        int32: packed.int32.clone(),
+        sint64: packed.sint64.clone(),
+        fixed32: packed.fixed32.clone(),
+        double: packed.double.clone(),
+        boolean: packed.boolean.clone(),
+    };
+    let packed_buf = packed.encode_to_vec();
+    let unpacked_buf = unpacked.encode_to_vec();
+    assert_ne!(packed_buf, unpacked_buf);
+
+    assert_eq!(UnpackedScalars::decode(&*packed_buf).unwrap(), unpacked);
+    assert_eq!(PackedScalars::decode(&*unpacked_buf).unwrap(), packed);
+
+    // Both encodings may be mixed, with the elements appended in order.
+    let mut mixed = packed_buf;
+    mixed.extend_from_slice(&unpacked_buf);
+    let decoded = PackedScalars::decode(&*mixed).unwrap();
+    assert_eq!(decoded.int32, vec![1, -2, 300, 1, -2, 300]);
+    assert_eq!(decoded.boolean, vec![true, false, true, false]);
+}
+
 /// A protobuf message which contains all scalar types.
 #[derive(Clone, PartialEq, Message)]
 pub struct ScalarTypes {
@@ -275,6 +355,138 @@ pub struct TagsQualified {
     pub eleven: ::alloc::collections::BTreeMap<String, String>,
 }
 
+/// A message with a hand-written `Message` implementation, which writes its field directly.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Blob {
+    pub data: [u8; 4],
+}
+
+impl Message for Blob {
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        if self.data != [0; 4] {
+            prost::encoding::encode_key(1, WireType::ThirtyTwoBit, buf);
+            buf.put_slice(&self.data);
+        }
+    }
+
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        if tag == 1 {
+            let mut value = 0;
+            prost::encoding::fixed32::merge(wire_type, &mut value, buf, ctx)?;
+            self.data = value.to_le_bytes();
+            Ok(())
+        } else {
+            prost::encoding::skip_field(wire_type, tag, buf, ctx)
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        if self.data != [0; 4] {
+            5
+        } else {
+            0
+        }
+    }
+
+    fn clear(&mut self) {
+        self.data = [0; 4];
+    }
+}
+
+#[test]
+fn check_hand_written_message() {
+    let blob = Blob { data: *b"blob" };
+    assert_eq!(blob.encode_to_vec(), b"\x0dblob");
+    check_message(&blob);
+
+    // Encode and decode it as a message field, the same way derived messages do.
+    let mut buf = Vec::new();
+    prost::encoding::message::encode(2, &blob, &mut buf);
+    assert_eq!(buf.len(), prost::encoding::message::encoded_len(2, &blob));
+    let mut buf = &*buf;
+    let (tag, wire_type) = prost::encoding::decode_key(&mut buf).unwrap();
+    assert_eq!(tag, 2);
+    let mut roundtrip = Blob::default();
+    prost::encoding::message::merge(
+        wire_type,
+        &mut roundtrip,
+        &mut buf,
+        DecodeContext::default(),
+    )
+    .unwrap();
+    assert_eq!(roundtrip, blob);
+}
+
+/// A message which records the tags of the fields it decodes, and otherwise delegates to `M`.
+#[derive(Debug, Default)]
+pub struct Logged<M> {
+    pub inner: M,
+    pub tags: Vec<u32>,
+}
+
+impl<M> Message for Logged<M>
+where
+    M: Message,
+{
+    fn encode_raw<B>(&self, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        self.inner.encode_raw(buf)
+    }
+
+    fn merge_field<B>(
+        &mut self,
+        tag: u32,
+        wire_type: WireType,
+        buf: &mut B,
+        ctx: DecodeContext,
+    ) -> Result<(), DecodeError>
+    where
+        B: Buf,
+    {
+        self.tags.push(tag);
+        self.inner.merge_field(tag, wire_type, buf, ctx)
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.inner.encoded_len()
+    }
+
+    fn clear(&mut self) {
+        self.inner.clear();
+        self.tags.clear();
+    }
+}
+
+#[test]
+fn check_merge_field_wrapper() {
+    let basic = Basic {
+        int32: 42,
+        string: "foo".to_owned(),
+        ..Basic::default()
+    };
+    let mut buf = basic.encode_to_vec();
+    // An unknown field, which the inner message skips.
+    buf.extend_from_slice(b"\xa0\x06\x01");
+
+    let logged = Logged::<Basic>::decode(&*buf).unwrap();
+    assert_eq!(logged.tags, [1, 3, 100]);
+    assert_eq!(logged.inner, basic);
+}
+
 /// A prost message with default value.
 #[derive(Clone, PartialEq, Message)]
 pub struct DefaultValues {
@@ -293,6 +505,9 @@ pub struct DefaultValues {
     #[prost(bytes = "bytes", tag = "8", default = "b\"foo\\x00bar\"")]
     pub bytes_buf: Bytes,
 
+    #[prost(bytes = "box", tag = "9", default = "b\"foo\\x00bar\"")]
+    pub bytes_boxed: Box<[u8]>,
+
     #[prost(enumeration = "BasicEnumeration", tag = "4", default = "ONE")]
     pub enumeration: i32,
 
@@ -311,6 +526,7 @@ fn check_default_values() {
     assert_eq!(&default.string, "fourty two");
     assert_eq!(&default.bytes_vec.as_ref(), b"foo\0bar");
     assert_eq!(&default.bytes_buf.as_ref(), b"foo\0bar");
+    assert_eq!(&default.bytes_boxed.as_ref(), b"foo\0bar");
     assert_eq!(default.enumeration, BasicEnumeration::ONE as i32);
     assert_eq!(default.optional_enumeration, None);
     assert_eq!(&default.repeated_enumeration, &[]);
@@ -326,7 +542,29 @@ pub enum BasicEnumeration {
     THREE = 3,
 }
 
+#[test]
+fn check_enumeration_try_from_i32() {
+    use core::convert::TryFrom;
+
+    assert_eq!(BasicEnumeration::try_from_i32(2), Ok(BasicEnumeration::TWO));
+    assert_eq!(BasicEnumeration::try_from(3), Ok(BasicEnumeration::THREE));
+    assert_eq!(
+        BasicEnumeration::try_from_i32(42),
+        Err(prost::UnknownEnumValue(42))
+    );
+
+    let mut values = DefaultValues {
+        enumeration: 42,
+        ..Default::default()
+    };
+    assert_eq!(values.enumeration(), BasicEnumeration::ONE);
+    assert_eq!(values.try_enumeration(), Err(prost::UnknownEnumValue(42)));
+    values.set_enumeration(BasicEnumeration::THREE);
+    assert_eq!(values.try_enumeration(), Ok(BasicEnumeration::THREE));
+}
+
 #[derive(Clone, PartialEq, Message)]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Basic {
     #[prost(int32, tag = "1")]
     pub int32: i32,
@@ -362,6 +600,7 @@ pub struct Basic {
 }
 
 #[derive(Clone, PartialEq, Message)]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub struct Compound {
     #[prost(message, optional, tag = "1")]
     pub optional_message: Option<Basic>,
@@ -381,9 +620,374 @@ pub struct Compound {
 }
 
 #[derive(Clone, PartialEq, Oneof)]
+#[cfg_attr(feature = "reflect", prost(reflect))]
 pub enum BasicOneof {
     #[prost(int32, tag = "8")]
     Int(i32),
     #[prost(string, tag = "9")]
     String(String),
 }
+
+#[test]
+fn check_encode_options() {
+    let message = Compound {
+        optional_message: Some(Basic::default()),
+        ..Compound::default()
+    };
+    let default_options = EncodeOptions::new();
+    assert_eq!(
+        message.encode_to_vec_with_options(&default_options),
+        message.encode_to_vec()
+    );
+
+    // The `int32`, `string` and `enumeration` fields of both nested messages are written.
+    let options = EncodeOptions::new().emit_defaults(true);
+    let buf = message.encode_to_vec_with_options(&options);
+    assert_eq!(
+        buf,
+        b"\x0a\x06\x08\x00\x1a\x00\x28\x00\x12\x06\x08\x00\x1a\x00\x28\x00"
+    );
+    assert_eq!(Compound::decode(&*buf), Ok(message.clone()));
+
+    let mut short = [0u8; 15];
+    assert!(message
+        .encode_with_options(&mut &mut short[..], &options)
+        .is_err());
+}
+
+#[test]
+fn check_is_default() {
+    let mut basic = Basic::default();
+    assert!(basic.is_default());
+    basic.oneof = Some(BasicOneof::Int(0));
+    assert!(!basic.is_default());
+    assert_ne!(basic.encoded_len(), 0);
+
+    basic.oneof = None;
+    basic.string_btree_map.insert(String::new(), String::new());
+    assert!(!basic.is_default());
+
+    // The required message field is always encoded.
+    assert!(!Compound::default().is_default());
+}
+
+#[cfg(feature = "indexmap")]
+#[derive(Clone, PartialEq, Message)]
+pub struct IndexMapFields {
+    #[prost(index_map = "string, int32", tag = "1")]
+    pub entries: prost::indexmap::IndexMap<String, i32>,
+}
+
+#[cfg(feature = "indexmap")]
+#[test]
+fn check_index_map() {
+    let mut message = IndexMapFields::default();
+    message.entries.insert("b".to_owned(), 2);
+    message.entries.insert("a".to_owned(), 1);
+    message.entries.insert("c".to_owned(), 3);
+
+    // Entries are decoded in wire order, so the message re-encodes identically.
+    let buf = message.encode_to_vec();
+    let decoded = IndexMapFields::decode(&*buf).unwrap();
+    assert_eq!(decoded.entries.keys().collect::<Vec<_>>(), ["b", "a", "c"]);
+    assert_eq!(decoded.encode_to_vec(), buf);
+
+    // Deterministic encoding emits the entries in key order.
+    let mut sorted = Vec::new();
+    message.encode_deterministic(&mut sorted).unwrap();
+    let decoded = IndexMapFields::decode(&*sorted).unwrap();
+    assert_eq!(decoded.entries.keys().collect::<Vec<_>>(), ["a", "b", "c"]);
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RequiredFields {
+    #[prost(int32, required, tag = "1")]
+    pub id: i32,
+
+    #[prost(message, optional, tag = "2")]
+    pub inner: Option<RequiredInner>,
+
+    #[prost(message, repeated, tag = "3")]
+    pub repeated: Vec<RequiredInner>,
+
+    #[prost(btree_map = "int32, message", tag = "4")]
+    pub map: prost::alloc::collections::BTreeMap<i32, RequiredInner>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RequiredInner {
+    #[prost(string, required, tag = "1")]
+    pub name: String,
+}
+
+#[test]
+fn check_required_fields() {
+    let message = RequiredFields {
+        id: 0,
+        inner: Some(RequiredInner::default()),
+        repeated: vec![RequiredInner::default()],
+        map: Default::default(),
+    };
+    let buf = message.encode_to_vec();
+    assert_eq!(RequiredFields::decode_validated(&*buf), Ok(message));
+
+    // The `id` field is missing, and so are the `name` fields of the nested messages.
+    let buf = b"\x12\x00\x1a\x00";
+    let message = RequiredFields::decode(&buf[..]).unwrap();
+    assert_eq!(message.id, 0);
+    let error = RequiredFields::validate_encoding(buf).unwrap_err();
+    assert_eq!(error.fields(), ["inner.name", "repeated[0].name", "id"]);
+
+    let error = RequiredFields::decode_validated(&buf[..]).unwrap_err();
+    assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+
+    // A buffer which isn't contiguous is copied to be checked.
+    let error = RequiredFields::decode_validated((&buf[..2]).chain(&buf[2..])).unwrap_err();
+    assert_eq!(error.kind(), prost::DecodeErrorKind::MissingRequiredField);
+}
+
+#[test]
+fn check_required_fields_occurrences() {
+    let buf = [
+        // `id`
+        &b"\x08\x01"[..],
+        // `inner`, with its `name` set in the first of two occurrences, which are merged.
+        b"\x12\x03\x0a\x01a",
+        b"\x12\x00",
+        // `repeated`, with only the second element missing its `name`.
+        b"\x1a\x03\x0a\x01b",
+        b"\x1a\x00",
+        // `map`, with the entry for key 1 missing its `name` replaced by one which has it.
+        b"\x22\x04\x08\x01\x12\x00",
+        b"\x22\x07\x08\x01\x12\x03\x0a\x01c",
+    ]
+    .concat();
+    let message = RequiredFields::decode(&*buf).unwrap();
+    assert_eq!(message.inner.as_ref().unwrap().name, "a");
+    assert_eq!(message.repeated.len(), 2);
+    assert_eq!(message.map[&1].name, "c");
+
+    let error = RequiredFields::validate_encoding(&buf).unwrap_err();
+    assert_eq!(error.fields(), ["repeated[1].name"]);
+
+    // An entry for key 1 which is missing the `name` replaces the valid one.
+    let buf = [&buf[..], b"\x22\x04\x08\x01\x12\x00"].concat();
+    let error = RequiredFields::decode_validated(&*buf).unwrap_err();
+    assert!(error.to_string().contains("map.name"));
+}
+
+#[cfg(feature = "descriptors")]
+#[test]
+fn check_descriptors() {
+    use prost::descriptor::{Descriptor, FieldDescriptor, FieldKind};
+
+    assert_eq!(
+        Basic::field(2),
+        Some(&FieldDescriptor {
+            name: "bools",
+            json_name: "bools",
+            tag: 2,
+            wire_type: WireType::Varint,
+            kind: FieldKind::Bool,
+            repeated: true,
+            oneof: None,
+        })
+    );
+
+    let map = Basic::field(11).unwrap();
+    assert_eq!(
+        (map.name, map.json_name),
+        ("string_btree_map", "stringBtreeMap")
+    );
+    assert_eq!(map.kind, FieldKind::Map);
+    assert!(map.repeated);
+
+    // Oneof variants are listed in place of the oneof field.
+    let oneof = &Basic::fields()[Basic::fields().len() - 2..];
+    assert_eq!(oneof[0].name, "int");
+    assert_eq!(oneof[1].tag, 9);
+    assert_eq!(oneof[1].kind, FieldKind::String);
+    assert!(oneof.iter().all(|field| field.oneof == Some("oneof")));
+
+    let required = Compound::field(2).unwrap();
+    assert_eq!(required.kind, FieldKind::Message);
+    assert_eq!(required.wire_type, WireType::LengthDelimited);
+    assert!(!required.repeated);
+    assert!(Compound::field(3).unwrap().repeated);
+    assert_eq!(Compound::field(6), None);
+
+    let packed = RepeatedFloats::field(41).unwrap();
+    assert_eq!(packed.wire_type, WireType::LengthDelimited);
+    assert_eq!(packed.kind, FieldKind::Float);
+    assert_eq!(
+        RepeatedFloats::field(11).unwrap().wire_type,
+        WireType::ThirtyTwoBit
+    );
+}
+
+#[cfg(feature = "reflect")]
+#[test]
+fn check_reflect() {
+    use prost::reflect::{Reflect, ReflectError, Value};
+
+    let mut basic = Basic::default();
+    basic.set_field(1, Value::I32(42)).unwrap();
+    basic
+        .set_field(2, Value::List(vec![Value::Bool(true), Value::Bool(false)]))
+        .unwrap();
+    basic
+        .set_field(4, Value::String("optional".to_owned()))
+        .unwrap();
+    basic
+        .set_field(
+            11,
+            Value::Map(vec![(
+                Value::String("key".to_owned()),
+                Value::String("value".to_owned()),
+            )]),
+        )
+        .unwrap();
+    assert_eq!(basic.int32, 42);
+    assert_eq!(basic.bools, [true, false]);
+    assert_eq!(basic.optional_string.as_deref(), Some("optional"));
+    assert_eq!(basic.string_btree_map["key"], "value");
+    assert!(matches!(basic.get_field(1), Some(Value::I32(42))));
+    assert!(matches!(basic.get_field(4), Some(Value::String(ref s)) if s == "optional"));
+    assert!(matches!(basic.get_field(11), Some(Value::Map(ref entries)) if entries.len() == 1));
+
+    // Oneof variants are addressed by their own tags.
+    assert!(basic.get_field(9).is_none());
+    basic
+        .set_field(9, Value::String("oneof".to_owned()))
+        .unwrap();
+    assert_eq!(basic.oneof, Some(BasicOneof::String("oneof".to_owned())));
+    assert!(basic.get_field(8).is_none());
+    assert!(matches!(basic.get_field(9), Some(Value::String(ref s)) if s == "oneof"));
+
+    let mut compound = Compound::default();
+    compound
+        .set_field(1, Value::Message(Box::new(basic.clone())))
+        .unwrap();
+    assert_eq!(compound.optional_message.as_ref(), Some(&basic));
+    match compound.get_field(1) {
+        Some(Value::Message(message)) => {
+            assert!(matches!(message.get_field(1), Some(Value::I32(42))))
+        }
+        other => panic!("unexpected value: {:?}", other),
+    }
+
+    assert_eq!(
+        compound.set_field(2, Value::Message(Box::new(Compound::default()))),
+        Err(ReflectError::TypeMismatch {
+            expected: basic.type_name(),
+            found: compound.type_name(),
+        })
+    );
+    assert_eq!(
+        basic.set_field(1, Value::String(String::new())),
+        Err(ReflectError::TypeMismatch {
+            expected: "i32",
+            found: "string",
+        })
+    );
+    assert_eq!(
+        basic.set_field(12, Value::I32(0)),
+        Err(ReflectError::UnknownField(12))
+    );
+}
+
+#[test]
+fn check_oneof_trait() {
+    /// Re-decodes a oneof through the generic `Oneof` trait.
+    fn roundtrip<O>(oneof: &O, tag: u32) -> Option<O>
+    where
+        O: Oneof,
+    {
+        let mut buf = Vec::new();
+        oneof.encode(&mut buf);
+        assert_eq!(buf.len(), oneof.encoded_len());
+
+        let mut slice = &buf[..];
+        let (decoded_tag, wire_type) = prost::encoding::decode_key(&mut slice).unwrap();
+        assert_eq!(decoded_tag, tag);
+        let mut field = None;
+        let ctx = prost::encoding::DecodeContext::default();
+        O::merge(&mut field, tag, wire_type, &mut slice, ctx).unwrap();
+        field
+    }
+
+    let int = BasicOneof::Int(42);
+    assert_eq!(roundtrip(&int, 8), Some(int));
+    let string = BasicOneof::String("foo".to_owned());
+    assert_eq!(roundtrip(&string, 9), Some(string));
+}
+
+#[cfg(feature = "encoded-len-by-field")]
+#[test]
+fn check_encoded_len_by_field() {
+    let message = Compound {
+        optional_message: Some(Basic {
+            string: "foo".to_owned(),
+            ..Basic::default()
+        }),
+        repeated_message: vec![Basic::default(); 2],
+        ..Compound::default()
+    };
+    let lens = message.encoded_len_by_field();
+    assert_eq!(
+        lens.iter().map(|&(_, len)| len).sum::<usize>(),
+        message.encoded_len()
+    );
+    // The key and length delimiter of each nested message are included.
+    assert_eq!(lens[0], ("optional_message", 7));
+    assert_eq!(lens[1], ("required_message", 2));
+    assert_eq!(lens[2], ("repeated_message", 4));
+}
+
+#[cfg(feature = "std")]
+#[derive(Clone, PartialEq, Message)]
+pub struct HashMaps {
+    #[prost(hash_map = "string, int32", tag = "1")]
+    pub string_map: ::std::collections::HashMap<String, i32>,
+
+    #[prost(hash_map = "int32, message", tag = "2")]
+    pub message_map: ::std::collections::HashMap<i32, HashMaps>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BTreeMaps {
+    #[prost(btree_map = "string, int32", tag = "1")]
+    pub string_map: prost::alloc::collections::BTreeMap<String, i32>,
+
+    #[prost(btree_map = "int32, message", tag = "2")]
+    pub message_map: prost::alloc::collections::BTreeMap<i32, BTreeMaps>,
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn check_deterministic_map_encoding() {
+    let mut hash_maps = HashMaps::default();
+    let mut btree_maps = BTreeMaps::default();
+    for i in 0..32 {
+        hash_maps.string_map.insert(format!("key{}", i), i);
+        btree_maps.string_map.insert(format!("key{}", i), i);
+    }
+    for i in 0..8 {
+        hash_maps.message_map.insert(i, hash_maps.clone());
+        btree_maps.message_map.insert(i, btree_maps.clone());
+    }
+
+    let mut hash_buf = Vec::new();
+    hash_maps.encode_deterministic(&mut hash_buf).unwrap();
+    let mut btree_buf = Vec::new();
+    btree_maps.encode(&mut btree_buf).unwrap();
+    assert_eq!(hash_buf, btree_buf);
+    assert_eq!(hash_maps.encode_deterministic_to_vec(), hash_buf);
+
+    let mut roundtrip = Vec::new();
+    HashMaps::decode(&*hash_buf)
+        .unwrap()
+        .encode_deterministic(&mut roundtrip)
+        .unwrap();
+    assert_eq!(hash_buf, roundtrip);
+}
@@ -0,0 +1,193 @@
+use alloc::collections::BTreeMap;
+use prost::alloc::{string::String, vec, vec::Vec};
+use prost::text_format::{
+    parse, parse_with_options, to_string, to_string_with_options, ParseOptions, TextFormatOptions,
+};
+use prost::{Enumeration, Message, Oneof};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Enumeration)]
+pub enum Color {
+    #[prost(name = "COLOR_RED")]
+    Red = 0,
+    Green = 1,
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(text_format)]
+pub struct Nested {
+    #[prost(int32, tag = "1")]
+    pub value: i32,
+}
+
+#[derive(Clone, PartialEq, Oneof)]
+#[prost(text_format)]
+pub enum Choice {
+    #[prost(string, tag = "8")]
+    TextValue(String),
+    #[prost(message, tag = "9")]
+    NestedValue(Nested),
+}
+
+#[derive(Clone, PartialEq, Message)]
+#[prost(text_format)]
+pub struct Sample {
+    #[prost(bytes = "vec", tag = "3")]
+    pub payload: Vec<u8>,
+    #[prost(int64, tag = "1")]
+    pub big_number: i64,
+    #[prost(double, tag = "2")]
+    pub ratio: f64,
+    #[prost(enumeration = "Color", repeated, tag = "4")]
+    pub colors: Vec<i32>,
+    #[prost(message, optional, tag = "5")]
+    pub nested: Option<Nested>,
+    #[prost(string, optional, tag = "6")]
+    pub label: Option<String>,
+    #[prost(btree_map = "string, int32", tag = "7")]
+    pub lookup: BTreeMap<String, i32>,
+    #[prost(oneof = "Choice", tags = "8, 9")]
+    pub choice: Option<Choice>,
+    #[prost(message, repeated, tag = "10")]
+    pub children: Vec<Nested>,
+}
+
+fn sample() -> Sample {
+    let mut lookup = BTreeMap::new();
+    lookup.insert("b".into(), 2);
+    lookup.insert("a".into(), 1);
+    Sample {
+        payload: b"\x00\"ok\"".to_vec(),
+        big_number: -5,
+        ratio: 0.0,
+        colors: vec![Color::Green as i32, 7],
+        nested: Some(Nested { value: 0 }),
+        label: Some(String::new()),
+        lookup,
+        choice: Some(Choice::NestedValue(Nested { value: 2 })),
+        children: vec![Nested { value: 3 }, Nested::default()],
+    }
+}
+
+#[test]
+fn test_to_string() {
+    let expected = r#"big_number: -5
+payload: "\000\"ok\""
+colors: Green
+colors: 7
+nested {
+}
+label: ""
+lookup {
+  key: "a"
+  value: 1
+}
+lookup {
+  key: "b"
+  value: 2
+}
+nested_value {
+  value: 2
+}
+children {
+  value: 3
+}
+children {
+}
+"#;
+    assert_eq!(to_string(&sample()), expected);
+    assert_eq!(to_string(&Sample::default()), "");
+}
+
+#[test]
+fn test_to_string_with_options() {
+    let msg = Sample {
+        colors: vec![Color::Red as i32],
+        choice: Some(Choice::TextValue("x\ny".into())),
+        children: vec![Nested { value: 1 }],
+        ..Sample::default()
+    };
+
+    let compact = TextFormatOptions::new().pretty(false);
+    assert_eq!(
+        to_string_with_options(&msg, compact),
+        r#"colors: COLOR_RED text_value: "x\ny" children { value: 1 }"#
+    );
+
+    let indented = TextFormatOptions::new().indent(4);
+    assert_eq!(
+        to_string_with_options(&msg, indented),
+        "colors: COLOR_RED\ntext_value: \"x\\ny\"\nchildren {\n    value: 1\n}\n"
+    );
+}
+
+#[test]
+fn test_generated_to_string() {
+    use crate::well_known_types::Foo;
+
+    let msg = Foo {
+        int64: Some(42),
+        string: Some(String::new()),
+        timestamp: Some(::prost_types::Timestamp {
+            seconds: 1,
+            nanos: 0,
+        }),
+        ..Foo::default()
+    };
+    let compact = TextFormatOptions::new().pretty(false);
+    assert_eq!(
+        to_string_with_options(&msg, compact),
+        "timestamp { seconds: 1 } int64 { value: 42 } string { }"
+    );
+}
+
+#[test]
+fn test_parse() {
+    let msg = sample();
+    assert_eq!(parse::<Sample>(&to_string(&msg)), Ok(msg.clone()));
+    let compact = TextFormatOptions::new().pretty(false);
+    assert_eq!(
+        parse::<Sample>(&to_string_with_options(&msg, compact)),
+        Ok(msg)
+    );
+
+    let input = r#"
+        # Lists, angle brackets and optional separators.
+        colors: [COLOR_RED, 1, 7]
+        children: [{ value: 1 }, < value: 2 >];
+        lookup { key: "a" value: 1 }, lookup: [{ key: "b" }]
+        text_value: "x" 'y'
+        ratio: -1.5e3
+    "#;
+    let msg = Sample {
+        colors: vec![Color::Red as i32, Color::Green as i32, 7],
+        children: vec![Nested { value: 1 }, Nested { value: 2 }],
+        lookup: vec![("a".into(), 1), ("b".into(), 0)].into_iter().collect(),
+        choice: Some(Choice::TextValue("xy".into())),
+        ratio: -1500.0,
+        ..Sample::default()
+    };
+    assert_eq!(parse::<Sample>(input), Ok(msg));
+}
+
+#[test]
+fn test_parse_errors() {
+    let input = "big_number: 1\nnested { value: 1 other: 2 }";
+    let error = parse::<Sample>(input).unwrap_err();
+    assert_eq!((error.line(), error.column()), (2, 19));
+    assert!(error.to_string().contains("unknown field `other`"));
+
+    let options = ParseOptions::new().allow_unknown(true);
+    assert_eq!(
+        parse_with_options::<Sample>(input, options),
+        Ok(Sample {
+            big_number: 1,
+            nested: Some(Nested { value: 1 }),
+            ..Sample::default()
+        })
+    );
+
+    let error = parse::<Sample>("colors: BLUE").unwrap_err();
+    assert_eq!((error.line(), error.column()), (1, 9));
+    let error = parse::<Sample>("nested { value: 1").unwrap_err();
+    assert_eq!((error.line(), error.column()), (1, 18));
+}
@@ -0,0 +1,83 @@
+//! Counts the allocations made while encoding. This needs a global allocator, so it is kept in a
+//! test binary of its own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use prost::Message;
+
+/// An allocator which counts allocations and reallocations while `COUNTING` is set.
+struct Counting;
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.load(Ordering::SeqCst) {
+            ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        if COUNTING.load(Ordering::SeqCst) {
+            ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        }
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Returns the result of `f`, along with the number of allocations it made.
+fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    ALLOCATIONS.store(0, Ordering::SeqCst);
+    COUNTING.store(true, Ordering::SeqCst);
+    let result = f();
+    COUNTING.store(false, Ordering::SeqCst);
+    (result, ALLOCATIONS.load(Ordering::SeqCst))
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Packed {
+    #[prost(int64, repeated, packed = "true", tag = "1")]
+    values: Vec<i64>,
+    #[prost(fixed32, repeated, packed = "true", tag = "2")]
+    fixed: Vec<u32>,
+}
+
+#[test]
+fn encode_large_packed_fields_allocates_once() {
+    let message = Packed {
+        values: (0..100_000).map(|value| value * 1_000_003).collect(),
+        fixed: (0..100_000).collect(),
+    };
+    let len = message.encoded_len();
+
+    let (buf, allocations) = count_allocations(|| message.encode_to_vec());
+    assert_eq!(allocations, 1);
+    assert_eq!(buf.len(), len);
+
+    let (buf, allocations) = count_allocations(|| message.encode_length_delimited_to_vec());
+    assert_eq!(allocations, 1);
+    assert_eq!(
+        buf.len(),
+        len + prost::encoding::encoded_len_varint(len as u64)
+    );
+
+    // A buffer which reserves `encoded_len` up front isn't grown while encoding.
+    let (buf, allocations) = count_allocations(|| {
+        let mut buf = Vec::new();
+        buf.reserve(message.encoded_len());
+        message.encode(&mut buf).unwrap();
+        buf
+    });
+    assert_eq!(allocations, 1);
+    assert_eq!(buf.len(), len);
+}